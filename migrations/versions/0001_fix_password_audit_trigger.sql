@@ -0,0 +1,19 @@
+-- Fix the password audit trigger on auth.users.
+-- auth.users has no user_id column, so any UPDATE that changed password_hash
+-- (e.g. rehashing with stronger Argon2 parameters) failed inside the trigger.
+
+CREATE OR REPLACE FUNCTION audit_password_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF OLD.password_hash IS DISTINCT FROM NEW.password_hash THEN
+        INSERT INTO auth.password_history (user_id, password_hash)
+        VALUES (NEW.id, NEW.password_hash);
+        
+        INSERT INTO auth.security_events (event_type, user_id, success)
+        VALUES ('password_change', NEW.id, TRUE);
+        
+        NEW.password_updated_at = NOW();
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
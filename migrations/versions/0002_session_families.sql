@@ -0,0 +1,12 @@
+-- Refresh token rotation.
+-- Every refresh creates a new session row; rows produced from the same login
+-- share a family_id so that reuse of an already rotated refresh token can
+-- revoke the whole chain.
+
+ALTER TABLE auth.sessions ADD COLUMN family_id UUID NULL;
+
+UPDATE auth.sessions SET family_id = session_id WHERE family_id IS NULL;
+
+ALTER TABLE auth.sessions ALTER COLUMN family_id SET NOT NULL;
+
+CREATE INDEX idx_sessions_family_id ON auth.sessions(family_id);
@@ -0,0 +1,5 @@
+-- Account lockout.
+-- Remembers the state a user was in before being locked so that it can be
+-- restored once the lockout expires or the user signs in successfully.
+
+ALTER TABLE auth.users ADD COLUMN state_before_lock VARCHAR(50) NULL;
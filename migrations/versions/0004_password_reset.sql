@@ -0,0 +1,31 @@
+-- Password reset (forgot password) flow.
+
+-- Single-use reset tokens; only a SHA-256 hash of the token is stored
+CREATE TABLE auth.password_reset_tokens (
+    token_id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES auth.users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    requested_ip INET NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_password_reset_tokens_user_id ON auth.password_reset_tokens(user_id);
+CREATE INDEX idx_password_reset_tokens_expires_at ON auth.password_reset_tokens(expires_at);
+
+-- Security events are now recorded by the application, which knows the
+-- request context (ip, user agent, how the password was changed). The trigger
+-- keeps maintaining password history and password_updated_at.
+CREATE OR REPLACE FUNCTION audit_password_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF OLD.password_hash IS DISTINCT FROM NEW.password_hash THEN
+        INSERT INTO auth.password_history (user_id, password_hash)
+        VALUES (NEW.id, NEW.password_hash);
+        
+        NEW.password_updated_at = NOW();
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
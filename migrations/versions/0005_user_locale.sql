@@ -0,0 +1,4 @@
+-- Preferred locale of a user, e.g. "en" or "pt-br".
+-- Selects the language of transactional emails; NULL uses EMAIL_DEFAULT_LOCALE.
+
+ALTER TABLE auth.users ADD COLUMN locale VARCHAR(35) NULL;
@@ -0,0 +1,24 @@
+-- Email outbox.
+-- Transactional emails are queued here in the same transaction as the change
+-- that triggers them and delivered by a background worker, so that mail is
+-- neither lost on failure nor on restart.
+--
+-- payload holds what the template needs, e.g. a verification token, and is
+-- cleared once the email has been sent.
+
+CREATE TABLE auth.email_outbox (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES auth.users(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL,
+    payload JSONB NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, sent, dead
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX idx_email_outbox_due ON auth.email_outbox(next_attempt_at)
+    WHERE status = 'pending';
+CREATE INDEX idx_email_outbox_user_id ON auth.email_outbox(user_id);
@@ -0,0 +1,14 @@
+-- Fine-grained permissions granted through roles.
+-- Permissions are "resource:action" strings such as "course:grade"; a "*"
+-- segment is a wildcard ("course:*"), and "*" alone grants everything.
+
+CREATE TABLE auth.role_permissions (
+    role_id UUID NOT NULL REFERENCES auth.roles(id) ON DELETE CASCADE,
+    permission VARCHAR(100) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (role_id, permission)
+);
+
+-- System administrators may do anything
+INSERT INTO auth.role_permissions (role_id, permission)
+SELECT id, '*' FROM auth.roles WHERE role_name = 'system_admin';
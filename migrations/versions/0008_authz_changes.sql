@@ -0,0 +1,49 @@
+-- Log of changes that affect authorization decisions.
+-- Services caching decisions from /authz/check poll /authz/changes (or
+-- LISTEN on the authz_changes channel) and drop what a change touches:
+-- entries of user_id, or of every holder of role_id.
+
+CREATE TABLE auth.authz_changes (
+    change_id BIGSERIAL PRIMARY KEY,
+    user_id UUID NULL,
+    role_id UUID NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_authz_changes_changed_at ON auth.authz_changes(changed_at);
+
+CREATE OR REPLACE FUNCTION record_authz_change()
+RETURNS TRIGGER AS $$
+DECLARE
+    changed_user UUID;
+    changed_role UUID;
+    new_change_id BIGINT;
+BEGIN
+    IF TG_TABLE_NAME = 'user_roles' THEN
+        changed_user := COALESCE(NEW.user_id, OLD.user_id);
+    ELSIF TG_TABLE_NAME = 'role_permissions' THEN
+        changed_role := COALESCE(NEW.role_id, OLD.role_id);
+    ELSE
+        changed_role := COALESCE(NEW.id, OLD.id);
+    END IF;
+
+    INSERT INTO auth.authz_changes (user_id, role_id)
+    VALUES (changed_user, changed_role)
+    RETURNING change_id INTO new_change_id;
+
+    PERFORM pg_notify('authz_changes', new_change_id::TEXT);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER user_roles_authz_change
+AFTER INSERT OR UPDATE OR DELETE ON auth.user_roles
+FOR EACH ROW EXECUTE FUNCTION record_authz_change();
+
+CREATE TRIGGER role_permissions_authz_change
+AFTER INSERT OR UPDATE OR DELETE ON auth.role_permissions
+FOR EACH ROW EXECUTE FUNCTION record_authz_change();
+
+CREATE TRIGGER roles_authz_change
+AFTER UPDATE OF role_name OR DELETE ON auth.roles
+FOR EACH ROW EXECUTE FUNCTION record_authz_change();
@@ -0,0 +1,53 @@
+-- Role hierarchy. A role holds every permission of the roles it inherits,
+-- transitively: tenant_admin inherits teacher, which inherits staff.
+-- The graph must stay acyclic; the application checks this before adding
+-- an edge.
+
+CREATE TABLE auth.role_inheritance (
+    role_id UUID NOT NULL REFERENCES auth.roles(id) ON DELETE CASCADE,
+    inherited_role_id UUID NOT NULL REFERENCES auth.roles(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (role_id, inherited_role_id),
+    CONSTRAINT no_self_inheritance CHECK (role_id <> inherited_role_id)
+);
+
+CREATE INDEX idx_role_inheritance_inherited_role_id ON auth.role_inheritance(inherited_role_id);
+
+INSERT INTO auth.role_inheritance (role_id, inherited_role_id)
+SELECT senior.id, junior.id
+FROM auth.roles senior, auth.roles junior
+WHERE (senior.role_name, junior.role_name) IN (
+    ('tenant_admin', 'teacher'),
+    ('teacher', 'staff')
+);
+
+-- Hierarchy changes are authorization changes of the inheriting role.
+-- Since roles inherit transitively, a role change in the log may affect
+-- holders of any role above it.
+CREATE OR REPLACE FUNCTION record_authz_change()
+RETURNS TRIGGER AS $$
+DECLARE
+    changed_user UUID;
+    changed_role UUID;
+    new_change_id BIGINT;
+BEGIN
+    IF TG_TABLE_NAME = 'user_roles' THEN
+        changed_user := COALESCE(NEW.user_id, OLD.user_id);
+    ELSIF TG_TABLE_NAME IN ('role_permissions', 'role_inheritance') THEN
+        changed_role := COALESCE(NEW.role_id, OLD.role_id);
+    ELSE
+        changed_role := COALESCE(NEW.id, OLD.id);
+    END IF;
+
+    INSERT INTO auth.authz_changes (user_id, role_id)
+    VALUES (changed_user, changed_role)
+    RETURNING change_id INTO new_change_id;
+
+    PERFORM pg_notify('authz_changes', new_change_id::TEXT);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER role_inheritance_authz_change
+AFTER INSERT OR UPDATE OR DELETE ON auth.role_inheritance
+FOR EACH ROW EXECUTE FUNCTION record_authz_change();
@@ -0,0 +1,20 @@
+-- Tenant-scoped role assignments.
+-- A user may hold different roles at different institutions, e.g. teacher
+-- at one school and parent at another. Assignments with a NULL tenant_id
+-- are global and apply in every tenant (system_admin).
+
+ALTER TABLE auth.user_roles
+    ADD COLUMN tenant_id UUID NULL REFERENCES auth.education_tenants(tenant_id) ON DELETE CASCADE;
+
+ALTER TABLE auth.user_roles DROP CONSTRAINT user_roles_pkey;
+
+ALTER TABLE auth.user_roles
+    ADD CONSTRAINT user_roles_user_role_tenant_key
+    UNIQUE NULLS NOT DISTINCT (user_id, role_id, tenant_id);
+
+CREATE INDEX idx_user_roles_tenant_id ON auth.user_roles(tenant_id);
+
+-- Tenant a session acts in; carried into the access tokens it issues.
+-- NULL when the user belongs to no tenant.
+ALTER TABLE auth.sessions
+    ADD COLUMN tenant_id UUID NULL REFERENCES auth.education_tenants(tenant_id) ON DELETE SET NULL;
@@ -0,0 +1,26 @@
+-- TOTP multi-factor authentication.
+
+-- Time step of the last accepted TOTP code; a code is only accepted for a
+-- later step, so an observed code cannot be replayed
+ALTER TABLE auth.mfa_methods ADD COLUMN last_used_step BIGINT NULL;
+
+-- Pending second factor of a login. Issued once the password checked out
+-- and exchanged for a session when a valid code is presented. Only a
+-- SHA-256 hash of the challenge token is stored.
+CREATE TABLE auth.mfa_challenges (
+    challenge_id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES auth.users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    -- the client that signed in, used for the session once the code is verified
+    device_identifier VARCHAR(255) NULL,
+    device_name VARCHAR(255) NULL,
+    device_type VARCHAR(50) NULL,
+    ip_address INET NULL,
+    user_agent TEXT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_mfa_challenges_user_id ON auth.mfa_challenges(user_id);
+CREATE INDEX idx_mfa_challenges_expires_at ON auth.mfa_challenges(expires_at);
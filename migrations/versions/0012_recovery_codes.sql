@@ -0,0 +1,10 @@
+-- MFA recovery codes.
+
+-- A user's recovery codes are represented by a single 'recovery' method,
+-- which counts wrong codes like any other method. The codes themselves
+-- live in auth.recovery_codes.
+CREATE UNIQUE INDEX idx_mfa_methods_user_recovery ON auth.mfa_methods(user_id)
+    WHERE method_type = 'recovery';
+
+-- Codes are looked up by the hash of what the user typed
+CREATE UNIQUE INDEX idx_recovery_codes_user_code ON auth.recovery_codes(user_id, code_hash);
@@ -0,0 +1,9 @@
+-- Email and SMS one-time codes as MFA methods.
+
+-- The code currently outstanding for an email or SMS method. Only a keyed
+-- hash is stored; it is cleared once the code is used.
+ALTER TABLE auth.mfa_methods
+    ADD COLUMN code_hash VARCHAR(64) NULL,
+    ADD COLUMN code_expires_at TIMESTAMPTZ NULL,
+    -- when the last code was sent, for resend throttling
+    ADD COLUMN code_sent_at TIMESTAMPTZ NULL;
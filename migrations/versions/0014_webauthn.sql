@@ -0,0 +1,49 @@
+-- WebAuthn credentials (passkeys and security keys).
+
+ALTER TABLE auth.mfa_methods DROP CONSTRAINT valid_method_type;
+ALTER TABLE auth.mfa_methods ADD CONSTRAINT valid_method_type
+    CHECK (method_type IN ('totp', 'sms', 'email', 'recovery', 'webauthn'));
+
+-- Like recovery codes, a user's credentials are represented by a single
+-- 'webauthn' method, which counts failed assertions. It exists while the
+-- user has at least one credential.
+CREATE UNIQUE INDEX idx_mfa_methods_user_webauthn ON auth.mfa_methods(user_id)
+    WHERE method_type = 'webauthn';
+
+CREATE TABLE auth.webauthn_credentials (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES auth.users(id) ON DELETE CASCADE,
+    -- the id chosen by the authenticator
+    credential_id BYTEA NOT NULL UNIQUE,
+    -- COSE_Key as registered
+    public_key BYTEA NOT NULL,
+    -- COSE algorithm identifier, e.g. -7 for ES256
+    algorithm INTEGER NOT NULL,
+    sign_count BIGINT NOT NULL DEFAULT 0,
+    transports TEXT[] NOT NULL DEFAULT '{}',
+    -- authenticator model, all zeros when not disclosed
+    aaguid UUID NOT NULL,
+    name VARCHAR(100) NULL,
+    backup_eligible BOOLEAN NOT NULL DEFAULT FALSE,
+    backed_up BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX idx_webauthn_credentials_user_id ON auth.webauthn_credentials(user_id);
+
+-- Challenges of registration and authentication ceremonies. Each is
+-- answered at most once. Only a SHA-256 hash of the challenge is stored.
+CREATE TABLE auth.webauthn_challenges (
+    challenge_id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    -- NULL for passkey logins, where the user is only known from the answer
+    user_id UUID NULL REFERENCES auth.users(id) ON DELETE CASCADE,
+    challenge_hash VARCHAR(64) NOT NULL UNIQUE,
+    ceremony VARCHAR(20) NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT valid_ceremony CHECK (ceremony IN ('registration', 'authentication'))
+);
+
+CREATE INDEX idx_webauthn_challenges_expires_at ON auth.webauthn_challenges(expires_at);
@@ -0,0 +1,10 @@
+-- Tenant-enforced MFA.
+
+-- A login by a user whose roles in the tenant are listed in
+-- education_tenants.mfa_required_roles, and who has no second factor yet,
+-- is held in a challenge until a first method is enrolled. The enrollment
+-- token handed out for it is a restricted JWT; only its hash is stored.
+ALTER TABLE auth.mfa_challenges
+    ADD COLUMN purpose VARCHAR(20) NOT NULL DEFAULT 'verification',
+    ADD CONSTRAINT valid_challenge_purpose
+        CHECK (purpose IN ('verification', 'enrollment'));
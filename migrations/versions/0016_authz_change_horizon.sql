@@ -0,0 +1,15 @@
+-- Commit horizon for the authorization change log.
+-- change_id is handed out when a change is written, not when it commits, so
+-- a change can become visible after one with a higher id was already
+-- polled. Each change now records the transaction that wrote it; pollers
+-- read up to the oldest transaction still running, below which no change
+-- can appear any more.
+--
+-- This replaces paging by change_id described in 0008: /authz/changes
+-- hands out a version to poll from next, and the change_id still sent on
+-- the authz_changes channel only tells listeners to poll, not where from.
+
+ALTER TABLE auth.authz_changes
+    ADD COLUMN txid XID8 NOT NULL DEFAULT pg_current_xact_id();
+
+CREATE INDEX idx_authz_changes_txid ON auth.authz_changes(txid);
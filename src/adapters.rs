@@ -1 +1,3 @@
 pub mod dtos;
+pub mod email_transports;
+pub mod sms_senders;
@@ -1,9 +1,12 @@
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use uuid::Uuid;
 
 pub struct RegistrationDto {
     pub email: String,
     pub password: Option<String>,
+    pub locale: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -12,3 +15,199 @@ pub struct RegisteredUserDto {
     pub email: String,
     pub auth_provider: String,
 }
+
+// Request metadata recorded against logins and sessions
+#[derive(Debug, Clone, Default)]
+pub struct ClientContextDto {
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub device_identifier: Option<String>,
+    pub device_name: Option<String>,
+    pub device_type: Option<String>,
+}
+
+// What a strategy needs to sign a user in: an email and password, or a
+// WebAuthn assertion for passkey logins
+pub struct AuthenticationDto {
+    pub email: Option<String>,
+    pub password: Option<String>,
+    pub assertion: Option<WebauthnAssertionDto>,
+    pub client: ClientContextDto,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthTokensDto {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: i64, // access token lifetime in seconds
+}
+
+// Returned by login instead of tokens while a second factor is due
+#[derive(Debug, Serialize)]
+pub struct MfaChallengeDto {
+    pub mfa_required: bool,
+    pub challenge_token: String,
+    pub methods: Vec<String>,
+    pub expires_in: i64, // in seconds
+}
+
+// Returned by login instead of tokens when the tenant requires MFA for
+// some of the user's roles and no method is enrolled yet. The enrollment
+// token is a bearer token that only the MFA enrollment endpoints accept;
+// confirming a first method with it answers with the session tokens.
+#[derive(Debug, Serialize)]
+pub struct MfaEnrollmentRequiredDto {
+    pub mfa_enrollment_required: bool,
+    pub enrollment_token: String,
+    pub roles: Vec<String>,   // the roles requiring MFA
+    pub methods: Vec<String>, // methods that can be enrolled
+    pub expires_in: i64,      // in seconds
+}
+
+// A TOTP secret to add to an authenticator app. otpauth_uri is the
+// payload of the QR code apps scan.
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollmentDto {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+// Where a one-time code went. The destination is masked so it can be
+// shown before the user has signed in.
+#[derive(Debug, Serialize)]
+pub struct MfaCodeSentDto {
+    pub method: String,
+    pub destination: String,
+    pub expires_in: i64, // in seconds
+}
+
+// Email outbox counts by status plus the retries it took, from the table
+#[derive(Debug, Serialize)]
+pub struct EmailOutboxStatsDto {
+    pub pending: i64,
+    pub sent: i64,
+    pub dead: i64,
+    pub retried: i64,
+}
+
+// WebAuthn ceremony options and responses, in the JSON form of the Web
+// Authentication API (PublicKeyCredential.toJSON and
+// parseCreationOptionsFromJSON / parseRequestOptionsFromJSON). Binary
+// values are base64url encoded without padding.
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnCreationOptionsDto {
+    pub challenge: String,
+    pub rp: WebauthnRelyingPartyDto,
+    pub user: WebauthnUserEntityDto,
+    pub pub_key_cred_params: Vec<WebauthnCredentialParametersDto>,
+    pub timeout: i64, // in milliseconds
+    pub exclude_credentials: Vec<WebauthnCredentialDescriptorDto>,
+    pub authenticator_selection: WebauthnAuthenticatorSelectionDto,
+    pub attestation: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnRequestOptionsDto {
+    pub challenge: String,
+    pub timeout: i64, // in milliseconds
+    pub rp_id: String,
+    // empty for passkey logins, letting the authenticator offer its passkeys
+    pub allow_credentials: Vec<WebauthnCredentialDescriptorDto>,
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebauthnRelyingPartyDto {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnUserEntityDto {
+    // user handle, returned by authenticators with passkey assertions
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebauthnCredentialParametersDto {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebauthnCredentialDescriptorDto {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub transports: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnAuthenticatorSelectionDto {
+    pub resident_key: String,
+    pub require_resident_key: bool,
+    pub user_verification: String,
+}
+
+// Answer to a registration ceremony
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnRegistrationDto {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub response: WebauthnAttestationResponseDto,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnAttestationResponseDto {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub attestation_object: String,
+    #[serde(default)]
+    pub transports: Vec<String>,
+}
+
+// Answer to an authentication ceremony
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnAssertionDto {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub response: WebauthnAssertionResponseDto,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebauthnAssertionResponseDto {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+    pub user_handle: Option<String>,
+}
+
+// A registered credential as shown to its owner
+#[derive(Debug, Serialize)]
+pub struct WebauthnCredentialDto {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub aaguid: Uuid,
+    pub transports: Vec<String>,
+    pub backup_eligible: bool,
+    pub backed_up: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,26 @@
+mod base_email_transport;
+mod file_email_transport;
+mod memory_email_transport;
+mod smtp_email_transport;
+
+pub use base_email_transport::EmailTransport;
+pub use file_email_transport::FileEmailTransport;
+pub use memory_email_transport::MemoryEmailTransport;
+pub use smtp_email_transport::SmtpEmailTransport;
+
+use std::sync::Arc;
+
+use crate::config::app_config::AppConfig;
+
+// Selects the transport named by EMAIL_TRANSPORT
+pub fn configure_email_transport(config: &AppConfig) -> Arc<dyn EmailTransport> {
+    match config.email_transport.to_lowercase().as_str() {
+        "smtp" => Arc::new(SmtpEmailTransport::new(config)),
+        "file" => Arc::new(FileEmailTransport::new(&config.email_spool_dir)),
+        "memory" => Arc::new(MemoryEmailTransport::new()),
+        other => panic!(
+            "EMAIL_TRANSPORT must be one of smtp, file, memory (got {})",
+            other
+        ),
+    }
+}
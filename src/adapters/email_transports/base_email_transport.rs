@@ -0,0 +1,41 @@
+use anyhow::anyhow;
+use lettre::Message;
+use lettre::message::MultiPart;
+use lettre::message::header::ContentType;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::EmailMessage;
+
+// Email Transport Trait
+#[async_trait::async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> Result<(), UserError>;
+}
+
+// Builds the MIME message shared by the transports that produce real mail
+pub(super) fn build_mime_message(message: &EmailMessage) -> Result<Message, UserError> {
+    let builder = Message::builder()
+        .from(
+            message
+                .from
+                .parse()
+                .map_err(|e| anyhow!("Invalid sender address: {}", e))?,
+        )
+        .to(message
+            .to
+            .parse()
+            .map_err(|e| anyhow!("Invalid recipient address: {}", e))?)
+        .subject(&message.subject);
+
+    let mime_message = match &message.html_body {
+        Some(html_body) => builder.multipart(MultiPart::alternative_plain_html(
+            message.text_body.clone(),
+            html_body.clone(),
+        )),
+        None => builder
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.text_body.clone()),
+    };
+
+    Ok(mime_message.map_err(|e| anyhow!("Failed to build email: {}", e))?)
+}
@@ -0,0 +1,50 @@
+// File Email Transport
+// Spools every message into a Maildir (tmp/, new/, cur/) so that it can be
+// opened with any mail client or inspected by scripts.
+
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::EmailMessage;
+
+use super::base_email_transport::{EmailTransport, build_mime_message};
+
+pub struct FileEmailTransport {
+    spool_dir: PathBuf,
+}
+
+impl FileEmailTransport {
+    pub fn new(spool_dir: impl Into<PathBuf>) -> Self {
+        let spool_dir = spool_dir.into();
+        for subdir in ["tmp", "new", "cur"] {
+            std::fs::create_dir_all(spool_dir.join(subdir))
+                .unwrap_or_else(|e| panic!("Failed to create mail spool {:?}: {}", spool_dir, e));
+        }
+        Self { spool_dir }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for FileEmailTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), UserError> {
+        let contents = build_mime_message(message)?.formatted();
+
+        // written to tmp/ first and moved into new/, so readers never see a
+        // partially written message
+        let file_name = format!("{}.{}.gandalf", Utc::now().timestamp(), Uuid::new_v4());
+        let tmp_path = self.spool_dir.join("tmp").join(&file_name);
+        let new_path = self.spool_dir.join("new").join(&file_name);
+
+        tokio::fs::write(&tmp_path, contents)
+            .await
+            .map_err(|e| anyhow!("Failed to spool email: {}", e))?;
+        tokio::fs::rename(&tmp_path, &new_path)
+            .await
+            .map_err(|e| anyhow!("Failed to spool email: {}", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+// In-Memory Email Transport
+// Captures messages instead of delivering them, for tests.
+
+use std::sync::Mutex;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::EmailMessage;
+
+use super::base_email_transport::EmailTransport;
+
+#[derive(Default)]
+pub struct MemoryEmailTransport {
+    messages: Mutex<Vec<EmailMessage>>,
+}
+
+impl MemoryEmailTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // messages sent so far, oldest first
+    #[cfg(test)]
+    pub fn sent_messages(&self) -> Vec<EmailMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for MemoryEmailTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), UserError> {
+        self.messages.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+// SMTP Email Transport
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+
+use crate::config::app_config::AppConfig;
+use crate::domain::errors::UserError;
+use crate::domain::models::EmailMessage;
+
+use super::base_email_transport::{EmailTransport, build_mime_message};
+
+// How the connection to the SMTP server is secured
+pub enum SmtpTls {
+    // plain connection upgraded with STARTTLS (usually port 587)
+    StartTls,
+    // TLS from the first byte (usually port 465)
+    Implicit,
+    // no encryption, only meant for local SMTP sinks
+    None,
+}
+
+impl FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "starttls" => Ok(SmtpTls::StartTls),
+            "implicit" | "tls" => Ok(SmtpTls::Implicit),
+            "none" => Ok(SmtpTls::None),
+            _ => Err(format!("Unknown SMTP TLS mode: {}", s)),
+        }
+    }
+}
+
+pub struct SmtpEmailTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpEmailTransport {
+    pub fn new(config: &AppConfig) -> Self {
+        let tls: SmtpTls = config
+            .smtp_tls
+            .parse()
+            .expect("SMTP_TLS must be one of starttls, implicit, none");
+
+        let mut builder = match tls {
+            SmtpTls::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+                    .expect("SMTP_HOST must be a valid host name")
+            }
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host)
+                .expect("SMTP_HOST must be a valid host name"),
+            SmtpTls::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+            }
+        }
+        .port(config.smtp_port);
+
+        if let Some(username) = &config.smtp_username {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                config.smtp_password.clone().unwrap_or_default(),
+            ));
+        }
+
+        Self {
+            mailer: builder.build(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpEmailTransport {
+    async fn send(&self, message: &EmailMessage) -> Result<(), UserError> {
+        self.mailer
+            .send(build_mime_message(message)?)
+            .await
+            .map_err(|e| anyhow!("SMTP delivery failed: {}", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+mod base_sms_sender;
+mod http_sms_sender;
+mod memory_sms_sender;
+
+pub use base_sms_sender::SmsSender;
+pub use http_sms_sender::HttpSmsSender;
+pub use memory_sms_sender::MemorySmsSender;
+
+use std::sync::Arc;
+
+use crate::config::app_config::AppConfig;
+
+// Selects the sender named by SMS_SENDER
+pub fn configure_sms_sender(config: &AppConfig) -> Arc<dyn SmsSender> {
+    match config.sms_sender.to_lowercase().as_str() {
+        "http" => Arc::new(HttpSmsSender::new(config)),
+        "memory" => Arc::new(MemorySmsSender::new()),
+        other => panic!("SMS_SENDER must be one of http, memory (got {})", other),
+    }
+}
@@ -0,0 +1,8 @@
+use crate::domain::errors::UserError;
+use crate::domain::models::SmsMessage;
+
+// SMS Sender Trait
+#[async_trait::async_trait]
+pub trait SmsSender: Send + Sync {
+    async fn send(&self, message: &SmsMessage) -> Result<(), UserError>;
+}
@@ -0,0 +1,65 @@
+// HTTP SMS Sender
+// Posts messages as JSON to an SMS gateway:
+//
+//   POST <SMS_GATEWAY_URL>
+//   Authorization: Bearer <SMS_GATEWAY_TOKEN>
+//   { "from": "...", "to": "+15551234567", "body": "..." }
+//
+// Any 2xx response counts as accepted.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use serde_json::json;
+
+use crate::config::app_config::AppConfig;
+use crate::domain::errors::UserError;
+use crate::domain::models::SmsMessage;
+
+use super::base_sms_sender::SmsSender;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct HttpSmsSender {
+    client: reqwest::Client,
+    gateway_url: String,
+    token: Option<String>,
+}
+
+impl HttpSmsSender {
+    pub fn new(config: &AppConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to build the SMS gateway client");
+
+        Self {
+            client,
+            gateway_url: config.sms_gateway_url.clone(),
+            token: config.sms_gateway_token.clone(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsSender for HttpSmsSender {
+    async fn send(&self, message: &SmsMessage) -> Result<(), UserError> {
+        let mut request = self.client.post(&self.gateway_url).json(&json!({
+            "from": message.from,
+            "to": message.to,
+            "body": message.body,
+        }));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow!("SMS delivery failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("SMS gateway rejected the message: {}", response.status()).into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+// In-Memory SMS Sender
+// Captures messages instead of delivering them, for local development and tests.
+
+use std::sync::Mutex;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::SmsMessage;
+
+use super::base_sms_sender::SmsSender;
+
+#[derive(Default)]
+pub struct MemorySmsSender {
+    messages: Mutex<Vec<SmsMessage>>,
+}
+
+impl MemorySmsSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // messages sent so far, oldest first
+    #[cfg(test)]
+    pub fn sent_messages(&self) -> Vec<SmsMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsSender for MemorySmsSender {
+    async fn send(&self, message: &SmsMessage) -> Result<(), UserError> {
+        self.messages.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+}
@@ -1,9 +1,34 @@
+pub mod errors;
 pub mod v1;
 
+use actix_web::middleware::from_fn;
 use actix_web::web;
 
-use v1::routes::user_routes;
+use crate::app_modules::middleware::authentication::authentication;
+use crate::domain::errors::UserError;
+use v1::routes::{admin_routes, auth_routes, authz_routes, role_routes, user_routes};
 
 pub fn api_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/api/v1").configure(user_routes));
+    cfg.service(
+        web::scope("/api/v1")
+            .wrap(from_fn(authentication))
+            // malformed bodies, paths and queries get the same error envelope
+            .app_data(
+                web::JsonConfig::default()
+                    .error_handler(|err, _req| UserError::InvalidRequest(err.to_string()).into()),
+            )
+            .app_data(
+                web::PathConfig::default()
+                    .error_handler(|err, _req| UserError::InvalidRequest(err.to_string()).into()),
+            )
+            .app_data(
+                web::QueryConfig::default()
+                    .error_handler(|err, _req| UserError::InvalidRequest(err.to_string()).into()),
+            )
+            .configure(user_routes)
+            .configure(auth_routes)
+            .configure(authz_routes)
+            .configure(role_routes)
+            .configure(admin_routes),
+    );
 }
@@ -0,0 +1,242 @@
+/*
+ Maps domain errors onto HTTP responses.
+
+ Every error leaves the API in the same envelope:
+
+   {
+     "code": "INVALID_CREDENTIALS",
+     "message": "Invalid email or password",
+     "details": { ... },
+     "request_id": "..."
+   }
+
+ Domain errors describe themselves through ApiError and get their
+ ResponseError implementation from impl_response_error!. The request id
+ is filled in by the request_id middleware.
+*/
+use std::collections::BTreeMap;
+
+use actix_web::HttpResponse;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tracing::error;
+
+use crate::domain::errors::UserError;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEnvelope {
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    pub request_id: Option<String>,
+}
+
+// Describes how a domain error is presented to API clients
+pub trait ApiError: std::error::Error {
+    fn status_code(&self) -> StatusCode;
+
+    // stable, machine readable error code
+    fn error_code(&self) -> &'static str;
+
+    // message safe to show to clients
+    fn public_message(&self) -> String {
+        self.to_string()
+    }
+
+    fn details(&self) -> Option<Value> {
+        None
+    }
+
+    fn headers(&self) -> Vec<(HeaderName, HeaderValue)> {
+        Vec::new()
+    }
+}
+
+// Builds the envelope response for any ApiError
+pub fn error_response<E: ApiError + ?Sized>(e: &E) -> HttpResponse {
+    let status = e.status_code();
+    if status.is_server_error() {
+        // the cause is logged but never sent to the client
+        error!("{}: {}", e.error_code(), e);
+    }
+
+    let envelope = ErrorEnvelope {
+        code: e.error_code(),
+        message: e.public_message(),
+        details: e.details(),
+        request_id: None,
+    };
+
+    let mut builder = HttpResponse::build(status);
+    for header in e.headers() {
+        builder.insert_header(header);
+    }
+    let mut response = builder.json(&envelope);
+    response.extensions_mut().insert(envelope);
+    response
+}
+
+macro_rules! impl_response_error {
+    ($error:ty) => {
+        impl actix_web::ResponseError for $error {
+            fn status_code(&self) -> actix_web::http::StatusCode {
+                ApiError::status_code(self)
+            }
+
+            fn error_response(&self) -> actix_web::HttpResponse {
+                error_response(self)
+            }
+        }
+    };
+}
+
+impl ApiError for UserError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UserError::NotFound => StatusCode::NOT_FOUND,
+            UserError::ValidationError(_)
+            | UserError::InvalidEmail
+            | UserError::DisposableEmail
+            | UserError::InvalidRequest(_)
+            | UserError::InvalidVerificationToken
+            | UserError::InvalidResetToken
+            | UserError::InvalidMfaCode
+            | UserError::InvalidWebauthnResponse(_) => StatusCode::BAD_REQUEST,
+            UserError::UserAlreadyExists
+            | UserError::MfaAlreadyEnabled
+            | UserError::CredentialAlreadyRegistered
+            | UserError::RoleAlreadyExists
+            | UserError::SystemRoleProtected
+            | UserError::RoleCycle => StatusCode::CONFLICT,
+            UserError::InvalidCredentials
+            | UserError::Unauthorized
+            | UserError::InvalidToken
+            | UserError::TokenRevoked
+            | UserError::InvalidRefreshToken
+            | UserError::RefreshTokenReused
+            | UserError::InvalidMfaChallenge => StatusCode::UNAUTHORIZED,
+            UserError::AccountLocked { .. } => StatusCode::LOCKED,
+            UserError::MfaCodeThrottled { .. } => StatusCode::TOO_MANY_REQUESTS,
+            UserError::AccountDisabled
+            | UserError::Forbidden
+            | UserError::MfaEnrollmentRequired
+            | UserError::MfaVerificationRequired => StatusCode::FORBIDDEN,
+            UserError::TokenError(_)
+            | UserError::PasswordHashingError
+            | UserError::DatabaseError(_)
+            | UserError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_code(&self) -> &'static str {
+        match self {
+            UserError::NotFound => "NOT_FOUND",
+            UserError::ValidationError(_) => "VALIDATION_FAILED",
+            UserError::UserAlreadyExists => "USER_EXISTS",
+            UserError::RoleAlreadyExists => "ROLE_EXISTS",
+            UserError::SystemRoleProtected => "SYSTEM_ROLE",
+            UserError::RoleCycle => "ROLE_CYCLE",
+            UserError::InvalidEmail => "INVALID_EMAIL",
+            UserError::DisposableEmail => "DISPOSABLE_EMAIL",
+            UserError::InvalidCredentials => "INVALID_CREDENTIALS",
+            UserError::AccountLocked { .. } => "ACCOUNT_LOCKED",
+            UserError::AccountDisabled => "ACCOUNT_DISABLED",
+            UserError::Unauthorized => "UNAUTHORIZED",
+            UserError::Forbidden => "FORBIDDEN",
+            UserError::InvalidToken | UserError::TokenRevoked => "INVALID_TOKEN",
+            // reuse is reported like any other bad refresh token
+            UserError::InvalidRefreshToken | UserError::RefreshTokenReused => {
+                "INVALID_REFRESH_TOKEN"
+            }
+            UserError::InvalidVerificationToken => "INVALID_VERIFICATION_TOKEN",
+            UserError::InvalidResetToken => "INVALID_RESET_TOKEN",
+            UserError::InvalidMfaCode => "INVALID_MFA_CODE",
+            UserError::InvalidMfaChallenge => "INVALID_MFA_CHALLENGE",
+            UserError::MfaAlreadyEnabled => "MFA_ALREADY_ENABLED",
+            UserError::MfaEnrollmentRequired => "MFA_ENROLLMENT_REQUIRED",
+            UserError::MfaVerificationRequired => "MFA_VERIFICATION_REQUIRED",
+            UserError::MfaCodeThrottled { .. } => "MFA_CODE_THROTTLED",
+            UserError::InvalidWebauthnResponse(_) => "INVALID_WEBAUTHN_RESPONSE",
+            UserError::CredentialAlreadyRegistered => "CREDENTIAL_EXISTS",
+            UserError::InvalidRequest(_) => "INVALID_REQUEST",
+            UserError::TokenError(_)
+            | UserError::PasswordHashingError
+            | UserError::DatabaseError(_)
+            | UserError::InternalError(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    fn public_message(&self) -> String {
+        match self {
+            UserError::NotFound => "The requested resource could not be found".into(),
+            UserError::ValidationError(_) => "Validation failed".into(),
+            UserError::InvalidEmail => "Invalid email format".into(),
+            UserError::InvalidCredentials => "Invalid email or password".into(),
+            UserError::AccountLocked { .. } => {
+                "Account is temporarily locked due to repeated failed logins".into()
+            }
+            UserError::AccountDisabled => "Account is disabled".into(),
+            UserError::Unauthorized => "Missing bearer token".into(),
+            UserError::InvalidToken | UserError::TokenRevoked => {
+                "Invalid or expired access token".into()
+            }
+            UserError::InvalidRefreshToken | UserError::RefreshTokenReused => {
+                "Invalid or expired refresh token".into()
+            }
+            UserError::TokenError(_)
+            | UserError::PasswordHashingError
+            | UserError::DatabaseError(_)
+            | UserError::InternalError(_) => "An internal error occurred".into(),
+            _ => self.to_string(),
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match self {
+            UserError::ValidationError(errors) => {
+                // field name -> messages, falling back to the validator code
+                let fields: BTreeMap<String, Vec<String>> = errors
+                    .field_errors()
+                    .into_iter()
+                    .map(|(field, errors)| {
+                        let messages = errors
+                            .iter()
+                            .map(|e| {
+                                e.message
+                                    .as_ref()
+                                    .map(|message| message.to_string())
+                                    .unwrap_or_else(|| e.code.to_string())
+                            })
+                            .collect();
+                        (field.to_string(), messages)
+                    })
+                    .collect();
+                Some(json!({ "fields": fields }))
+            }
+            UserError::AccountLocked {
+                retry_after_secs: Some(retry_after),
+            }
+            | UserError::MfaCodeThrottled {
+                retry_after_secs: retry_after,
+            } => Some(json!({ "retry_after": retry_after })),
+            _ => None,
+        }
+    }
+
+    fn headers(&self) -> Vec<(HeaderName, HeaderValue)> {
+        match self {
+            UserError::AccountLocked {
+                retry_after_secs: Some(retry_after),
+            }
+            | UserError::MfaCodeThrottled {
+                retry_after_secs: retry_after,
+            } => vec![(header::RETRY_AFTER, HeaderValue::from(*retry_after))],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl_response_error!(UserError);
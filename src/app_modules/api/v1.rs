@@ -1,3 +1,9 @@
+pub mod admin_endpoints;
+pub mod auth_endpoints;
+pub mod authz_endpoints;
+pub mod mfa_endpoints;
+pub mod role_endpoints;
 pub mod routes;
 mod schemas;
 pub mod user_endpoints;
+pub mod webauthn_endpoints;
@@ -0,0 +1,21 @@
+/*
+ This module holds operator endpoints.
+
+ created modules must be registered in routes.rs
+*/
+use actix_web::{HttpResponse, get, web};
+
+use crate::app_modules::app_state::AppState;
+use crate::app_modules::auth::{RequireRole, SystemAdmin};
+use crate::domain::errors::UserError;
+
+// Email Outbox Stats Endpoint
+// queue depth by status plus retries, across all instances
+#[get("/email-outbox")]
+pub async fn email_outbox_stats(
+    app_state: web::Data<AppState>,
+    _admin: RequireRole<SystemAdmin>,
+) -> Result<HttpResponse, UserError> {
+    let stats = app_state.email_outbox_service.stats().await?;
+    Ok(HttpResponse::Ok().json(stats))
+}
@@ -0,0 +1,269 @@
+/*
+ This module holds authentication endpoints.
+
+ created modules must be registered in routes.rs
+*/
+use std::net::IpAddr;
+
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, Responder, get, post, web};
+
+use crate::app_modules::app_state::AppState;
+
+use super::schemas::{
+    IntrospectionRequest, IntrospectionResponse, LoginRequest, PasswordResetConfirmRequest,
+    PasswordResetRequest, RefreshRequest, SwitchTenantRequest, TenantMembershipResponse,
+};
+use crate::adapters::dtos::{AuthenticationDto, ClientContextDto};
+use crate::app_modules::auth::{
+    AuthMethod, AuthenticatedUser, CheckAuthorization, RequirePermission,
+};
+use crate::domain::errors::UserError;
+
+use anyhow::anyhow;
+use serde_json::json;
+use tracing::error;
+use validator::Validate;
+
+// Collects the caller's ip address and user agent
+pub(super) fn client_context(req: &HttpRequest) -> ClientContextDto {
+    let ip_address = req
+        .connection_info()
+        .realip_remote_addr()
+        .and_then(|addr| addr.parse::<IpAddr>().ok());
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    ClientContextDto {
+        ip_address,
+        user_agent,
+        ..Default::default()
+    }
+}
+
+// Login Endpoint
+// answers with an MFA challenge instead of tokens when the user has a
+// second factor, see /mfa/verify, and with an enrollment token when the
+// tenant requires MFA for the user's roles but none is set up yet
+#[post("/login")]
+pub async fn login(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    login_request: web::Json<LoginRequest>,
+) -> Result<HttpResponse, UserError> {
+    let login_data = login_request.into_inner();
+
+    let strategy = app_state
+        .auth_service
+        .strategies
+        .get(&AuthMethod::EmailPassword)
+        .ok_or_else(|| anyhow!("Authentication method not supported"))?;
+
+    let client = ClientContextDto {
+        device_identifier: login_data.device_identifier,
+        device_name: login_data.device_name,
+        device_type: login_data.device_type,
+        ..client_context(&req)
+    };
+
+    let user = strategy
+        .authenticate(AuthenticationDto {
+            email: Some(login_data.email),
+            password: Some(login_data.password),
+            assertion: None,
+            client: client.clone(),
+        })
+        .await?;
+
+    if let Some(challenge) = app_state
+        .mfa_service
+        .start_challenge(&user, &client)
+        .await?
+    {
+        return Ok(HttpResponse::Ok().json(challenge));
+    }
+    if let Some(enrollment) = app_state
+        .mfa_service
+        .start_required_enrollment(&user, &client)
+        .await?
+    {
+        return Ok(HttpResponse::Ok().json(enrollment));
+    }
+
+    let tokens = app_state
+        .session_service
+        .create_session(&user, &client)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+// Refresh Endpoint
+// rotates the refresh token on every use
+#[post("/refresh")]
+pub async fn refresh(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    refresh_request: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, UserError> {
+    let tokens = app_state
+        .session_service
+        .refresh_session(&refresh_request.refresh_token, &client_context(&req))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+// Switch Tenant Endpoint
+// rotates the refresh token like /refresh, reissuing tokens whose roles,
+// permissions and tenant claim are those of the requested membership
+#[post("/switch-tenant")]
+pub async fn switch_tenant(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    switch_request: web::Json<SwitchTenantRequest>,
+) -> Result<HttpResponse, UserError> {
+    let switch_data = switch_request.into_inner();
+    let tokens = app_state
+        .session_service
+        .switch_tenant(
+            &switch_data.refresh_token,
+            switch_data.tenant_id,
+            &client_context(&req),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+// Tenants Endpoint
+// tenants the current user may switch to
+#[get("/tenants")]
+pub async fn list_tenants(
+    app_state: web::Data<AppState>,
+    caller: AuthenticatedUser,
+) -> Result<HttpResponse, UserError> {
+    let user = app_state
+        .user_service
+        .get_user(caller.user_id())
+        .await?
+        .ok_or(UserError::Unauthorized)?;
+    let tenants = app_state.user_service.get_tenants(&user).await?;
+
+    let memberships: Vec<TenantMembershipResponse> = tenants
+        .into_iter()
+        .map(|tenant| TenantMembershipResponse {
+            current: caller.claims.tenant == Some(tenant.tenant_id),
+            tenant_id: tenant.tenant_id,
+            tenant_name: tenant.tenant_name,
+            domain: tenant.domain,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(memberships))
+}
+
+// Logout Endpoint
+// revokes the current session and blacklists the presented access token
+#[post("/logout")]
+pub async fn logout(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, UserError> {
+    app_state.session_service.logout(&user.claims).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Logout All Endpoint
+// revokes every session of the current user
+#[post("/logout-all")]
+pub async fn logout_all(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, UserError> {
+    let revoked_sessions = app_state.session_service.logout_all(&user.claims).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "revoked_sessions": revoked_sessions
+    })))
+}
+
+// Password Reset Request Endpoint
+// always answers 202 so that it cannot be used to discover accounts. The
+// reset email is queued in the outbox along with the token and delivered
+// from there, so only the lookup and that insert are waited for; a failure
+// is logged rather than answered, as it would tell existing accounts apart
+#[post("/password-reset/request")]
+pub async fn request_password_reset(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    reset_request: web::Json<PasswordResetRequest>,
+) -> impl Responder {
+    if let Err(e) = app_state
+        .password_reset_service
+        .request_reset(&reset_request.email, &client_context(&req))
+        .await
+    {
+        error!("Failed to process password reset request: {}", e);
+    }
+
+    HttpResponse::Accepted().json(json!({
+        "message": "If an account with that email exists, a password reset link has been sent."
+    }))
+}
+
+// Password Reset Confirm Endpoint
+#[post("/password-reset/confirm")]
+pub async fn confirm_password_reset(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    confirm_request: web::Json<PasswordResetConfirmRequest>,
+) -> Result<HttpResponse, UserError> {
+    let confirm_data = confirm_request.into_inner();
+    confirm_data.validate()?;
+
+    app_state
+        .password_reset_service
+        .confirm_reset(
+            &confirm_data.token,
+            &confirm_data.new_password,
+            &client_context(&req),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Password has been reset. Please sign in with your new password."
+    })))
+}
+
+// Token Introspection Endpoint
+// lets downstream services confirm a token is still valid, including revocation.
+// restricted tokens are only of use to gandalf and reported as inactive.
+// callers need authz:check like the /authz endpoints, so the endpoint does
+// not serve as a token oracle to anyone holding a token
+#[post("/introspect")]
+pub async fn introspect(
+    app_state: web::Data<AppState>,
+    _caller: RequirePermission<CheckAuthorization>,
+    introspection_request: web::Json<IntrospectionRequest>,
+) -> Result<HttpResponse, UserError> {
+    // an invalid or revoked token is a valid answer, not an error
+    let claims = match app_state
+        .token_service
+        .validate_access_token(&introspection_request.token)
+        .await
+    {
+        Ok(claims) if claims.scope.is_none() => Some(claims),
+        Ok(_) => None,
+        Err(UserError::InvalidToken | UserError::TokenRevoked) => None,
+        Err(e) => return Err(e),
+    };
+
+    Ok(HttpResponse::Ok().json(IntrospectionResponse {
+        active: claims.is_some(),
+        claims,
+    }))
+}
@@ -0,0 +1,122 @@
+/*
+ This module holds authorization decision endpoints for other services.
+ Callers need the authz:check permission.
+
+ Decisions carry a ttl and a matching Cache-Control header. Callers that
+ cache them poll /authz/changes and drop entries of the users listed there.
+ Roles are inherited, so a role change may affect holders of any role
+ above it; most callers simply drop everything on one.
+
+ created modules must be registered in routes.rs
+*/
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpResponse, get, post, web};
+
+use crate::app_modules::app_state::AppState;
+use crate::app_modules::auth::{CheckAuthorization, RequirePermission};
+use crate::domain::errors::UserError;
+
+use super::schemas::AuthzBatchCheckRequest;
+use super::schemas::AuthzBatchCheckResponse;
+use super::schemas::AuthzChangeResponse;
+use super::schemas::AuthzChangesQuery;
+use super::schemas::AuthzChangesResponse;
+use super::schemas::AuthzCheckRequest;
+use super::schemas::AuthzCheckResponse;
+
+const MAX_BATCH_CHECKS: usize = 100;
+// changes are returned by whole transactions
+const MAX_TRANSACTIONS_PER_POLL: i64 = 1000;
+
+fn cache_control(ttl: u64) -> CacheControl {
+    CacheControl(vec![
+        CacheDirective::Private,
+        CacheDirective::MaxAge(ttl as u32),
+    ])
+}
+
+// Authorization Check Endpoint
+#[post("/check")]
+pub async fn check(
+    app_state: web::Data<AppState>,
+    _caller: RequirePermission<CheckAuthorization>,
+    check_request: web::Json<AuthzCheckRequest>,
+) -> Result<HttpResponse, UserError> {
+    let check_request = check_request.into_inner();
+    let decision = app_state
+        .authz_service
+        .check(
+            check_request.subject,
+            &check_request.resource,
+            &check_request.action,
+            check_request.tenant_id,
+        )
+        .await?;
+
+    let ttl = app_state.authz_service.decision_ttl();
+    Ok(HttpResponse::Ok()
+        .insert_header(cache_control(ttl))
+        .json(AuthzCheckResponse::new(decision, ttl)))
+}
+
+// Batch Authorization Check Endpoint
+// evaluates up to MAX_BATCH_CHECKS checks, answering in the same order
+#[post("/check/batch")]
+pub async fn check_batch(
+    app_state: web::Data<AppState>,
+    _caller: RequirePermission<CheckAuthorization>,
+    batch_request: web::Json<AuthzBatchCheckRequest>,
+) -> Result<HttpResponse, UserError> {
+    let checks = batch_request.into_inner().checks;
+    if checks.len() > MAX_BATCH_CHECKS {
+        return Err(UserError::InvalidRequest(format!(
+            "At most {} checks per batch",
+            MAX_BATCH_CHECKS
+        )));
+    }
+
+    let ttl = app_state.authz_service.decision_ttl();
+    let mut results = Vec::with_capacity(checks.len());
+    for request in checks {
+        let decision = app_state
+            .authz_service
+            .check(
+                request.subject,
+                &request.resource,
+                &request.action,
+                request.tenant_id,
+            )
+            .await?;
+        results.push(AuthzCheckResponse::new(decision, ttl));
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header(cache_control(ttl))
+        .json(AuthzBatchCheckResponse { results, ttl }))
+}
+
+// Authorization Changes Endpoint
+// role assignment and permission changes from version `since` on, oldest
+// first. Changes are only listed once their transaction and every one
+// before it has finished, so following `version` skips none.
+#[get("/changes")]
+pub async fn changes(
+    app_state: web::Data<AppState>,
+    _caller: RequirePermission<CheckAuthorization>,
+    query: web::Query<AuthzChangesQuery>,
+) -> Result<HttpResponse, UserError> {
+    let page = app_state
+        .authz_service
+        .changes_since(query.since, MAX_TRANSACTIONS_PER_POLL)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(AuthzChangesResponse {
+        version: page.version,
+        changes: page
+            .changes
+            .into_iter()
+            .map(AuthzChangeResponse::from)
+            .collect(),
+        has_more: page.has_more,
+    }))
+}
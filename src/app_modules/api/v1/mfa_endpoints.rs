@@ -0,0 +1,309 @@
+/*
+ This module holds multi-factor authentication endpoints.
+
+ {method} in paths is one of totp, email or sms. WebAuthn credentials are
+ managed in webauthn_endpoints.
+
+ Enrollment endpoints also accept the enrollment token login hands out
+ when the tenant requires MFA; confirming a method with it completes the
+ login. Otherwise a user who already has a verified method confirms
+ starting an enrollment with it, so a stolen access token cannot add the
+ thief's own authenticator or phone number.
+
+ created modules must be registered in routes.rs
+*/
+use std::str::FromStr;
+
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+
+use crate::app_modules::app_state::AppState;
+
+use super::auth_endpoints::client_context;
+use super::schemas::{
+    MfaChallengeRequest, MfaCodeRequest, MfaEnrollmentResponse, MfaSendCodeRequest,
+    MfaStepUpRequest, MfaVerifyRequest, RecoveryCodeCountResponse, RecoveryCodesRequest,
+    RecoveryCodesResponse, SmsEnrollmentRequest,
+};
+use crate::app_modules::auth::{AuthenticatedUser, EnrollingUser};
+use crate::domain::errors::UserError;
+use crate::domain::models::MfaMethodType;
+
+// TOTP Enrollment Endpoint
+// returns a new secret; it is only used once confirmed with a first code
+#[post("/mfa/totp")]
+pub async fn enroll_totp(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: EnrollingUser,
+    step_up: Option<web::Json<MfaStepUpRequest>>,
+) -> Result<HttpResponse, UserError> {
+    let step_up = step_up.map(web::Json::into_inner).unwrap_or_default();
+    check_step_up(&req, &app_state, &user, &step_up).await?;
+    let enrollment = app_state
+        .mfa_service
+        .start_totp_enrollment(user.user_id())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(enrollment))
+}
+
+// Email Enrollment Endpoint
+// sends a code to the account email; confirm it to enable the method
+#[post("/mfa/email")]
+pub async fn enroll_email(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: EnrollingUser,
+    step_up: Option<web::Json<MfaStepUpRequest>>,
+) -> Result<HttpResponse, UserError> {
+    let step_up = step_up.map(web::Json::into_inner).unwrap_or_default();
+    check_step_up(&req, &app_state, &user, &step_up).await?;
+    let sent = app_state
+        .mfa_service
+        .start_email_enrollment(user.user_id())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(sent))
+}
+
+// SMS Enrollment Endpoint
+// sends a code to the phone number; confirm it to enable the method
+#[post("/mfa/sms")]
+pub async fn enroll_sms(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: EnrollingUser,
+    enrollment_request: web::Json<SmsEnrollmentRequest>,
+) -> Result<HttpResponse, UserError> {
+    check_step_up(&req, &app_state, &user, &enrollment_request.step_up).await?;
+    let sent = app_state
+        .mfa_service
+        .start_sms_enrollment(user.user_id(), &enrollment_request.phone_number)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(sent))
+}
+
+// Enrollment Confirmation Endpoint
+// activates an enrollment started above, so it needs no further step-up.
+// Answers with recovery codes when this is the user's first method; they
+// are not shown again. With an enrollment token it also answers with the
+// session tokens
+#[post("/mfa/{method}/confirm")]
+pub async fn confirm_method(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: EnrollingUser,
+    path: web::Path<String>,
+    code_request: web::Json<MfaCodeRequest>,
+) -> Result<HttpResponse, UserError> {
+    let method_type = parse_method(&path)?;
+    let recovery_codes = app_state
+        .mfa_service
+        .confirm_enrollment(
+            user.user_id(),
+            method_type,
+            &code_request.code,
+            &client_context(&req),
+        )
+        .await?;
+
+    let tokens = if user.has_pending_login() {
+        Some(
+            app_state
+                .mfa_service
+                .complete_required_enrollment(&user.claims)
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(MfaEnrollmentResponse {
+        method: method_type.to_string(),
+        recovery_codes,
+        tokens,
+    }))
+}
+
+// Method Removal Endpoint
+// requires a current code, so a stolen access token cannot turn MFA off
+#[post("/mfa/{method}/disable")]
+pub async fn disable_method(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    path: web::Path<String>,
+    code_request: web::Json<MfaCodeRequest>,
+) -> Result<HttpResponse, UserError> {
+    app_state
+        .mfa_service
+        .disable_method(
+            user.user_id(),
+            parse_method(&path)?,
+            &code_request.code,
+            &client_context(&req),
+        )
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Code Delivery Endpoint
+// sends a new code for the user's email or SMS method, throttled by
+// MFA_OTP_RESEND_INTERVAL
+#[post("/mfa/{method}/send")]
+pub async fn send_code(
+    app_state: web::Data<AppState>,
+    user: EnrollingUser,
+    path: web::Path<String>,
+) -> Result<HttpResponse, UserError> {
+    let sent = app_state
+        .mfa_service
+        .send_code_to_user(user.user_id(), parse_method(&path)?)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(sent))
+}
+
+// Recovery Codes Endpoint
+// replaces every recovery code with a new batch; requires a code of
+// another method
+#[post("/mfa/recovery-codes")]
+pub async fn regenerate_recovery_codes(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    codes_request: web::Json<RecoveryCodesRequest>,
+) -> Result<HttpResponse, UserError> {
+    let method_type = match codes_request.method.as_deref() {
+        Some(method) => parse_method(method)?,
+        None => MfaMethodType::Totp,
+    };
+    let recovery_codes = app_state
+        .mfa_service
+        .regenerate_recovery_codes(
+            user.user_id(),
+            method_type,
+            &codes_request.code,
+            &client_context(&req),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesResponse { recovery_codes }))
+}
+
+// Recovery Code Count Endpoint
+#[get("/mfa/recovery-codes")]
+pub async fn count_recovery_codes(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, UserError> {
+    let remaining = app_state
+        .mfa_service
+        .remaining_recovery_codes(user.user_id())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodeCountResponse { remaining }))
+}
+
+// Challenge Code Endpoint
+// sends a code for the email or SMS method picked to answer a login
+// challenge with
+#[post("/mfa/send-code")]
+pub async fn send_challenge_code(
+    app_state: web::Data<AppState>,
+    send_request: web::Json<MfaSendCodeRequest>,
+) -> Result<HttpResponse, UserError> {
+    let sent = app_state
+        .mfa_service
+        .send_challenge_code(
+            &send_request.challenge_token,
+            parse_method(&send_request.method)?,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(sent))
+}
+
+// WebAuthn Challenge Options Endpoint
+// options for answering a login challenge with a WebAuthn credential
+#[post("/mfa/webauthn/options")]
+pub async fn webauthn_challenge_options(
+    app_state: web::Data<AppState>,
+    challenge_request: web::Json<MfaChallengeRequest>,
+) -> Result<HttpResponse, UserError> {
+    let options = app_state
+        .mfa_service
+        .start_webauthn_challenge(&challenge_request.challenge_token)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(options))
+}
+
+// MFA Verification Endpoint
+// exchanges the challenge returned by /login and a code or WebAuthn
+// assertion for session tokens
+#[post("/mfa/verify")]
+pub async fn verify(
+    app_state: web::Data<AppState>,
+    verify_request: web::Json<MfaVerifyRequest>,
+) -> Result<HttpResponse, UserError> {
+    let verify_data = verify_request.into_inner();
+    let method_type = match verify_data.method.as_deref() {
+        Some(method) => parse_method(method)?,
+        None => MfaMethodType::Totp,
+    };
+
+    let tokens = match method_type {
+        MfaMethodType::Webauthn => {
+            let credential = verify_data.credential.ok_or_else(|| {
+                UserError::InvalidRequest("credential is required for webauthn".to_string())
+            })?;
+            app_state
+                .mfa_service
+                .complete_webauthn_challenge(&verify_data.challenge_token, &credential)
+                .await?
+        }
+        _ => {
+            let code = verify_data
+                .code
+                .ok_or_else(|| UserError::InvalidRequest("code is required".to_string()))?;
+            app_state
+                .mfa_service
+                .complete_challenge(&verify_data.challenge_token, method_type, &code)
+                .await?
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+fn parse_method(method: &str) -> Result<MfaMethodType, UserError> {
+    MfaMethodType::from_str(method).map_err(UserError::InvalidRequest)
+}
+
+// Adding a method, including a passkey, needs a second factor once the
+// user has one. Enrollment tokens are only handed out to users without a
+// method and are exempt.
+pub(super) async fn check_step_up(
+    req: &HttpRequest,
+    app_state: &AppState,
+    user: &EnrollingUser,
+    step_up: &MfaStepUpRequest,
+) -> Result<(), UserError> {
+    if user.has_pending_login() {
+        return Ok(());
+    }
+    let method_type = step_up.method.as_deref().map(parse_method).transpose()?;
+    app_state
+        .mfa_service
+        .verify_step_up(
+            user.user_id(),
+            method_type,
+            step_up.code.as_deref(),
+            step_up.assertion.as_ref(),
+            &client_context(req),
+        )
+        .await
+}
@@ -0,0 +1,780 @@
+/*
+ This module holds role management endpoints. They require the
+ role:manage permission, further limited to the caller's scope. Roles are
+ global, so editing them, and global assignments, need role:manage from a
+ global role (or system_admin); tenant assignments and lookups of a user's
+ roles and permissions are limited to the tenant the caller is signed in
+ to. Granting a wildcard resource permission or inheriting a system role
+ takes a global system_admin.
+
+ created modules must be registered in routes.rs
+*/
+use actix_web::{HttpResponse, delete, get, patch, post, web};
+use uuid::Uuid;
+
+use crate::app_modules::app_state::AppState;
+use crate::app_modules::auth::{AuthenticatedUser, ManageRoles, RequirePermission};
+use crate::domain::errors::UserError;
+use crate::domain::models::Permission;
+
+use super::schemas::AssignRoleRequest;
+use super::schemas::CreateRoleRequest;
+use super::schemas::ExplainPermissionQuery;
+use super::schemas::ExplainPermissionResponse;
+use super::schemas::GrantPermissionRequest;
+use super::schemas::InheritRoleRequest;
+use super::schemas::PermissionSourceResponse;
+use super::schemas::RoleResponse;
+use super::schemas::TenantQuery;
+use super::schemas::UpdateRoleRequest;
+use super::schemas::UserRoleResponse;
+
+#[get("")]
+pub async fn list_roles(
+    app_state: web::Data<AppState>,
+    _admin: RequirePermission<ManageRoles>,
+) -> Result<HttpResponse, UserError> {
+    let roles = app_state.role_service.list_roles().await?;
+    let roles: Vec<RoleResponse> = roles.into_iter().map(RoleResponse::from).collect();
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+#[post("")]
+pub async fn create_role(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    create_request: web::Json<CreateRoleRequest>,
+) -> Result<HttpResponse, UserError> {
+    check_global_scope(&app_state, &admin.user).await?;
+    let create_request = create_request.into_inner();
+    let role = app_state
+        .role_service
+        .create_role(
+            &create_request.role_name,
+            create_request.description.as_deref(),
+        )
+        .await?;
+
+    Ok(HttpResponse::Created().json(RoleResponse::from(role)))
+}
+
+#[get("/{role_id}")]
+pub async fn get_role(
+    app_state: web::Data<AppState>,
+    _admin: RequirePermission<ManageRoles>,
+    role_id: web::Path<Uuid>,
+) -> Result<HttpResponse, UserError> {
+    let role = app_state
+        .role_service
+        .get_role(role_id.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(RoleResponse::from(role)))
+}
+
+#[patch("/{role_id}")]
+pub async fn update_role(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    role_id: web::Path<Uuid>,
+    update_request: web::Json<UpdateRoleRequest>,
+) -> Result<HttpResponse, UserError> {
+    check_global_scope(&app_state, &admin.user).await?;
+    let update_request = update_request.into_inner();
+    let role = app_state
+        .role_service
+        .update_role(
+            role_id.into_inner(),
+            update_request.role_name.as_deref(),
+            update_request.description.as_deref(),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(RoleResponse::from(role)))
+}
+
+// System roles cannot be deleted
+#[delete("/{role_id}")]
+pub async fn delete_role(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    role_id: web::Path<Uuid>,
+) -> Result<HttpResponse, UserError> {
+    check_global_scope(&app_state, &admin.user).await?;
+    app_state
+        .role_service
+        .delete_role(role_id.into_inner())
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Role Permissions Endpoint
+#[get("/{role_id}/permissions")]
+pub async fn list_role_permissions(
+    app_state: web::Data<AppState>,
+    _admin: RequirePermission<ManageRoles>,
+    role_id: web::Path<Uuid>,
+) -> Result<HttpResponse, UserError> {
+    let permissions = app_state
+        .role_service
+        .list_role_permissions(role_id.into_inner())
+        .await?;
+    Ok(HttpResponse::Ok().json(permissions))
+}
+
+#[post("/{role_id}/permissions")]
+pub async fn grant_permission(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    role_id: web::Path<Uuid>,
+    grant_request: web::Json<GrantPermissionRequest>,
+) -> Result<HttpResponse, UserError> {
+    check_global_scope(&app_state, &admin.user).await?;
+    let permission: Permission = grant_request
+        .permission
+        .parse()
+        .map_err(UserError::InvalidRequest)?;
+    if permission.has_wildcard_resource() {
+        check_system_admin(&app_state, &admin.user).await?;
+    }
+    app_state
+        .role_service
+        .grant_permission(role_id.into_inner(), &grant_request.permission)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/{role_id}/permissions/{permission}")]
+pub async fn revoke_permission(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    path: web::Path<(Uuid, String)>,
+) -> Result<HttpResponse, UserError> {
+    check_global_scope(&app_state, &admin.user).await?;
+    let (role_id, permission) = path.into_inner();
+
+    app_state
+        .role_service
+        .revoke_permission(role_id, &permission)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Inherited Roles Endpoint
+#[get("/{role_id}/inherits")]
+pub async fn list_inherited_roles(
+    app_state: web::Data<AppState>,
+    _admin: RequirePermission<ManageRoles>,
+    role_id: web::Path<Uuid>,
+) -> Result<HttpResponse, UserError> {
+    let roles = app_state
+        .role_service
+        .list_inherited_roles(role_id.into_inner())
+        .await?;
+    let roles: Vec<RoleResponse> = roles.into_iter().map(RoleResponse::from).collect();
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+// rejected with 409 if it would make the hierarchy cyclic
+#[post("/{role_id}/inherits")]
+pub async fn add_inherited_role(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    role_id: web::Path<Uuid>,
+    inherit_request: web::Json<InheritRoleRequest>,
+) -> Result<HttpResponse, UserError> {
+    check_global_scope(&app_state, &admin.user).await?;
+    let inherited_role = app_state
+        .role_service
+        .get_role(inherit_request.role_id)
+        .await?;
+    if inherited_role.is_system_role {
+        check_system_admin(&app_state, &admin.user).await?;
+    }
+    app_state
+        .role_service
+        .add_inherited_role(role_id.into_inner(), inherit_request.role_id)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/{role_id}/inherits/{inherited_role_id}")]
+pub async fn remove_inherited_role(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, UserError> {
+    check_global_scope(&app_state, &admin.user).await?;
+    let (role_id, inherited_role_id) = path.into_inner();
+
+    app_state
+        .role_service
+        .remove_inherited_role(role_id, inherited_role_id)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Explain Permission Endpoint
+// lists the roles a user's permission comes from, through inheritance
+#[get("/{user_id}/permissions/explain")]
+pub async fn explain_user_permission(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    user_id: web::Path<Uuid>,
+    query: web::Query<ExplainPermissionQuery>,
+) -> Result<HttpResponse, UserError> {
+    check_lookup_scope(&app_state, &admin.user, query.tenant_id).await?;
+    let ExplainPermissionQuery {
+        permission,
+        tenant_id,
+    } = query.into_inner();
+    let sources = app_state
+        .role_service
+        .explain_user_permission(user_id.into_inner(), &permission, tenant_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(ExplainPermissionResponse {
+        permission,
+        granted: !sources.is_empty(),
+        sources: sources
+            .into_iter()
+            .map(PermissionSourceResponse::from)
+            .collect(),
+    }))
+}
+
+// User Permissions Endpoint
+// effective permissions of the user in ?tenant_id, the same set their
+// tokens for that tenant carry
+#[get("/{user_id}/permissions")]
+pub async fn list_user_permissions(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    user_id: web::Path<Uuid>,
+    query: web::Query<TenantQuery>,
+) -> Result<HttpResponse, UserError> {
+    check_lookup_scope(&app_state, &admin.user, query.tenant_id).await?;
+    let permissions = app_state
+        .role_service
+        .list_user_permissions(user_id.into_inner(), query.tenant_id)
+        .await?;
+    Ok(HttpResponse::Ok().json(permissions))
+}
+
+// User Roles Endpoint
+// the user's assignments in ?tenant_id, or all of them without it
+#[get("/{user_id}/roles")]
+pub async fn list_user_roles(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    user_id: web::Path<Uuid>,
+    query: web::Query<TenantQuery>,
+) -> Result<HttpResponse, UserError> {
+    check_lookup_scope(&app_state, &admin.user, query.tenant_id).await?;
+    let roles = app_state
+        .role_service
+        .list_user_roles(user_id.into_inner(), query.tenant_id)
+        .await?;
+    let roles: Vec<UserRoleResponse> = roles.into_iter().map(UserRoleResponse::from).collect();
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+// Assign Role Endpoint
+// records the calling administrator as assigned_by
+#[post("/{user_id}/roles")]
+pub async fn assign_role(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    user_id: web::Path<Uuid>,
+    assign_request: web::Json<AssignRoleRequest>,
+) -> Result<HttpResponse, UserError> {
+    check_assignment_scope(&app_state, &admin.user, assign_request.tenant_id).await?;
+    app_state
+        .role_service
+        .assign_role(
+            user_id.into_inner(),
+            assign_request.role_id,
+            assign_request.tenant_id,
+            admin.user.user_id(),
+        )
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Revoke Role Endpoint
+// revokes the assignment in ?tenant_id, or the global one without it
+#[delete("/{user_id}/roles/{role_id}")]
+pub async fn revoke_role(
+    app_state: web::Data<AppState>,
+    admin: RequirePermission<ManageRoles>,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<TenantQuery>,
+) -> Result<HttpResponse, UserError> {
+    let (user_id, role_id) = path.into_inner();
+
+    check_assignment_scope(&app_state, &admin.user, query.tenant_id).await?;
+    app_state
+        .role_service
+        .revoke_role(user_id, role_id, query.tenant_id, admin.user.user_id())
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// global assignments need a global grant, tenant ones the caller's tenant
+async fn check_assignment_scope(
+    app_state: &AppState,
+    admin: &AuthenticatedUser,
+    tenant_id: Option<Uuid>,
+) -> Result<(), UserError> {
+    let allowed = match tenant_id {
+        None => {
+            app_state
+                .role_service
+                .can_manage_globally(admin.user_id())
+                .await?
+        }
+        Some(tenant_id) => admin.claims.tenant == Some(tenant_id),
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(UserError::Forbidden)
+    }
+}
+
+// role definitions are global, so editing them needs a global grant
+async fn check_global_scope(
+    app_state: &AppState,
+    admin: &AuthenticatedUser,
+) -> Result<(), UserError> {
+    if app_state
+        .role_service
+        .can_manage_globally(admin.user_id())
+        .await?
+    {
+        Ok(())
+    } else {
+        Err(UserError::Forbidden)
+    }
+}
+
+// grants that reach every resource are reserved to global system_admins
+async fn check_system_admin(
+    app_state: &AppState,
+    admin: &AuthenticatedUser,
+) -> Result<(), UserError> {
+    if app_state
+        .role_service
+        .is_global_system_admin(admin.user_id())
+        .await?
+    {
+        Ok(())
+    } else {
+        Err(UserError::Forbidden)
+    }
+}
+
+// tenant-scoped managers look up users only within their own tenant
+async fn check_lookup_scope(
+    app_state: &AppState,
+    admin: &AuthenticatedUser,
+    tenant_id: Option<Uuid>,
+) -> Result<(), UserError> {
+    if tenant_id.is_some() && admin.claims.tenant == tenant_id {
+        return Ok(());
+    }
+    check_global_scope(app_state, admin).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::{Value, json};
+    use uuid::Uuid;
+
+    use actix_web::test::TestRequest;
+
+    use crate::test_support::{TestApp, unique_email};
+
+    // Signs in a new user granted role:manage in `tenant_id` (globally
+    // without one) and returns their access token
+    async fn role_manager(app: &TestApp, tenant_id: Option<Uuid>) -> String {
+        let email = unique_email();
+        let user_id = app.register(&email).await;
+        app.grant(user_id, "role:manage", tenant_id).await;
+        let (status, body) = app.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["access_token"].as_str().unwrap().to_string()
+    }
+
+    async fn assign(
+        app: &TestApp,
+        token: &str,
+        user_id: Uuid,
+        role_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> StatusCode {
+        let (status, _) = app
+            .post(
+                &format!("/api/v1/users/{}/roles", user_id),
+                Some(token),
+                json!({ "role_id": role_id, "tenant_id": tenant_id }),
+            )
+            .await;
+        status
+    }
+
+    async fn revoke(
+        app: &TestApp,
+        token: &str,
+        user_id: Uuid,
+        role_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> StatusCode {
+        let query = tenant_id.map_or(String::new(), |id| format!("?tenant_id={}", id));
+        let request = actix_web::test::TestRequest::delete()
+            .uri(&format!(
+                "/api/v1/users/{}/roles/{}{}",
+                user_id, role_id, query
+            ))
+            .insert_header(("Authorization", format!("Bearer {}", token)));
+        let (status, _): (StatusCode, Value) = app.call(request).await;
+        status
+    }
+
+    async fn send(
+        app: &TestApp,
+        request: TestRequest,
+        token: &str,
+        body: Option<Value>,
+    ) -> StatusCode {
+        let request = request.insert_header(("Authorization", format!("Bearer {}", token)));
+        let request = match body {
+            Some(body) => request.set_json(body),
+            None => request,
+        };
+        let (status, _): (StatusCode, Value) = app.call(request).await;
+        status
+    }
+
+    // Signs in a new user holding the global system_admin role and returns
+    // their access token
+    async fn system_admin(app: &TestApp) -> String {
+        let email = unique_email();
+        let user_id = app.register(&email).await;
+        app.pool
+            .get()
+            .await
+            .unwrap()
+            .execute(
+                "INSERT INTO auth.user_roles (user_id, role_id)
+                 SELECT $1, id FROM auth.roles WHERE role_name = 'system_admin'",
+                &[&user_id],
+            )
+            .await
+            .unwrap();
+        let (status, body) = app.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["access_token"].as_str().unwrap().to_string()
+    }
+
+    async fn create_role(app: &TestApp, token: &str) -> (StatusCode, Value) {
+        app.post(
+            "/api/v1/roles",
+            Some(token),
+            json!({ "role_name": format!("role_{}", Uuid::new_v4().simple()) }),
+        )
+        .await
+    }
+
+    async fn inherit(
+        app: &TestApp,
+        token: &str,
+        role_id: &str,
+        inherited_role_id: &str,
+    ) -> (StatusCode, Value) {
+        app.post(
+            &format!("/api/v1/roles/{}/inherits", role_id),
+            Some(token),
+            json!({ "role_id": inherited_role_id }),
+        )
+        .await
+    }
+
+    async fn system_admin_role_id(app: &TestApp) -> Uuid {
+        app.pool
+            .get()
+            .await
+            .unwrap()
+            .query_one(
+                "SELECT id FROM auth.roles WHERE role_name = 'system_admin'",
+                &[],
+            )
+            .await
+            .unwrap()
+            .get("id")
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn tenant_managers_assign_roles_only_in_their_tenant() {
+        let app = TestApp::start().await;
+        let (own_tenant, _) = app.create_tenant().await;
+        let (other_tenant, _) = app.create_tenant().await;
+        let token = role_manager(&app, Some(own_tenant)).await;
+        let target = app.register(&unique_email()).await;
+        let role_id = app.grant(target, "course:read", Some(other_tenant)).await;
+
+        assert_eq!(
+            assign(&app, &token, target, role_id, Some(own_tenant)).await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            assign(&app, &token, target, role_id, Some(other_tenant)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            assign(&app, &token, target, role_id, None).await,
+            StatusCode::FORBIDDEN
+        );
+
+        assert_eq!(
+            revoke(&app, &token, target, role_id, Some(other_tenant)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            revoke(&app, &token, target, role_id, Some(own_tenant)).await,
+            StatusCode::NO_CONTENT
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn global_assignments_need_a_global_grant() {
+        let app = TestApp::start().await;
+        let token = role_manager(&app, None).await;
+        let target = app.register(&unique_email()).await;
+        let role_id = app.grant(target, "course:read", None).await;
+
+        assert_eq!(
+            revoke(&app, &token, target, role_id, None).await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            assign(&app, &token, target, role_id, None).await,
+            StatusCode::NO_CONTENT
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn tenant_managers_cannot_edit_role_definitions() {
+        let app = TestApp::start().await;
+        let (tenant_id, _) = app.create_tenant().await;
+        let token = role_manager(&app, Some(tenant_id)).await;
+        let user_id = app.register(&unique_email()).await;
+        let role_id = app.grant(user_id, "course:read", Some(tenant_id)).await;
+        let system_admin_id = system_admin_role_id(&app).await;
+        let role = format!("/api/v1/roles/{}", role_id);
+
+        let (status, _) = create_role(&app, &token).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let edits = [
+            (
+                TestRequest::patch().uri(&role),
+                Some(json!({ "description": "edited" })),
+            ),
+            (TestRequest::delete().uri(&role), None),
+            (
+                TestRequest::post().uri(&format!("{}/permissions", role)),
+                Some(json!({ "permission": "course:write" })),
+            ),
+            (
+                TestRequest::delete().uri(&format!("{}/permissions/course:read", role)),
+                None,
+            ),
+            (
+                TestRequest::post().uri(&format!("{}/inherits", role)),
+                Some(json!({ "role_id": system_admin_id })),
+            ),
+            (
+                TestRequest::delete().uri(&format!("{}/inherits/{}", role, system_admin_id)),
+                None,
+            ),
+        ];
+        for (request, body) in edits {
+            assert_eq!(
+                send(&app, request, &token, body).await,
+                StatusCode::FORBIDDEN
+            );
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn wildcard_grants_and_system_roles_need_a_system_admin() {
+        let app = TestApp::start().await;
+        let manager = role_manager(&app, None).await;
+        let admin = system_admin(&app).await;
+        let system_admin_id = system_admin_role_id(&app).await;
+
+        let (status, role) = create_role(&app, &manager).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", role);
+        let role = format!("/api/v1/roles/{}", role["role_id"].as_str().unwrap());
+        let grant = |permission: &str| {
+            (
+                TestRequest::post().uri(&format!("{}/permissions", role)),
+                Some(json!({ "permission": permission })),
+            )
+        };
+        let inherit = || {
+            (
+                TestRequest::post().uri(&format!("{}/inherits", role)),
+                Some(json!({ "role_id": system_admin_id })),
+            )
+        };
+
+        for (request, body) in [grant("*"), grant("*:manage"), inherit()] {
+            assert_eq!(
+                send(&app, request, &manager, body).await,
+                StatusCode::FORBIDDEN
+            );
+        }
+        let (request, body) = grant("course:*");
+        assert_eq!(
+            send(&app, request, &manager, body).await,
+            StatusCode::NO_CONTENT
+        );
+
+        for (request, body) in [grant("*"), inherit()] {
+            assert_eq!(
+                send(&app, request, &admin, body).await,
+                StatusCode::NO_CONTENT
+            );
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn inheritance_cycles_are_rejected_and_permissions_flow_through() {
+        let app = TestApp::start().await;
+        let admin = system_admin(&app).await;
+        let mut roles = Vec::new();
+        for _ in 0..3 {
+            let (status, role) = create_role(&app, &admin).await;
+            assert_eq!(status, StatusCode::CREATED, "{}", role);
+            roles.push(role);
+        }
+        let id = |role: &Value| role["role_id"].as_str().unwrap().to_string();
+        let name = |role: &Value| role["role_name"].as_str().unwrap().to_string();
+        let (top, middle, bottom) = (&roles[0], &roles[1], &roles[2]);
+
+        // top -> middle -> bottom
+        for (role, inherited) in [(top, middle), (middle, bottom)] {
+            let (status, body) = inherit(&app, &admin, &id(role), &id(inherited)).await;
+            assert_eq!(status, StatusCode::NO_CONTENT, "{}", body);
+        }
+        for (role, inherited) in [(bottom, top), (middle, top), (top, top)] {
+            let (status, body) = inherit(&app, &admin, &id(role), &id(inherited)).await;
+            assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+            assert_eq!(body["code"], "ROLE_CYCLE");
+        }
+
+        let (status, body) = app
+            .post(
+                &format!("/api/v1/roles/{}/permissions", id(bottom)),
+                Some(&admin),
+                json!({ "permission": "course:grade" }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{}", body);
+        let user_id = app.register(&unique_email()).await;
+        let top_id: Uuid = id(top).parse().unwrap();
+        assert_eq!(
+            assign(&app, &admin, user_id, top_id, None).await,
+            StatusCode::NO_CONTENT
+        );
+
+        let (status, permissions) = app
+            .get(
+                &format!("/api/v1/users/{}/permissions", user_id),
+                Some(&admin),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", permissions);
+        assert!(
+            permissions
+                .as_array()
+                .unwrap()
+                .contains(&json!("course:grade")),
+            "{}",
+            permissions
+        );
+
+        let (status, explained) = app
+            .get(
+                &format!(
+                    "/api/v1/users/{}/permissions/explain?permission=course:grade",
+                    user_id
+                ),
+                Some(&admin),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", explained);
+        assert_eq!(explained["granted"], true);
+        assert_eq!(explained["sources"][0]["role"], name(bottom).as_str());
+        assert_eq!(
+            explained["sources"][0]["via"],
+            json!([name(top), name(middle), name(bottom)])
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn tenant_managers_look_up_users_only_in_their_tenant() {
+        let app = TestApp::start().await;
+        let (own_tenant, _) = app.create_tenant().await;
+        let (other_tenant, _) = app.create_tenant().await;
+        let token = role_manager(&app, Some(own_tenant)).await;
+        let user_id = app.register(&unique_email()).await;
+        app.grant(user_id, "course:read", Some(own_tenant)).await;
+        app.grant(user_id, "course:grade", Some(other_tenant)).await;
+
+        let lookups = |query: &str| {
+            [
+                format!("/api/v1/users/{}/roles{}", user_id, query),
+                format!("/api/v1/users/{}/permissions{}", user_id, query),
+                format!(
+                    "/api/v1/users/{}/permissions/explain?permission=course:read{}",
+                    user_id,
+                    query.replacen('?', "&", 1)
+                ),
+            ]
+        };
+
+        for path in lookups(&format!("?tenant_id={}", own_tenant)) {
+            let (status, body) = app.get(&path, Some(&token)).await;
+            assert_eq!(status, StatusCode::OK, "{}: {}", path, body);
+        }
+        for query in [format!("?tenant_id={}", other_tenant), String::new()] {
+            for path in lookups(&query) {
+                let (status, _) = app.get(&path, Some(&token)).await;
+                assert_eq!(status, StatusCode::FORBIDDEN, "{}", path);
+            }
+        }
+
+        let (_, roles) = app
+            .get(
+                &format!("/api/v1/users/{}/roles?tenant_id={}", user_id, own_tenant),
+                Some(&token),
+            )
+            .await;
+        assert_eq!(roles.as_array().map(Vec::len), Some(1), "{}", roles);
+    }
+}
@@ -1,12 +1,92 @@
 use actix_web::web;
 
+use super::admin_endpoints;
+use super::auth_endpoints;
+use super::authz_endpoints;
+use super::mfa_endpoints;
+use super::role_endpoints;
 use super::user_endpoints;
+use super::webauthn_endpoints;
 
 // Grouped routes for users
 pub fn user_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/users")
             .service(user_endpoints::get_user)
-            .service(user_endpoints::register),
+            .service(user_endpoints::register)
+            .service(user_endpoints::verify_email)
+            .service(user_endpoints::resend_verification)
+            .service(role_endpoints::list_user_roles)
+            .service(role_endpoints::assign_role)
+            .service(role_endpoints::revoke_role)
+            .service(role_endpoints::list_user_permissions)
+            .service(role_endpoints::explain_user_permission),
     );
 }
+
+// Grouped routes for authentication
+pub fn auth_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/auth")
+            .service(auth_endpoints::login)
+            .service(auth_endpoints::refresh)
+            .service(auth_endpoints::switch_tenant)
+            .service(auth_endpoints::list_tenants)
+            .service(auth_endpoints::logout)
+            .service(auth_endpoints::logout_all)
+            .service(auth_endpoints::request_password_reset)
+            .service(auth_endpoints::confirm_password_reset)
+            .service(auth_endpoints::introspect)
+            .service(mfa_endpoints::enroll_totp)
+            .service(mfa_endpoints::enroll_email)
+            .service(mfa_endpoints::enroll_sms)
+            .service(mfa_endpoints::confirm_method)
+            .service(mfa_endpoints::disable_method)
+            .service(mfa_endpoints::send_code)
+            .service(mfa_endpoints::regenerate_recovery_codes)
+            .service(mfa_endpoints::count_recovery_codes)
+            .service(mfa_endpoints::send_challenge_code)
+            .service(mfa_endpoints::webauthn_challenge_options)
+            .service(mfa_endpoints::verify)
+            .service(webauthn_endpoints::registration_options)
+            .service(webauthn_endpoints::register)
+            .service(webauthn_endpoints::list_credentials)
+            .service(webauthn_endpoints::reauthentication_options)
+            .service(webauthn_endpoints::remove_credential)
+            .service(webauthn_endpoints::login_options)
+            .service(webauthn_endpoints::login),
+    );
+}
+
+// Grouped routes for authorization decisions
+pub fn authz_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/authz")
+            .service(authz_endpoints::check)
+            .service(authz_endpoints::check_batch)
+            .service(authz_endpoints::changes),
+    );
+}
+
+// Grouped routes for role management
+pub fn role_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/roles")
+            .service(role_endpoints::list_roles)
+            .service(role_endpoints::create_role)
+            .service(role_endpoints::get_role)
+            .service(role_endpoints::update_role)
+            .service(role_endpoints::delete_role)
+            .service(role_endpoints::list_role_permissions)
+            .service(role_endpoints::grant_permission)
+            .service(role_endpoints::revoke_permission)
+            .service(role_endpoints::list_inherited_roles)
+            .service(role_endpoints::add_inherited_role)
+            .service(role_endpoints::remove_inherited_role),
+    );
+}
+
+// Grouped routes for operators
+pub fn admin_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/admin").service(admin_endpoints::email_outbox_stats));
+}
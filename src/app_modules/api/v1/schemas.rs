@@ -1,4 +1,51 @@
+mod auth_schemas;
+mod authz_schemas;
+mod mfa_schemas;
+mod role_schemas;
 mod user_schemas;
+mod webauthn_schemas;
 
+pub use auth_schemas::IntrospectionRequest;
+pub use auth_schemas::IntrospectionResponse;
+pub use auth_schemas::LoginRequest;
+pub use auth_schemas::PasswordResetConfirmRequest;
+pub use auth_schemas::PasswordResetRequest;
+pub use auth_schemas::RefreshRequest;
+pub use auth_schemas::SwitchTenantRequest;
+pub use auth_schemas::TenantMembershipResponse;
+pub use authz_schemas::AuthzBatchCheckRequest;
+pub use authz_schemas::AuthzBatchCheckResponse;
+pub use authz_schemas::AuthzChangeResponse;
+pub use authz_schemas::AuthzChangesQuery;
+pub use authz_schemas::AuthzChangesResponse;
+pub use authz_schemas::AuthzCheckRequest;
+pub use authz_schemas::AuthzCheckResponse;
+pub use mfa_schemas::MfaChallengeRequest;
+pub use mfa_schemas::MfaCodeRequest;
+pub use mfa_schemas::MfaEnrollmentResponse;
+pub use mfa_schemas::MfaSendCodeRequest;
+pub use mfa_schemas::MfaStepUpRequest;
+pub use mfa_schemas::MfaVerifyRequest;
+pub use mfa_schemas::RecoveryCodeCountResponse;
+pub use mfa_schemas::RecoveryCodesRequest;
+pub use mfa_schemas::RecoveryCodesResponse;
+pub use mfa_schemas::SmsEnrollmentRequest;
+pub use role_schemas::AssignRoleRequest;
+pub use role_schemas::CreateRoleRequest;
+pub use role_schemas::ExplainPermissionQuery;
+pub use role_schemas::ExplainPermissionResponse;
+pub use role_schemas::GrantPermissionRequest;
+pub use role_schemas::InheritRoleRequest;
+pub use role_schemas::PermissionSourceResponse;
+pub use role_schemas::RoleResponse;
+pub use role_schemas::TenantQuery;
+pub use role_schemas::UpdateRoleRequest;
+pub use role_schemas::UserRoleResponse;
 pub use user_schemas::RegistrationRequestLocal;
+pub use user_schemas::ResendVerificationRequest;
 pub use user_schemas::UserResponse;
+pub use user_schemas::VerifyEmailRequest;
+pub use webauthn_schemas::WebauthnLoginRequest;
+pub use webauthn_schemas::WebauthnRegisterRequest;
+pub use webauthn_schemas::WebauthnRegisterResponse;
+pub use webauthn_schemas::WebauthnRemoveRequest;
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::domain::models::AccessTokenClaims;
+
+// Login with email and password
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+    pub device_identifier: Option<String>,
+    pub device_name: Option<String>,
+    pub device_type: Option<String>,
+}
+
+// Refresh token rotation
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+// Reissue tokens acting in another tenant membership
+#[derive(Debug, Deserialize)]
+pub struct SwitchTenantRequest {
+    pub refresh_token: String,
+    pub tenant_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TenantMembershipResponse {
+    pub tenant_id: Uuid,
+    pub tenant_name: String,
+    pub domain: String,
+    // the tenant the presented access token acts in
+    pub current: bool,
+}
+
+// Forgot password
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
+    pub new_password: String,
+}
+
+// Token introspection, loosely following RFC 7662
+#[derive(Debug, Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(flatten)]
+    pub claims: Option<AccessTokenClaims>,
+}
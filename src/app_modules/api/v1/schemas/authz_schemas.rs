@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::models::{AuthzChange, AuthzDecision};
+
+// May `subject` perform `action` on `resource`? The required permission
+// is "<resource>:<action>", e.g. resource "course" and action "grade".
+#[derive(Debug, Deserialize)]
+pub struct AuthzCheckRequest {
+    pub subject: Uuid,
+    pub resource: String,
+    pub action: String,
+    // tenant owning the resource; the subject must belong to it
+    pub tenant_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthzBatchCheckRequest {
+    pub checks: Vec<AuthzCheckRequest>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthzCheckResponse {
+    pub allowed: bool,
+    pub reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_permission: Option<String>,
+    // seconds the decision may be cached
+    pub ttl: u64,
+}
+
+impl AuthzCheckResponse {
+    pub fn new(decision: AuthzDecision, ttl: u64) -> Self {
+        Self {
+            allowed: decision.allowed,
+            reason: decision.reason.to_string(),
+            matched_permission: decision.matched_permission,
+            ttl,
+        }
+    }
+}
+
+// Results in the order of the checks
+#[derive(Debug, Serialize)]
+pub struct AuthzBatchCheckResponse {
+    pub results: Vec<AuthzCheckResponse>,
+    pub ttl: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthzChangesQuery {
+    // `version` of the previous poll, 0 for the whole log
+    #[serde(default)]
+    pub since: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthzChangeResponse {
+    pub change_id: i64,
+    pub user_id: Option<Uuid>,
+    pub role_id: Option<Uuid>,
+    pub changed_at: String,
+}
+
+impl From<AuthzChange> for AuthzChangeResponse {
+    fn from(change: AuthzChange) -> Self {
+        Self {
+            change_id: change.change_id,
+            user_id: change.user_id,
+            role_id: change.role_id,
+            changed_at: change.changed_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthzChangesResponse {
+    // pass as `since` on the next poll
+    pub version: i64,
+    pub changes: Vec<AuthzChangeResponse>,
+    pub has_more: bool,
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+use crate::adapters::dtos::{AuthTokensDto, WebauthnAssertionDto};
+
+// A code of the method named in the path: from the authenticator app, or
+// the one sent by email or SMS
+#[derive(Debug, Deserialize)]
+pub struct MfaCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SmsEnrollmentRequest {
+    // international format, e.g. +15551234567
+    pub phone_number: String,
+    #[serde(flatten)]
+    pub step_up: MfaStepUpRequest,
+}
+
+// Confirms adding an MFA method once the user has a verified one: an
+// assertion for options from /webauthn/credentials/options, or a code of
+// `method` ("totp" when omitted). Not needed with an enrollment token.
+#[derive(Debug, Default, Deserialize)]
+pub struct MfaStepUpRequest {
+    pub method: Option<String>,
+    pub code: Option<String>,
+    pub assertion: Option<WebauthnAssertionDto>,
+}
+
+// Asks for a code to answer a login challenge with. `method` is "email"
+// or "sms" and must be listed in the challenge.
+#[derive(Debug, Deserialize)]
+pub struct MfaSendCodeRequest {
+    pub challenge_token: String,
+    pub method: String,
+}
+
+// A code of the user's `method` ("totp" when omitted)
+#[derive(Debug, Deserialize)]
+pub struct RecoveryCodesRequest {
+    pub method: Option<String>,
+    pub code: String,
+}
+
+// Second step of a login that requires MFA. `method` is one of the
+// methods listed in the challenge, "totp" when omitted. WebAuthn is
+// answered with the result of navigator.credentials.get() for options from
+// /mfa/webauthn/options, every other method with a code.
+#[derive(Debug, Deserialize)]
+pub struct MfaVerifyRequest {
+    pub challenge_token: String,
+    pub method: Option<String>,
+    pub code: Option<String>,
+    pub credential: Option<WebauthnAssertionDto>,
+}
+
+// Asks for WebAuthn options to answer a login challenge with
+#[derive(Debug, Deserialize)]
+pub struct MfaChallengeRequest {
+    pub challenge_token: String,
+}
+
+// Shown once; only hashes are kept
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+// Recovery codes are only handed out with the user's first method. Tokens
+// are included when the confirmation was made with an enrollment token.
+#[derive(Debug, Serialize)]
+pub struct MfaEnrollmentResponse {
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<AuthTokensDto>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodeCountResponse {
+    pub remaining: i64,
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::models::{PermissionSource, Role, UserRole};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRoleRequest {
+    pub role_name: String,
+    pub description: Option<String>,
+}
+
+// Omitted fields are left unchanged
+#[derive(Debug, Deserialize)]
+pub struct UpdateRoleRequest {
+    pub role_name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignRoleRequest {
+    pub role_id: Uuid,
+    // omitted for a global assignment
+    pub tenant_id: Option<Uuid>,
+}
+
+// The role to inherit
+#[derive(Debug, Deserialize)]
+pub struct InheritRoleRequest {
+    pub role_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExplainPermissionQuery {
+    pub permission: String,
+    pub tenant_id: Option<Uuid>,
+}
+
+// Omitted for global role assignments only
+#[derive(Debug, Deserialize)]
+pub struct TenantQuery {
+    pub tenant_id: Option<Uuid>,
+}
+
+// A "resource:action" permission, wildcards allowed ("course:*")
+#[derive(Debug, Deserialize)]
+pub struct GrantPermissionRequest {
+    pub permission: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleResponse {
+    pub role_id: Uuid,
+    pub role_name: String,
+    pub description: Option<String>,
+    pub is_system_role: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<Role> for RoleResponse {
+    fn from(role: Role) -> Self {
+        Self {
+            role_id: role.id,
+            role_name: role.role_name,
+            description: role.description,
+            is_system_role: role.is_system_role,
+            created_at: role.created_at.to_rfc3339(),
+            updated_at: role.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserRoleResponse {
+    pub role_id: Uuid,
+    pub role_name: String,
+    pub tenant_id: Option<Uuid>,
+    pub assigned_at: String,
+    pub assigned_by: Option<Uuid>,
+}
+
+impl From<UserRole> for UserRoleResponse {
+    fn from(user_role: UserRole) -> Self {
+        Self {
+            role_id: user_role.role_id,
+            role_name: user_role.role_name,
+            tenant_id: user_role.tenant_id,
+            assigned_at: user_role.assigned_at.to_rfc3339(),
+            assigned_by: user_role.assigned_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PermissionSourceResponse {
+    pub permission: String,
+    pub role: String,
+    // assigned role first, `role` last
+    pub via: Vec<String>,
+}
+
+impl From<PermissionSource> for PermissionSourceResponse {
+    fn from(source: PermissionSource) -> Self {
+        Self {
+            permission: source.permission,
+            role: source.role_name,
+            via: source.via,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExplainPermissionResponse {
+    pub permission: String,
+    pub granted: bool,
+    pub sources: Vec<PermissionSourceResponse>,
+}
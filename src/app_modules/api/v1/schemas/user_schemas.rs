@@ -8,6 +8,19 @@ use crate::domain::models::User;
 pub struct RegistrationRequestLocal {
     pub email: String,
     pub password: String,
+    // preferred language for emails, e.g. "en" or "pt-BR";
+    // the Accept-Language header is used when omitted
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResendVerificationRequest {
+    pub email: String,
 }
 
 #[derive(Debug, Serialize)]
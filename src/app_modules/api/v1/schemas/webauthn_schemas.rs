@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+use crate::adapters::dtos::{
+    AuthTokensDto, WebauthnAssertionDto, WebauthnCredentialDto, WebauthnRegistrationDto,
+};
+
+use super::mfa_schemas::MfaStepUpRequest;
+
+// The result of navigator.credentials.create() and an optional label
+#[derive(Debug, Deserialize)]
+pub struct WebauthnRegisterRequest {
+    pub credential: WebauthnRegistrationDto,
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub step_up: MfaStepUpRequest,
+}
+
+// Recovery codes are only handed out with the user's first MFA method,
+// tokens when registering with an enrollment token
+#[derive(Debug, Serialize)]
+pub struct WebauthnRegisterResponse {
+    pub credential: WebauthnCredentialDto,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<AuthTokensDto>,
+}
+
+// The result of navigator.credentials.get() for options from
+// /webauthn/credentials/options
+#[derive(Debug, Deserialize)]
+pub struct WebauthnRemoveRequest {
+    pub credential: WebauthnAssertionDto,
+}
+
+// Passkey login with the result of navigator.credentials.get()
+#[derive(Debug, Deserialize)]
+pub struct WebauthnLoginRequest {
+    pub credential: WebauthnAssertionDto,
+    pub device_identifier: Option<String>,
+    pub device_name: Option<String>,
+    pub device_type: Option<String>,
+}
@@ -3,90 +3,138 @@
 
  created modules must be registered in routes.rs
 */
-use actix_web::{HttpResponse, Responder, get, post, web};
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
 
 use uuid::Uuid;
 
 use crate::app_modules::app_state::AppState;
 
 use super::schemas::RegistrationRequestLocal;
+use super::schemas::ResendVerificationRequest;
 use super::schemas::UserResponse;
+use super::schemas::VerifyEmailRequest;
 use crate::adapters::dtos::RegistrationDto;
-use crate::app_modules::auth::AuthMethod;
+use crate::app_modules::auth::{
+    AuthMethod, AuthenticatedUser, RoleRequirement, SystemAdmin, TenantAdmin,
+};
 use crate::domain::errors::UserError;
 
+use anyhow::anyhow;
 use serde_json::json;
 
+// First language tag of the Accept-Language header
+fn preferred_language(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(|tag| tag.split(';').next().unwrap_or_default().trim().to_string())
+        .filter(|tag| !tag.is_empty() && tag != "*")
+}
+
+// Get User Endpoint
+// users may read their own record; system admins may read anyone and
+// tenant admins anyone in their current tenant
 #[get("/{user_id}")]
-pub async fn get_user(app_state: web::Data<AppState>, user_id: web::Path<Uuid>) -> impl Responder {
+pub async fn get_user(
+    app_state: web::Data<AppState>,
+    caller: AuthenticatedUser,
+    user_id: web::Path<Uuid>,
+) -> Result<HttpResponse, UserError> {
     let user_id = user_id.into_inner();
+    let is_self = caller.user_id() == user_id;
+    if !is_self && !caller.has_role(SystemAdmin::ROLE) && !caller.has_role(TenantAdmin::ROLE) {
+        return Err(UserError::Forbidden);
+    }
 
-    match app_state.user_service.get_user(user_id).await {
-        Ok(Some(user)) => {
-            let user_response = UserResponse::from(user);
-            HttpResponse::Ok().json(user_response)
-        }
-        Ok(None) => HttpResponse::NotFound().json("User not found"),
-        Err(e) => {
-            println!("Error getting user: {}", e);
-            HttpResponse::InternalServerError().json("Failed to get user")
+    let user = app_state
+        .user_service
+        .get_user(user_id)
+        .await?
+        .ok_or(UserError::NotFound)?;
+
+    if !is_self && !caller.has_role(SystemAdmin::ROLE) {
+        // users of other tenants are reported as missing
+        let member = match caller.claims.tenant {
+            Some(tenant_id) => {
+                app_state
+                    .user_service
+                    .is_tenant_member(&user, tenant_id)
+                    .await?
+            }
+            None => false,
+        };
+        if !member {
+            return Err(UserError::NotFound);
         }
     }
+
+    Ok(HttpResponse::Ok().json(UserResponse::from(user)))
 }
 
 // Registration Endpoint
 #[post("/register")]
 pub async fn register(
+    req: HttpRequest,
     app_state: web::Data<AppState>,
     registration_request: web::Json<RegistrationRequestLocal>,
-) -> impl Responder {
+) -> Result<HttpResponse, UserError> {
     let user_data = registration_request.into_inner();
 
     // Select appropriate strategy based on registration method
-    let strategy = match app_state
+    let strategy = app_state
         .auth_service
         .strategies
         .get(&AuthMethod::EmailPassword)
-    {
-        Some(strategy) => strategy,
-        None => {
-            return HttpResponse::InternalServerError().json(json!({
-                "error": "Authentication method not supported"
-            }));
-        }
-    };
+        .ok_or_else(|| anyhow!("Authentication method not supported"))?;
 
-    // Attempt registration
-    match strategy
+    let registered_user = strategy
         .register(RegistrationDto {
             email: user_data.email,
             password: Some(user_data.password),
+            locale: user_data.locale.or_else(|| preferred_language(&req)),
         })
-        .await
-    {
-        Ok(registered_user) => HttpResponse::Created().json(json!({
-            "user": registered_user,
-            "message": "Registration successful. Please verify your email."
-        })),
-        Err(e) => match e {
-            UserError::UserAlreadyExists => HttpResponse::Conflict().json(json!({
-                "error": "User already exists",
-                "code": "USER_EXISTS"
-            })),
-            UserError::ValidationError(validation_errors) => {
-                HttpResponse::BadRequest().json(json!({
-                    "error": "Validation failed",
-                    "details": validation_errors.to_string()
-                }))
-            }
-            UserError::InvalidEmail => HttpResponse::BadRequest().json(json!({
-                "error": "Invalid email format",
-                "code": "INVALID_EMAIL"
-            })),
-            _ => HttpResponse::InternalServerError().json(json!({
-                "error": "Registration failed",
-                "code": "REGISTRATION_ERROR"
-            })),
-        },
-    }
+        .await?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "user": registered_user,
+        "message": "Registration successful. Please verify your email."
+    })))
+}
+
+// Email Verification Endpoint
+#[post("/verify-email")]
+pub async fn verify_email(
+    app_state: web::Data<AppState>,
+    verify_request: web::Json<VerifyEmailRequest>,
+) -> Result<HttpResponse, UserError> {
+    app_state
+        .user_service
+        .verify_email(&verify_request.token)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Email verified successfully."
+    })))
+}
+
+// Resend Verification Email Endpoint
+// Responds the same way whether or not a mail was sent, so that it cannot be
+// used to probe for accounts. Resends are throttled per account.
+#[post("/resend-verification")]
+pub async fn resend_verification(
+    app_state: web::Data<AppState>,
+    resend_request: web::Json<ResendVerificationRequest>,
+) -> Result<HttpResponse, UserError> {
+    let email = resend_request.into_inner().email;
+
+    app_state
+        .user_service
+        .regenerate_email_verification_token(&email)
+        .await?;
+
+    Ok(HttpResponse::Accepted().json(json!({
+        "message": "If the account exists and is not verified yet, a verification email has been sent."
+    })))
 }
@@ -0,0 +1,350 @@
+/*
+ This module holds WebAuthn (passkey) endpoints: registering and removing
+ credentials, and passkey logins. Answering MFA challenges with a
+ credential is part of mfa_endpoints.
+
+ created modules must be registered in routes.rs
+*/
+use actix_web::{HttpRequest, HttpResponse, get, post, web};
+use anyhow::anyhow;
+use uuid::Uuid;
+
+use crate::adapters::dtos::{AuthenticationDto, ClientContextDto};
+use crate::app_modules::app_state::AppState;
+use crate::app_modules::auth::{AuthMethod, AuthenticatedUser, EnrollingUser};
+use crate::domain::errors::UserError;
+
+use super::auth_endpoints::client_context;
+use super::mfa_endpoints::check_step_up;
+use super::schemas::{
+    WebauthnLoginRequest, WebauthnRegisterRequest, WebauthnRegisterResponse, WebauthnRemoveRequest,
+};
+
+// Registration Options Endpoint
+// options for navigator.credentials.create()
+#[post("/webauthn/register/options")]
+pub async fn registration_options(
+    app_state: web::Data<AppState>,
+    user: EnrollingUser,
+) -> Result<HttpResponse, UserError> {
+    let options = app_state
+        .webauthn_service
+        .start_registration(user.user_id())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(options))
+}
+
+// Registration Endpoint
+// stores the new credential; it answers MFA challenges and signs the user
+// in from now on, so users with a verified MFA method confirm it with one.
+// With an enrollment token, the held back login is completed as well
+#[post("/webauthn/register")]
+pub async fn register(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: EnrollingUser,
+    register_request: web::Json<WebauthnRegisterRequest>,
+) -> Result<HttpResponse, UserError> {
+    check_step_up(&req, &app_state, &user, &register_request.step_up).await?;
+
+    let (credential, recovery_codes) = app_state
+        .webauthn_service
+        .finish_registration(
+            user.user_id(),
+            &register_request.credential,
+            register_request.name.as_deref(),
+            &client_context(&req),
+        )
+        .await?;
+
+    let tokens = if user.has_pending_login() {
+        Some(
+            app_state
+                .mfa_service
+                .complete_required_enrollment(&user.claims)
+                .await?,
+        )
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Created().json(WebauthnRegisterResponse {
+        credential,
+        recovery_codes,
+        tokens,
+    }))
+}
+
+// Credentials Endpoint
+#[get("/webauthn/credentials")]
+pub async fn list_credentials(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, UserError> {
+    let credentials = app_state
+        .webauthn_service
+        .list_credentials(user.user_id())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(credentials))
+}
+
+// Reauthentication Options Endpoint
+// options for proving possession of one of the user's credentials, as
+// required to remove one or to confirm registering another
+#[post("/webauthn/credentials/options")]
+pub async fn reauthentication_options(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, UserError> {
+    let options = app_state
+        .webauthn_service
+        .start_authentication(Some(user.user_id()))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(options))
+}
+
+// Credential Removal Endpoint
+#[post("/webauthn/credentials/{id}/remove")]
+pub async fn remove_credential(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    id: web::Path<Uuid>,
+    remove_request: web::Json<WebauthnRemoveRequest>,
+) -> Result<HttpResponse, UserError> {
+    app_state
+        .webauthn_service
+        .remove_credential(
+            user.user_id(),
+            id.into_inner(),
+            &remove_request.credential,
+            &client_context(&req),
+        )
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Passkey Login Options Endpoint
+// options for navigator.credentials.get() without a username
+#[post("/webauthn/login/options")]
+pub async fn login_options(app_state: web::Data<AppState>) -> Result<HttpResponse, UserError> {
+    let options = app_state
+        .webauthn_service
+        .start_authentication(None)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(options))
+}
+
+// Passkey Login Endpoint
+// a user verified passkey stands in for password and second factor, so
+// this answers with session tokens directly
+#[post("/webauthn/login")]
+pub async fn login(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    login_request: web::Json<WebauthnLoginRequest>,
+) -> Result<HttpResponse, UserError> {
+    let login_data = login_request.into_inner();
+
+    let strategy = app_state
+        .auth_service
+        .strategies
+        .get(&AuthMethod::Webauthn)
+        .ok_or_else(|| anyhow!("Authentication method not supported"))?;
+
+    let client = ClientContextDto {
+        device_identifier: login_data.device_identifier,
+        device_name: login_data.device_name,
+        device_type: login_data.device_type,
+        ..client_context(&req)
+    };
+
+    let user = strategy
+        .authenticate(AuthenticationDto {
+            email: None,
+            password: None,
+            assertion: Some(login_data.credential),
+            client: client.clone(),
+        })
+        .await?;
+
+    let tokens = app_state
+        .session_service
+        .create_session(&user, &client)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::{Value, json};
+
+    use crate::test_support::{SoftAuthenticator, TEST_ORIGIN, TEST_RP_ID, TestApp};
+
+    async fn register_passkey(
+        app: &TestApp,
+        token: &str,
+        authenticator: &mut SoftAuthenticator,
+        confirmation: Value,
+    ) -> (StatusCode, Value) {
+        let (status, options) = app
+            .post(
+                "/api/v1/auth/webauthn/register/options",
+                Some(token),
+                json!({}),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", options);
+
+        let mut body = json!({ "credential": authenticator.create(&options) });
+        if let (Some(body), Some(confirmation)) = (body.as_object_mut(), confirmation.as_object()) {
+            body.extend(confirmation.clone());
+        }
+        app.post("/api/v1/auth/webauthn/register", Some(token), body)
+            .await
+    }
+
+    async fn passkey_login(
+        app: &TestApp,
+        authenticator: &mut SoftAuthenticator,
+    ) -> (StatusCode, Value) {
+        let (status, options) = app
+            .post("/api/v1/auth/webauthn/login/options", None, json!({}))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", options);
+
+        app.post(
+            "/api/v1/auth/webauthn/login",
+            None,
+            json!({ "credential": authenticator.get(&options) }),
+        )
+        .await
+    }
+
+    // Signs in a new user with a registered passkey and returns their
+    // access token
+    async fn passkey_user(app: &TestApp, authenticator: &mut SoftAuthenticator) -> String {
+        let (_, token) = app.signed_in_user().await;
+        let (status, body) = register_passkey(app, &token, authenticator, json!({})).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        token
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn passkeys_register_and_sign_in() {
+        let app = TestApp::start().await;
+        let mut authenticator = SoftAuthenticator::new();
+        let token = passkey_user(&app, &mut authenticator).await;
+
+        let (status, credentials) = app
+            .get("/api/v1/auth/webauthn/credentials", Some(&token))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", credentials);
+        assert_eq!(credentials.as_array().map(Vec::len), Some(1));
+
+        let (status, body) = passkey_login(&app, &mut authenticator).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert!(body["access_token"].is_string());
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn sign_counter_going_backwards_is_rejected() {
+        let app = TestApp::start().await;
+        let mut authenticator = SoftAuthenticator::new();
+        passkey_user(&app, &mut authenticator).await;
+
+        for _ in 0..2 {
+            let (status, body) = passkey_login(&app, &mut authenticator).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+
+        // A clone of the authenticator would answer with an older counter
+        authenticator.sign_count = 0;
+        let (status, _) = passkey_login(&app, &mut authenticator).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn responses_for_another_origin_are_rejected() {
+        let app = TestApp::start().await;
+        let (_, token) = app.signed_in_user().await;
+        let mut authenticator = SoftAuthenticator::new();
+        authenticator.origin = "https://app.gandalf.test.evil.example".to_string();
+
+        let (status, body) = register_passkey(&app, &token, &mut authenticator, json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+        authenticator.origin = TEST_ORIGIN.to_string();
+        let (status, body) = register_passkey(&app, &token, &mut authenticator, json!({})).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        authenticator.origin = "https://evil.example".to_string();
+        let (status, _) = passkey_login(&app, &mut authenticator).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn responses_for_another_relying_party_are_rejected() {
+        let app = TestApp::start().await;
+        let (_, token) = app.signed_in_user().await;
+        let mut authenticator = SoftAuthenticator::new();
+        authenticator.rp_id = "evil.example".to_string();
+
+        let (status, body) = register_passkey(&app, &token, &mut authenticator, json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+        authenticator.rp_id = TEST_RP_ID.to_string();
+        let (status, body) = register_passkey(&app, &token, &mut authenticator, json!({})).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        authenticator.rp_id = "evil.example".to_string();
+        let (status, _) = passkey_login(&app, &mut authenticator).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn passkey_sign_in_requires_user_verification() {
+        let app = TestApp::start().await;
+        let mut authenticator = SoftAuthenticator::new();
+        passkey_user(&app, &mut authenticator).await;
+
+        authenticator.user_verified = false;
+        let (status, body) = passkey_login(&app, &mut authenticator).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn further_passkeys_need_confirmation_with_an_existing_one() {
+        let app = TestApp::start().await;
+        let mut first = SoftAuthenticator::new();
+        let token = passkey_user(&app, &mut first).await;
+        let mut second = SoftAuthenticator::new();
+
+        let (status, body) = register_passkey(&app, &token, &mut second, json!({})).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+        assert_eq!(body["code"], "MFA_VERIFICATION_REQUIRED");
+
+        let (status, options) = app
+            .post(
+                "/api/v1/auth/webauthn/credentials/options",
+                Some(&token),
+                json!({}),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", options);
+        let confirmation = json!({ "assertion": first.get(&options) });
+        let (status, body) = register_passkey(&app, &token, &mut second, confirmation).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+}
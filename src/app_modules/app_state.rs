@@ -1,39 +1,137 @@
 use std::sync::Arc;
 
+use crate::adapters::email_transports::{EmailTransport, configure_email_transport};
+use crate::adapters::sms_senders::{SmsSender, configure_sms_sender};
+use crate::config::app_config::AppConfig;
 use crate::config::database::PgPool;
 use crate::domain::services::AuthService;
+use crate::domain::services::AuthzService;
+use crate::domain::services::EmailOutboxService;
 use crate::domain::services::EmailService;
+use crate::domain::services::MfaService;
+use crate::domain::services::PasswordResetService;
+use crate::domain::services::RoleService;
+use crate::domain::services::SessionService;
+use crate::domain::services::TokenService;
 use crate::domain::services::UserService;
+use crate::domain::services::WebauthnService;
 
 use crate::app_modules::auth::{PasswordHasher, configure_auth_strategies};
 
 // Configuration struct to hold application state
 pub struct AppState {
-    db_pool: Arc<PgPool>,
     pub user_service: Arc<UserService>,
     pub auth_service: Arc<AuthService>,
+    pub authz_service: Arc<AuthzService>,
+    pub email_outbox_service: Arc<EmailOutboxService>,
+    pub mfa_service: Arc<MfaService>,
+    pub password_reset_service: Arc<PasswordResetService>,
+    pub role_service: Arc<RoleService>,
+    pub session_service: Arc<SessionService>,
+    pub token_service: Arc<TokenService>,
+    pub webauthn_service: Arc<WebauthnService>,
     // Add other services or configuration as needed
 }
 
 impl AppState {
-    pub fn new(pool: PgPool) -> AppState {
+    pub fn new(pool: PgPool, config: &AppConfig) -> AppState {
+        Self::with_adapters(
+            pool,
+            config,
+            configure_email_transport(config),
+            configure_sms_sender(config),
+        )
+    }
+
+    // like new, with the given email transport and SMS sender instead of
+    // the configured ones, e.g. capturing ones in tests
+    pub fn with_adapters(
+        pool: PgPool,
+        config: &AppConfig,
+        email_transport: Arc<dyn EmailTransport>,
+        sms_sender: Arc<dyn SmsSender>,
+    ) -> AppState {
         let db_pool = Arc::new(pool);
-        let user_service = Arc::new(UserService::new(db_pool.clone()));
+        let user_service = Arc::new(UserService::new(db_pool.clone(), config));
+
+        let email_service = Arc::new(EmailService::new(
+            email_transport,
+            Arc::clone(&user_service),
+            config,
+        ));
+
+        let email_outbox_service = Arc::new(EmailOutboxService::new(
+            db_pool.clone(),
+            Arc::clone(&user_service),
+            Arc::clone(&email_service),
+            config,
+        ));
 
-        let email_service = Arc::new(EmailService::new());
+        let password_hasher = Arc::new(PasswordHasher::new(config));
+
+        let webauthn_service = Arc::new(WebauthnService::new(
+            db_pool.clone(),
+            Arc::clone(&user_service),
+            config,
+        ));
 
         let auth_strategies = configure_auth_strategies(
             Arc::clone(&user_service),
             Arc::clone(&email_service),
-            Arc::new(PasswordHasher::new()),
+            Arc::clone(&password_hasher),
+            Arc::clone(&webauthn_service),
         );
 
         let auth_service = Arc::new(AuthService::new(auth_strategies));
 
+        let token_service = Arc::new(TokenService::new(db_pool.clone(), config));
+        let session_service = Arc::new(SessionService::new(
+            db_pool.clone(),
+            Arc::clone(&user_service),
+            Arc::clone(&token_service),
+            config,
+        ));
+
+        let mfa_service = Arc::new(MfaService::new(
+            db_pool.clone(),
+            Arc::clone(&user_service),
+            Arc::clone(&session_service),
+            Arc::clone(&email_service),
+            sms_sender,
+            Arc::clone(&webauthn_service),
+            config,
+        ));
+
+        let password_reset_service = Arc::new(PasswordResetService::new(
+            db_pool.clone(),
+            Arc::clone(&session_service),
+            Arc::clone(&password_hasher),
+            config,
+        ));
+
+        let authz_service = Arc::new(AuthzService::new(
+            db_pool.clone(),
+            Arc::clone(&user_service),
+            config,
+        ));
+
+        let role_service = Arc::new(RoleService::new(
+            db_pool.clone(),
+            Arc::clone(&user_service),
+            Arc::clone(&authz_service),
+        ));
+
         AppState {
-            db_pool,
             user_service,
             auth_service,
+            authz_service,
+            email_outbox_service,
+            mfa_service,
+            password_reset_service,
+            role_service,
+            session_service,
+            token_service,
+            webauthn_service,
         }
     }
 }
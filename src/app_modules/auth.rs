@@ -1,33 +1,120 @@
 mod auth_strategies;
+mod extractors;
 
 pub use auth_strategies::AuthStrategy;
+pub use extractors::{
+    AuthenticatedUser, CheckAuthorization, EnrollingUser, ManageRoles, RequirePermission,
+    RequireRole, RoleRequirement, SystemAdmin, TenantAdmin, TokenRejection,
+};
 
 pub use crate::domain::errors::UserError;
 
+use crate::config::app_config::AppConfig;
 use crate::domain::services::EmailService;
 use crate::domain::services::UserService;
-use auth_strategies::EmailPasswordAuthStrategy;
+use crate::domain::services::WebauthnService;
+use argon2::password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use auth_strategies::{EmailPasswordAuthStrategy, WebauthnAuthStrategy};
+use rand::rngs::OsRng;
 use std::collections::HashMap;
 use std::sync::Arc;
+use tracing::warn;
 
 // Authentication Methods Enum
 #[derive(Hash, Eq, PartialEq)]
 pub enum AuthMethod {
     EmailPassword,
+    Webauthn,
+    #[allow(dead_code)]
     Google,
+    #[allow(dead_code)]
     Facebook,
     // Other providers can be added
 }
 
-pub struct PasswordHasher;
+// Argon2id password hasher.
+// Hashes are stored as PHC strings ($argon2id$v=19$m=...,t=...,p=...$salt$hash)
+// so the parameters used for each hash travel with it and can be raised later.
+pub struct PasswordHasher {
+    argon2: Argon2<'static>,
+    // verified against when there is no account, so that unknown emails
+    // take as long as wrong passwords
+    dummy_hash: String,
+}
+
 impl PasswordHasher {
-    pub fn new() -> Self {
-        Self
+    pub fn new(config: &AppConfig) -> Self {
+        let params = Params::new(
+            config.argon2_memory_cost,
+            config.argon2_time_cost,
+            config.argon2_parallelism,
+            None,
+        )
+        .expect("ARGON2_* settings must be valid Argon2 parameters");
+
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let dummy_hash = argon2
+            .hash_password(b"gandalf dummy password", &SaltString::generate(&mut OsRng))
+            .expect("hashing with valid parameters succeeds")
+            .to_string();
+
+        Self { argon2, dummy_hash }
     }
 
     pub fn hash_password(&self, password: &str) -> Result<String, UserError> {
-        // TODO: Implement password hashing logic here
-        Ok("hashed_password".to_string())
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = self
+            .argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| UserError::InternalError(anyhow::anyhow!("{}", e)))?;
+        Ok(hash.to_string())
+    }
+
+    // Returns false for a wrong password as well as for stored values that
+    // are not valid PHC strings (e.g. legacy placeholder hashes).
+    pub fn verify_password(&self, password: &str, password_hash: &str) -> bool {
+        let parsed_hash = match PasswordHash::new(password_hash) {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!("Stored password hash is not a valid PHC string: {}", e);
+                return false;
+            }
+        };
+
+        self.argon2
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok()
+    }
+
+    // Spends the time of a password verification without an account to
+    // verify against
+    pub fn verify_dummy(&self, password: &str) {
+        let _ = self.verify_password(password, &self.dummy_hash);
+    }
+
+    // A hash needs upgrading when it was produced with a different algorithm,
+    // version or cost parameters than the ones currently configured.
+    pub fn needs_rehash(&self, password_hash: &str) -> bool {
+        let Ok(parsed_hash) = PasswordHash::new(password_hash) else {
+            return true;
+        };
+
+        if parsed_hash.algorithm != Algorithm::Argon2id.ident()
+            || parsed_hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+
+        match Params::try_from(&parsed_hash) {
+            Ok(params) => {
+                let current = self.argon2.params();
+                params.m_cost() != current.m_cost()
+                    || params.t_cost() != current.t_cost()
+                    || params.p_cost() != current.p_cost()
+            }
+            Err(_) => true,
+        }
     }
 }
 
@@ -36,18 +123,25 @@ pub fn configure_auth_strategies(
     user_service: Arc<UserService>,
     email_service: Arc<EmailService>,
     password_hasher: Arc<PasswordHasher>,
+    webauthn_service: Arc<WebauthnService>,
 ) -> HashMap<AuthMethod, Box<dyn AuthStrategy + Send + Sync>> {
     let mut strategies = HashMap::new();
 
     strategies.insert(
         AuthMethod::EmailPassword,
         Box::new(EmailPasswordAuthStrategy::new(
-            user_service,
+            Arc::clone(&user_service),
             email_service,
             password_hasher,
         )) as Box<dyn AuthStrategy + Send + Sync>,
     );
 
+    strategies.insert(
+        AuthMethod::Webauthn,
+        Box::new(WebauthnAuthStrategy::new(user_service, webauthn_service))
+            as Box<dyn AuthStrategy + Send + Sync>,
+    );
+
     // When ready to add Google Auth
     // strategies.insert(
     //     AuthMethod::Google,
@@ -56,3 +150,91 @@ pub fn configure_auth_strategies(
 
     strategies
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+
+    use super::PasswordHasher;
+    use crate::config::app_config::AppConfig;
+    use crate::test_support::{TEST_PASSWORD, TestApp, test_config, unique_email};
+
+    // test_config with a different Argon2 memory cost
+    fn older_config() -> AppConfig {
+        let mut config = test_config();
+        config.argon2_memory_cost *= 2;
+        config
+    }
+
+    #[test]
+    fn hashes_are_salted_argon2id_phc_strings() {
+        let hasher = PasswordHasher::new(&test_config());
+        let first = hasher.hash_password(TEST_PASSWORD).unwrap();
+        let second = hasher.hash_password(TEST_PASSWORD).unwrap();
+
+        assert!(first.starts_with("$argon2id$v=19$"), "{}", first);
+        assert!(!first.contains(TEST_PASSWORD));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn only_the_right_password_verifies() {
+        let hasher = PasswordHasher::new(&test_config());
+        let hash = hasher.hash_password(TEST_PASSWORD).unwrap();
+
+        assert!(hasher.verify_password(TEST_PASSWORD, &hash));
+        assert!(!hasher.verify_password("Correct-Horse-Battery-8", &hash));
+        assert!(!hasher.verify_password("", &hash));
+        // the placeholder stored before hashing was real
+        assert!(!hasher.verify_password("hashed_password", "hashed_password"));
+    }
+
+    #[test]
+    fn hashes_with_other_parameters_need_a_rehash() {
+        let hasher = PasswordHasher::new(&test_config());
+        let current = hasher.hash_password(TEST_PASSWORD).unwrap();
+        let older = PasswordHasher::new(&older_config())
+            .hash_password(TEST_PASSWORD)
+            .unwrap();
+
+        assert!(!hasher.needs_rehash(&current));
+        assert!(hasher.needs_rehash(&older));
+        assert!(hasher.needs_rehash("hashed_password"));
+        // hashes with other parameters still verify until they are upgraded
+        assert!(hasher.verify_password(TEST_PASSWORD, &older));
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn login_upgrades_an_outdated_hash() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        let user_id = app.register(&email).await;
+        let older = PasswordHasher::new(&older_config())
+            .hash_password(TEST_PASSWORD)
+            .unwrap();
+        let conn = app.pool.get().await.unwrap();
+        conn.execute(
+            "UPDATE auth.users SET password_hash = $2 WHERE id = $1",
+            &[&user_id, &older],
+        )
+        .await
+        .unwrap();
+
+        let (status, body) = app.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let upgraded: String = conn
+            .query_one(
+                "SELECT password_hash FROM auth.users WHERE id = $1",
+                &[&user_id],
+            )
+            .await
+            .unwrap()
+            .get("password_hash");
+        let hasher = PasswordHasher::new(&test_config());
+        assert_ne!(upgraded, older);
+        assert!(!hasher.needs_rehash(&upgraded));
+        assert!(hasher.verify_password(TEST_PASSWORD, &upgraded));
+    }
+}
@@ -1,5 +1,7 @@
 mod base_auth_strategy;
 mod email_password_strategy;
+mod webauthn_strategy;
 
 pub use base_auth_strategy::AuthStrategy;
 pub use email_password_strategy::EmailPasswordAuthStrategy;
+pub use webauthn_strategy::WebauthnAuthStrategy;
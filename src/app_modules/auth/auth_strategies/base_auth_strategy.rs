@@ -1,6 +1,8 @@
+use crate::adapters::dtos::AuthenticationDto;
 use crate::adapters::dtos::RegisteredUserDto;
 use crate::adapters::dtos::RegistrationDto;
 use crate::domain::errors::UserError;
+use crate::domain::models::User;
 
 // Authentication Strategy Trait
 #[async_trait::async_trait]
@@ -9,4 +11,8 @@ pub trait AuthStrategy {
         &self,
         registration_data: RegistrationDto,
     ) -> Result<RegisteredUserDto, UserError>;
+
+    // Verifies the supplied credentials and returns the signed in user.
+    // Token/session issuance is left to the caller.
+    async fn authenticate(&self, credentials: AuthenticationDto) -> Result<User, UserError>;
 }
@@ -1,17 +1,18 @@
 // Email/Password Registration Strategy
 
+use crate::adapters::dtos::AuthenticationDto;
 use crate::adapters::dtos::RegisteredUserDto;
 use crate::adapters::dtos::RegistrationDto;
 use crate::domain::errors::UserError;
-use crate::domain::models::AuthProvider;
+use crate::domain::models::{AuthProvider, User, UserState};
 use crate::domain::services::UserService;
 
 use crate::app_modules::auth::PasswordHasher;
 use crate::app_modules::auth::auth_strategies::AuthStrategy;
-use crate::domain::services::EmailService;
+use crate::domain::services::{EmailService, normalize_locale};
 
 use std::sync::Arc;
-use tracing::error;
+use tracing::{error, warn};
 
 pub struct EmailPasswordAuthStrategy {
     user_service: Arc<UserService>,
@@ -39,23 +40,18 @@ impl AuthStrategy for EmailPasswordAuthStrategy {
         &self,
         registration_data: RegistrationDto,
     ) -> Result<RegisteredUserDto, UserError> {
-        // Validate email format
-        self.email_service
+        // Validate email format and bring it into canonical form
+        let email = self
+            .email_service
             .validate_email(&registration_data.email)?;
 
         // Check if user already exists
-        if self
-            .user_service
-            .user_exists(&registration_data.email)
-            .await?
-        {
+        if self.user_service.user_exists(&email).await? {
             return Err(UserError::UserAlreadyExists);
         }
 
         // Create user with defaults
-        let mut new_user = self
-            .user_service
-            .create_user_with_defaults(&registration_data.email);
+        let mut new_user = self.user_service.create_user_with_defaults(&email);
 
         // Hash password if provided
         if let Some(password) = &registration_data.password {
@@ -67,31 +63,179 @@ impl AuthStrategy for EmailPasswordAuthStrategy {
         }
 
         new_user.auth_provider = AuthProvider::Local;
+        new_user.locale = registration_data
+            .locale
+            .as_deref()
+            .and_then(normalize_locale);
 
+        // the verification email is queued together with the user
         let saved_user = self.user_service.create_user(new_user).await?;
 
-        // Generate verification token
-        let verification_token = self
-            .user_service
-            .generate_email_verification_token(&saved_user.id)
-            .await?;
-
-        // Send verification email asynchronously
-        let email_clone = saved_user.email.clone();
-        tokio::spawn(async move {
-            let email_service = EmailService::new();
-            if let Err(e) = email_service
-                .send_verification_email(email_clone, verification_token)
-                .await
-            {
-                error!("Failed to send verification email: {}", e);
-            }
-        });
-
         Ok(RegisteredUserDto {
             id: saved_user.id,
             email: saved_user.email,
             auth_provider: saved_user.auth_provider.to_string(),
         })
     }
+
+    async fn authenticate(&self, credentials: AuthenticationDto) -> Result<User, UserError> {
+        // Unknown emails and wrong passwords share one error, and both pay
+        // for a hash verification, so that neither the response nor its
+        // timing reveals which accounts exist
+        let (Some(email), Some(password)) = (&credentials.email, &credentials.password) else {
+            return Err(UserError::InvalidCredentials);
+        };
+        let user = self.user_service.find_by_email(email).await?;
+
+        let Some((user, password_hash)) = user.and_then(|user| {
+            let password_hash = user.password_hash.clone()?;
+            Some((user, password_hash))
+        }) else {
+            self.password_hasher.verify_dummy(password);
+            return Err(UserError::InvalidCredentials);
+        };
+
+        // Locked accounts are rejected before the password is checked so a
+        // lockout cannot be used as a password oracle
+        if user.is_locked() {
+            self.password_hasher.verify_dummy(password);
+            return Err(UserError::account_locked(user.account_locked_until));
+        }
+
+        if !self
+            .password_hasher
+            .verify_password(password, &password_hash)
+        {
+            if let Some(locked_until) = self.user_service.register_failed_login(user.id).await? {
+                warn!("Account {} locked after repeated failed logins", user.id);
+                return Err(UserError::account_locked(Some(locked_until)));
+            }
+            return Err(UserError::InvalidCredentials);
+        }
+
+        if matches!(user.user_state, UserState::Disabled | UserState::Deleted) {
+            return Err(UserError::AccountDisabled);
+        }
+
+        // Transparently upgrade hashes produced with outdated parameters.
+        // A failed upgrade must not fail the login itself.
+        if self.password_hasher.needs_rehash(&password_hash) {
+            match self.password_hasher.hash_password(password) {
+                Ok(new_hash) => {
+                    if let Err(e) = self
+                        .user_service
+                        .update_password_hash(user.id, &new_hash)
+                        .await
+                    {
+                        warn!("Failed to upgrade password hash for {}: {}", user.id, e);
+                    }
+                }
+                Err(e) => warn!("Failed to rehash password for {}: {}", user.id, e),
+            }
+        }
+
+        self.user_service
+            .record_successful_login(
+                user.id,
+                credentials.client.ip_address,
+                credentials.client.user_agent.as_deref(),
+            )
+            .await?;
+
+        Ok(user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::{Value, json};
+    use uuid::Uuid;
+
+    use crate::test_support::{TestApp, test_config, unique_email};
+
+    async fn wrong_password(app: &TestApp, email: &str) -> (StatusCode, Value) {
+        app.post(
+            "/api/v1/auth/login",
+            None,
+            json!({ "email": email, "password": "not the password" }),
+        )
+        .await
+    }
+
+    async fn account(app: &TestApp, user_id: Uuid) -> (i32, String) {
+        let row = app
+            .pool
+            .get()
+            .await
+            .unwrap()
+            .query_one(
+                "SELECT failed_login_attempts, user_state::TEXT AS user_state
+                 FROM auth.users WHERE id = $1",
+                &[&user_id],
+            )
+            .await
+            .unwrap();
+        (row.get("failed_login_attempts"), row.get("user_state"))
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn repeated_failures_lock_the_account_until_the_lockout_ends() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        let user_id = app.register(&email).await;
+        let max_attempts = test_config().max_failed_login_attempts;
+
+        for _ in 1..max_attempts {
+            let (status, body) = wrong_password(&app, &email).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+        }
+        let (status, body) = wrong_password(&app, &email).await;
+        assert_eq!(status, StatusCode::LOCKED, "{}", body);
+        assert_eq!(body["code"], "ACCOUNT_LOCKED");
+        assert!(body["details"]["retry_after"].as_i64() > Some(0));
+        assert_eq!(account(&app, user_id).await.1, "locked");
+
+        // the right password does not get in while locked
+        let (status, body) = app.login(&email).await;
+        assert_eq!(status, StatusCode::LOCKED, "{}", body);
+
+        // once the lockout has run out the account unlocks on the next login
+        app.pool
+            .get()
+            .await
+            .unwrap()
+            .execute(
+                "UPDATE auth.users SET account_locked_until = now() - interval '1 second'
+                 WHERE id = $1",
+                &[&user_id],
+            )
+            .await
+            .unwrap();
+        let (status, body) = app.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (attempts, state) = account(&app, user_id).await;
+        assert_eq!(attempts, 0);
+        assert_ne!(state, "locked");
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn a_successful_login_resets_the_failure_count() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        let user_id = app.register(&email).await;
+        let max_attempts = test_config().max_failed_login_attempts;
+
+        for _ in 0..2 {
+            for _ in 1..max_attempts {
+                let (status, body) = wrong_password(&app, &email).await;
+                assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+            }
+            let (status, body) = app.login(&email).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            assert_eq!(account(&app, user_id).await.0, 0);
+        }
+    }
 }
@@ -0,0 +1,74 @@
+// WebAuthn (Passkey) Authentication Strategy
+
+use crate::adapters::dtos::AuthenticationDto;
+use crate::adapters::dtos::RegisteredUserDto;
+use crate::adapters::dtos::RegistrationDto;
+use crate::domain::errors::UserError;
+use crate::domain::models::{User, UserState};
+use crate::domain::services::{UserService, WebauthnService};
+
+use crate::app_modules::auth::auth_strategies::AuthStrategy;
+
+use std::sync::Arc;
+
+pub struct WebauthnAuthStrategy {
+    user_service: Arc<UserService>,
+    webauthn_service: Arc<WebauthnService>,
+}
+
+impl WebauthnAuthStrategy {
+    pub fn new(user_service: Arc<UserService>, webauthn_service: Arc<WebauthnService>) -> Self {
+        Self {
+            user_service,
+            webauthn_service,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthStrategy for WebauthnAuthStrategy {
+    // Passkeys are added to existing accounts, see /webauthn/register
+    async fn register(
+        &self,
+        _registration_data: RegistrationDto,
+    ) -> Result<RegisteredUserDto, UserError> {
+        Err(UserError::InvalidRequest(
+            "Accounts cannot be created with a passkey".to_string(),
+        ))
+    }
+
+    async fn authenticate(&self, credentials: AuthenticationDto) -> Result<User, UserError> {
+        let assertion = credentials
+            .assertion
+            .as_ref()
+            .ok_or(UserError::InvalidCredentials)?;
+
+        let user_id = self
+            .webauthn_service
+            .verify_passkey(assertion, &credentials.client)
+            .await?;
+        let user = self
+            .user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::InvalidCredentials)?;
+
+        // a passkey does not get around a lockout
+        if user.is_locked() {
+            return Err(UserError::account_locked(user.account_locked_until));
+        }
+        if matches!(user.user_state, UserState::Disabled | UserState::Deleted) {
+            return Err(UserError::AccountDisabled);
+        }
+
+        self.user_service
+            .record_successful_login(
+                user.id,
+                credentials.client.ip_address,
+                credentials.client.user_agent.as_deref(),
+            )
+            .await?;
+
+        Ok(user)
+    }
+}
@@ -0,0 +1,210 @@
+/*
+ Request extractors for authenticated routes.
+
+ The authentication middleware validates the bearer token; handlers state
+ what they need by declaring one of these as an argument:
+
+   AuthenticatedUser                 any valid access token
+   RequireRole<SystemAdmin>          a token carrying the role
+   RequirePermission<ManageRoles>    a token granting the permission
+   EnrollingUser                     an access token or an enrollment token
+
+ A missing, invalid or revoked token is answered with 401, a token lacking
+ the role or the permission with 403. Enrollment tokens are restricted to
+ setting up a first MFA method and are refused everywhere else with 403
+ MFA_ENROLLMENT_REQUIRED. Roles and permissions are read from the token,
+ so changes apply from the user's next login or refresh.
+*/
+use std::future::{Ready, ready};
+use std::marker::PhantomData;
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::{AccessTokenClaims, MFA_ENROLLMENT_SCOPE, permissions_grant};
+
+// A role an endpoint can require
+pub trait RoleRequirement {
+    const ROLE: &'static str;
+}
+
+// A "resource:action" permission an endpoint can require
+pub trait PermissionRequirement {
+    const PERMISSION: &'static str;
+}
+
+macro_rules! role_requirement {
+    ($name:ident, $role:literal) => {
+        pub struct $name;
+
+        impl RoleRequirement for $name {
+            const ROLE: &'static str = $role;
+        }
+    };
+}
+
+macro_rules! permission_requirement {
+    ($name:ident, $permission:literal) => {
+        pub struct $name;
+
+        impl PermissionRequirement for $name {
+            const PERMISSION: &'static str = $permission;
+        }
+    };
+}
+
+role_requirement!(SystemAdmin, "system_admin");
+role_requirement!(TenantAdmin, "tenant_admin");
+
+permission_requirement!(ManageRoles, "role:manage");
+permission_requirement!(CheckAuthorization, "authz:check");
+
+// The caller, as identified by a valid access token
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub claims: AccessTokenClaims,
+}
+
+impl AuthenticatedUser {
+    pub fn user_id(&self) -> Uuid {
+        self.claims.sub
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.claims.roles.iter().any(|r| r == role)
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        permissions_grant(&self.claims.permissions, permission)
+    }
+
+    // claims are stored by the authentication middleware
+    fn from_extensions(req: &HttpRequest) -> Result<Self, UserError> {
+        let claims = claims_from_extensions(req)?;
+        if claims.scope.is_some() {
+            return Err(UserError::MfaEnrollmentRequired);
+        }
+        Ok(AuthenticatedUser { claims })
+    }
+}
+
+// Why the authentication middleware refused the request's bearer token.
+// Stored in place of the claims, so only endpoints that need a caller
+// report it.
+#[derive(Debug, Clone, Copy)]
+pub enum TokenRejection {
+    Invalid,
+    Revoked,
+}
+
+impl From<TokenRejection> for UserError {
+    fn from(rejection: TokenRejection) -> Self {
+        match rejection {
+            TokenRejection::Invalid => UserError::InvalidToken,
+            TokenRejection::Revoked => UserError::TokenRevoked,
+        }
+    }
+}
+
+fn claims_from_extensions(req: &HttpRequest) -> Result<AccessTokenClaims, UserError> {
+    let extensions = req.extensions();
+    if let Some(claims) = extensions.get::<AccessTokenClaims>() {
+        return Ok(claims.clone());
+    }
+    match extensions.get::<TokenRejection>() {
+        Some(rejection) => Err((*rejection).into()),
+        None => Err(UserError::Unauthorized),
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = UserError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(AuthenticatedUser::from_extensions(req))
+    }
+}
+
+pub struct RequireRole<R: RoleRequirement> {
+    #[allow(dead_code)] // for handlers that act on behalf of the caller
+    pub user: AuthenticatedUser,
+    _role: PhantomData<R>,
+}
+
+impl<R: RoleRequirement> FromRequest for RequireRole<R> {
+    type Error = UserError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = AuthenticatedUser::from_extensions(req).and_then(|user| {
+            if user.has_role(R::ROLE) {
+                Ok(RequireRole {
+                    user,
+                    _role: PhantomData,
+                })
+            } else {
+                Err(UserError::Forbidden)
+            }
+        });
+        ready(result)
+    }
+}
+
+pub struct RequirePermission<P: PermissionRequirement> {
+    pub user: AuthenticatedUser,
+    _permission: PhantomData<P>,
+}
+
+impl<P: PermissionRequirement> FromRequest for RequirePermission<P> {
+    type Error = UserError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = AuthenticatedUser::from_extensions(req).and_then(|user| {
+            if user.has_permission(P::PERMISSION) {
+                Ok(RequirePermission {
+                    user,
+                    _permission: PhantomData,
+                })
+            } else {
+                Err(UserError::Forbidden)
+            }
+        });
+        ready(result)
+    }
+}
+
+// The caller of an MFA enrollment endpoint: a signed in user, or one whose
+// login is held back until they enroll a first method
+#[derive(Debug, Clone)]
+pub struct EnrollingUser {
+    pub claims: AccessTokenClaims,
+}
+
+impl EnrollingUser {
+    pub fn user_id(&self) -> Uuid {
+        self.claims.sub
+    }
+
+    // true for enrollment tokens, whose login is completed once a method
+    // is enrolled
+    pub fn has_pending_login(&self) -> bool {
+        self.claims.scope.as_deref() == Some(MFA_ENROLLMENT_SCOPE)
+    }
+}
+
+impl FromRequest for EnrollingUser {
+    type Error = UserError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = claims_from_extensions(req).and_then(|claims| match claims.scope.as_deref() {
+            None | Some(MFA_ENROLLMENT_SCOPE) => Ok(EnrollingUser { claims }),
+            Some(_) => Err(UserError::Forbidden),
+        });
+        ready(result)
+    }
+}
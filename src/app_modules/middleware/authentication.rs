@@ -0,0 +1,119 @@
+/*
+ Authentication middleware.
+
+ Validates the bearer token of requests that carry one and stores its
+ claims in the request extensions, where the AuthenticatedUser,
+ RequireRole and RequirePermission extractors pick them up. An invalid,
+ expired or revoked token is recorded there instead and reported with 401
+ by those extractors, so public endpoints work with or without a token,
+ even a stale one a client still sends.
+*/
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage, web};
+
+use crate::app_modules::app_state::AppState;
+use crate::app_modules::auth::TokenRejection;
+use crate::domain::errors::UserError;
+
+pub async fn authentication(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    if let Some(token) = token {
+        let app_state = req
+            .app_data::<web::Data<AppState>>()
+            .expect("AppState must be registered")
+            .clone();
+
+        match app_state.token_service.validate_access_token(&token).await {
+            Ok(claims) => {
+                req.extensions_mut().insert(claims);
+            }
+            Err(UserError::InvalidToken) => {
+                req.extensions_mut().insert(TokenRejection::Invalid);
+            }
+            Err(UserError::TokenRevoked) => {
+                req.extensions_mut().insert(TokenRejection::Revoked);
+            }
+            // the token could not be checked; rendered here so the request
+            // id middleware can stamp it
+            Err(e) => return Ok(req.error_response(e)),
+        }
+    }
+
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::{Value, json};
+
+    use crate::test_support::{TEST_PASSWORD, TestApp, unique_email};
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn public_endpoints_ignore_an_invalid_token() {
+        let app = TestApp::start().await;
+
+        let (status, body) = app
+            .post(
+                "/api/v1/users/register",
+                Some("not-a-token"),
+                json!({ "email": unique_email(), "password": TEST_PASSWORD }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn protected_endpoints_reject_an_invalid_token() {
+        let app = TestApp::start().await;
+
+        let (status, body) = app.get("/api/v1/auth/tenants", Some("not-a-token")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "INVALID_TOKEN");
+
+        let (status, body) = app.get("/api/v1/auth/tenants", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_ne!(body["code"], "INVALID_TOKEN");
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn revoked_tokens_are_rejected_only_where_a_caller_is_needed() {
+        let app = TestApp::start().await;
+        let (_, access_token) = app.signed_in_user().await;
+
+        let (status, _) = app
+            .post("/api/v1/auth/logout", Some(&access_token), Value::Null)
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, body) = app.get("/api/v1/auth/tenants", Some(&access_token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "INVALID_TOKEN");
+
+        let email = unique_email();
+        app.register(&email).await;
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/login",
+                Some(&access_token),
+                json!({ "email": email, "password": TEST_PASSWORD }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+}
@@ -0,0 +1,47 @@
+/*
+ Request id middleware.
+
+ Echoes the request id generated by TracingLogger in the X-Request-Id
+ response header and stamps it into error envelopes, so that a client
+ report can be matched with the server logs.
+
+ Must be registered inside TracingLogger.
+*/
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpMessage};
+use tracing_actix_web::RequestId;
+use uuid::Uuid;
+
+use crate::app_modules::api::errors::ErrorEnvelope;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| Uuid::from(*id))
+        .unwrap_or_else(Uuid::new_v4)
+        .to_string();
+
+    let mut res = next.call(req).await?.map_into_boxed_body();
+
+    let envelope = res.response().extensions().get::<ErrorEnvelope>().cloned();
+    if let Some(mut envelope) = envelope {
+        envelope.request_id = Some(request_id.clone());
+        let body =
+            serde_json::to_vec(&envelope).map_err(actix_web::error::ErrorInternalServerError)?;
+        res = res.map_body(|_, _| BoxBody::new(body));
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(res)
+}
@@ -3,13 +3,53 @@ This module contains config settings for authentication.
 It reads the following environment variables:
 - JWT_SECRET
 - JWT_EXPIRATION
+- JWT_ISSUER
+- JWT_AUDIENCE
 - REFRESH_TOKEN_EXPIRATION
 - ACCESS_TOKEN_EXPIRATION
 - PASSWORD_RESET_EXPIRATION
 - VERIFICATION_CODE_EXPIRATION
+- VERIFICATION_RESEND_INTERVAL
+- DISPOSABLE_EMAIL_DOMAINS (comma separated)
+- DISPOSABLE_EMAIL_DOMAINS_FILE (one domain per line)
 - MAX_FAILED_LOGIN_ATTEMPTS
 - ACCOUNT_LOCKOUT_DURATION
 - SESSION_TIMEOUT
+- ARGON2_MEMORY_COST
+- ARGON2_TIME_COST
+- ARGON2_PARALLELISM
+- EMAIL_TRANSPORT (smtp, file or memory)
+- EMAIL_FROM
+- EMAIL_TEMPLATES_DIR
+- EMAIL_DEFAULT_LOCALE
+- APP_BASE_URL (used for links in emails)
+- EMAIL_SPOOL_DIR (file transport)
+- SMTP_HOST
+- SMTP_PORT
+- SMTP_USERNAME
+- SMTP_PASSWORD
+- SMTP_TLS (starttls, implicit or none)
+- EMAIL_OUTBOX_POLL_INTERVAL
+- EMAIL_OUTBOX_BATCH_SIZE
+- EMAIL_OUTBOX_MAX_ATTEMPTS
+- EMAIL_OUTBOX_BACKOFF_BASE
+- EMAIL_OUTBOX_BACKOFF_MAX
+- AUTHZ_DECISION_TTL
+- MFA_ENCRYPTION_KEY (base64, 32 bytes)
+- MFA_ISSUER
+- MFA_CHALLENGE_EXPIRATION
+- MFA_ENROLLMENT_EXPIRATION
+- MFA_MAX_ATTEMPTS
+- MFA_OTP_EXPIRATION
+- MFA_OTP_RESEND_INTERVAL
+- SMS_SENDER (http or memory)
+- SMS_GATEWAY_URL
+- SMS_GATEWAY_TOKEN
+- SMS_FROM
+- WEBAUTHN_RP_ID
+- WEBAUTHN_RP_NAME
+- WEBAUTHN_ORIGINS (comma separated)
+- WEBAUTHN_CHALLENGE_EXPIRATION
 
 and sets default values for any missing environment variables.
 The default values are defined in the defaults module.
@@ -21,55 +61,226 @@ use std::sync::OnceLock;
 use super::defaults;
 
 #[derive(Clone, Debug)]
+#[allow(dead_code)] // not every setting has a consumer yet
 pub struct AppConfig {
     pub jwt_secret: String,
-    pub jwt_expiration: u32,              // in minutes
-    pub refresh_token_expiration: u8,     // in days
-    pub access_token_expiration: u8,      // in minutes
-    pub password_reset_expiration: u8,    // in hours
-    pub verification_code_expiration: u8, // in hours
+    pub jwt_expiration: u32, // in minutes, upper bound for any issued JWT
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
+    pub refresh_token_expiration: u8,      // in days
+    pub access_token_expiration: u8,       // in minutes
+    pub password_reset_expiration: u8,     // in hours
+    pub verification_code_expiration: u8,  // in hours
+    pub verification_resend_interval: u32, // in seconds
+    pub disposable_email_domains: Vec<String>,
+    pub disposable_email_domains_file: Option<String>,
     pub max_failed_login_attempts: u8,
     pub account_lockout_duration: u8, // in minutes
     pub session_timeout: u8,          // in minutes
+    pub argon2_memory_cost: u32,      // in KiB
+    pub argon2_time_cost: u32,        // iterations
+    pub argon2_parallelism: u32,      // lanes
+    pub email_transport: String,
+    pub email_from: String,
+    pub email_templates_dir: String,
+    pub email_default_locale: String,
+    pub app_base_url: String,
+    pub email_spool_dir: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_tls: String,
+    pub email_outbox_poll_interval: u32, // in seconds
+    pub email_outbox_batch_size: u32,
+    pub email_outbox_max_attempts: u8,
+    pub email_outbox_backoff_base: u32, // in seconds
+    pub email_outbox_backoff_max: u32,  // in seconds
+    pub authz_decision_ttl: u32,        // in seconds
+    pub mfa_encryption_key: String,
+    pub mfa_issuer: String,
+    pub mfa_challenge_expiration: u8,  // in minutes
+    pub mfa_enrollment_expiration: u8, // in minutes
+    pub mfa_max_attempts: u8,
+    pub mfa_otp_expiration: u8,       // in minutes
+    pub mfa_otp_resend_interval: u32, // in seconds
+    pub sms_sender: String,
+    pub sms_gateway_url: String,
+    pub sms_gateway_token: Option<String>,
+    pub sms_from: String,
+    pub webauthn_rp_id: String,
+    pub webauthn_rp_name: String,
+    pub webauthn_origins: Vec<String>,
+    pub webauthn_challenge_expiration: u8, // in minutes
 }
 
 impl AppConfig {
     fn load() -> Self {
         println!("Loading app config ... ... ...");
+        Self::from_vars(|name| env::var(name))
+    }
+
+    // builds the config from explicitly given variables, for tests
+    #[cfg(test)]
+    pub fn from_pairs(pairs: &[(&str, &str)]) -> Self {
+        Self::from_vars(|name| {
+            pairs
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+                .ok_or(env::VarError::NotPresent)
+        })
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Result<String, env::VarError>) -> Self {
         Self {
-            jwt_secret: env::var("JWT_SECRET").expect("JWT_SECRET must be set"),
-            jwt_expiration: env::var("JWT_EXPIRATION")
+            jwt_secret: var("JWT_SECRET").expect("JWT_SECRET must be set"),
+            jwt_expiration: var("JWT_EXPIRATION")
                 .unwrap_or_else(|_| defaults::JWT_EXPIRATION.to_string())
                 .parse()
                 .expect("JWT_EXPIRATION must be a number"),
-            refresh_token_expiration: env::var("REFRESH_TOKEN_EXPIRATION")
+            jwt_issuer: var("JWT_ISSUER").unwrap_or_else(|_| defaults::JWT_ISSUER.to_string()),
+            jwt_audience: var("JWT_AUDIENCE")
+                .unwrap_or_else(|_| defaults::JWT_AUDIENCE.to_string()),
+            refresh_token_expiration: var("REFRESH_TOKEN_EXPIRATION")
                 .unwrap_or_else(|_| defaults::REFRESH_TOKEN_EXPIRATION.to_string())
                 .parse()
                 .expect("REFRESH_TOKEN_EXPIRATION must be a number"),
-            access_token_expiration: env::var("ACCESS_TOKEN_EXPIRATION")
+            access_token_expiration: var("ACCESS_TOKEN_EXPIRATION")
                 .unwrap_or_else(|_| defaults::ACCESS_TOKEN_EXPIRATION.to_string())
                 .parse()
                 .expect("ACCESS_TOKEN_EXPIRATION must be a number"),
-            password_reset_expiration: env::var("PASSWORD_RESET_EXPIRATION")
+            password_reset_expiration: var("PASSWORD_RESET_EXPIRATION")
                 .unwrap_or_else(|_| defaults::PASSWORD_RESET_EXPIRATION.to_string())
                 .parse()
                 .expect("PASSWORD_RESET_EXPIRATION must be a number"),
-            verification_code_expiration: env::var("VERIFICATION_CODE_EXPIRATION")
+            verification_code_expiration: var("VERIFICATION_CODE_EXPIRATION")
                 .unwrap_or_else(|_| defaults::VERIFICATION_CODE_EXPIRATION.to_string())
                 .parse()
                 .expect("VERIFICATION_CODE_EXPIRATION must be a number"),
-            max_failed_login_attempts: env::var("MAX_FAILED_LOGIN_ATTEMPTS")
+            verification_resend_interval: var("VERIFICATION_RESEND_INTERVAL")
+                .unwrap_or_else(|_| defaults::VERIFICATION_RESEND_INTERVAL.to_string())
+                .parse()
+                .expect("VERIFICATION_RESEND_INTERVAL must be a number"),
+            disposable_email_domains: var("DISPOSABLE_EMAIL_DOMAINS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|domain| !domain.is_empty())
+                .map(str::to_string)
+                .collect(),
+            disposable_email_domains_file: var("DISPOSABLE_EMAIL_DOMAINS_FILE")
+                .ok()
+                .filter(|path| !path.is_empty()),
+            max_failed_login_attempts: var("MAX_FAILED_LOGIN_ATTEMPTS")
                 .unwrap_or_else(|_| defaults::MAX_FAILED_LOGIN_ATTEMPTS.to_string())
                 .parse()
                 .expect("MAX_FAILED_LOGIN_ATTEMPTS to be a number"),
-            account_lockout_duration: env::var("ACCOUNT_LOCKOUT_DURATION")
+            account_lockout_duration: var("ACCOUNT_LOCKOUT_DURATION")
                 .unwrap_or_else(|_| defaults::ACCOUNT_LOCKOUT_DURATION.to_string())
                 .parse()
                 .expect("ACCOUNT_LOCKOUT_DURATION must be a number"),
-            session_timeout: env::var("SESSION_TIMEOUT")
+            session_timeout: var("SESSION_TIMEOUT")
                 .unwrap_or_else(|_| defaults::SESSION_TIMEOUT.to_string())
                 .parse()
                 .expect("SESSION_TIMEOUT must be a number"),
+            argon2_memory_cost: var("ARGON2_MEMORY_COST")
+                .unwrap_or_else(|_| defaults::ARGON2_MEMORY_COST.to_string())
+                .parse()
+                .expect("ARGON2_MEMORY_COST must be a number"),
+            argon2_time_cost: var("ARGON2_TIME_COST")
+                .unwrap_or_else(|_| defaults::ARGON2_TIME_COST.to_string())
+                .parse()
+                .expect("ARGON2_TIME_COST must be a number"),
+            argon2_parallelism: var("ARGON2_PARALLELISM")
+                .unwrap_or_else(|_| defaults::ARGON2_PARALLELISM.to_string())
+                .parse()
+                .expect("ARGON2_PARALLELISM must be a number"),
+            email_transport: var("EMAIL_TRANSPORT")
+                .unwrap_or_else(|_| defaults::EMAIL_TRANSPORT.to_string()),
+            email_from: var("EMAIL_FROM").unwrap_or_else(|_| defaults::EMAIL_FROM.to_string()),
+            email_templates_dir: var("EMAIL_TEMPLATES_DIR")
+                .unwrap_or_else(|_| defaults::EMAIL_TEMPLATES_DIR.to_string()),
+            email_default_locale: var("EMAIL_DEFAULT_LOCALE")
+                .unwrap_or_else(|_| defaults::EMAIL_DEFAULT_LOCALE.to_string()),
+            app_base_url: var("APP_BASE_URL")
+                .unwrap_or_else(|_| defaults::APP_BASE_URL.to_string()),
+            email_spool_dir: var("EMAIL_SPOOL_DIR")
+                .unwrap_or_else(|_| defaults::EMAIL_SPOOL_DIR.to_string()),
+            smtp_host: var("SMTP_HOST").unwrap_or_else(|_| defaults::SMTP_HOST.to_string()),
+            smtp_port: var("SMTP_PORT")
+                .unwrap_or_else(|_| defaults::SMTP_PORT.to_string())
+                .parse()
+                .expect("SMTP_PORT must be a number"),
+            smtp_username: var("SMTP_USERNAME").ok().filter(|v| !v.is_empty()),
+            smtp_password: var("SMTP_PASSWORD").ok().filter(|v| !v.is_empty()),
+            smtp_tls: var("SMTP_TLS").unwrap_or_else(|_| defaults::SMTP_TLS.to_string()),
+            email_outbox_poll_interval: var("EMAIL_OUTBOX_POLL_INTERVAL")
+                .unwrap_or_else(|_| defaults::EMAIL_OUTBOX_POLL_INTERVAL.to_string())
+                .parse()
+                .expect("EMAIL_OUTBOX_POLL_INTERVAL must be a number"),
+            email_outbox_batch_size: var("EMAIL_OUTBOX_BATCH_SIZE")
+                .unwrap_or_else(|_| defaults::EMAIL_OUTBOX_BATCH_SIZE.to_string())
+                .parse()
+                .expect("EMAIL_OUTBOX_BATCH_SIZE must be a number"),
+            email_outbox_max_attempts: var("EMAIL_OUTBOX_MAX_ATTEMPTS")
+                .unwrap_or_else(|_| defaults::EMAIL_OUTBOX_MAX_ATTEMPTS.to_string())
+                .parse()
+                .expect("EMAIL_OUTBOX_MAX_ATTEMPTS must be a number"),
+            email_outbox_backoff_base: var("EMAIL_OUTBOX_BACKOFF_BASE")
+                .unwrap_or_else(|_| defaults::EMAIL_OUTBOX_BACKOFF_BASE.to_string())
+                .parse()
+                .expect("EMAIL_OUTBOX_BACKOFF_BASE must be a number"),
+            email_outbox_backoff_max: var("EMAIL_OUTBOX_BACKOFF_MAX")
+                .unwrap_or_else(|_| defaults::EMAIL_OUTBOX_BACKOFF_MAX.to_string())
+                .parse()
+                .expect("EMAIL_OUTBOX_BACKOFF_MAX must be a number"),
+            authz_decision_ttl: var("AUTHZ_DECISION_TTL")
+                .unwrap_or_else(|_| defaults::AUTHZ_DECISION_TTL.to_string())
+                .parse()
+                .expect("AUTHZ_DECISION_TTL must be a number"),
+            mfa_encryption_key: var("MFA_ENCRYPTION_KEY").expect("MFA_ENCRYPTION_KEY must be set"),
+            mfa_issuer: var("MFA_ISSUER").unwrap_or_else(|_| defaults::MFA_ISSUER.to_string()),
+            mfa_challenge_expiration: var("MFA_CHALLENGE_EXPIRATION")
+                .unwrap_or_else(|_| defaults::MFA_CHALLENGE_EXPIRATION.to_string())
+                .parse()
+                .expect("MFA_CHALLENGE_EXPIRATION must be a number"),
+            mfa_enrollment_expiration: var("MFA_ENROLLMENT_EXPIRATION")
+                .unwrap_or_else(|_| defaults::MFA_ENROLLMENT_EXPIRATION.to_string())
+                .parse()
+                .expect("MFA_ENROLLMENT_EXPIRATION must be a number"),
+            mfa_max_attempts: var("MFA_MAX_ATTEMPTS")
+                .unwrap_or_else(|_| defaults::MFA_MAX_ATTEMPTS.to_string())
+                .parse()
+                .expect("MFA_MAX_ATTEMPTS must be a number"),
+            mfa_otp_expiration: var("MFA_OTP_EXPIRATION")
+                .unwrap_or_else(|_| defaults::MFA_OTP_EXPIRATION.to_string())
+                .parse()
+                .expect("MFA_OTP_EXPIRATION must be a number"),
+            mfa_otp_resend_interval: var("MFA_OTP_RESEND_INTERVAL")
+                .unwrap_or_else(|_| defaults::MFA_OTP_RESEND_INTERVAL.to_string())
+                .parse()
+                .expect("MFA_OTP_RESEND_INTERVAL must be a number"),
+            sms_sender: var("SMS_SENDER").unwrap_or_else(|_| defaults::SMS_SENDER.to_string()),
+            sms_gateway_url: var("SMS_GATEWAY_URL")
+                .unwrap_or_else(|_| defaults::SMS_GATEWAY_URL.to_string()),
+            sms_gateway_token: var("SMS_GATEWAY_TOKEN").ok().filter(|v| !v.is_empty()),
+            sms_from: var("SMS_FROM").unwrap_or_else(|_| defaults::SMS_FROM.to_string()),
+            webauthn_rp_id: var("WEBAUTHN_RP_ID")
+                .unwrap_or_else(|_| defaults::WEBAUTHN_RP_ID.to_string()),
+            webauthn_rp_name: var("WEBAUTHN_RP_NAME")
+                .unwrap_or_else(|_| defaults::WEBAUTHN_RP_NAME.to_string()),
+            webauthn_origins: var("WEBAUTHN_ORIGINS")
+                .unwrap_or_else(|_| defaults::WEBAUTHN_ORIGINS.to_string())
+                .split(',')
+                .map(|origin| origin.trim().trim_end_matches('/'))
+                .filter(|origin| !origin.is_empty())
+                .map(str::to_string)
+                .collect(),
+            webauthn_challenge_expiration: var("WEBAUTHN_CHALLENGE_EXPIRATION")
+                .unwrap_or_else(|_| defaults::WEBAUTHN_CHALLENGE_EXPIRATION.to_string())
+                .parse()
+                .expect("WEBAUTHN_CHALLENGE_EXPIRATION must be a number"),
         }
     }
 }
@@ -78,5 +289,5 @@ impl AppConfig {
 static CONFIG_INSTANCE: OnceLock<AppConfig> = OnceLock::new();
 
 pub async fn get_config() -> &'static AppConfig {
-    CONFIG_INSTANCE.get_or_init(|| AppConfig::load())
+    CONFIG_INSTANCE.get_or_init(AppConfig::load)
 }
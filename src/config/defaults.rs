@@ -5,14 +5,60 @@ in the environment.
  */
 
 // Auth defaults
+pub const JWT_ISSUER: &str = "gandalf";
+pub const JWT_AUDIENCE: &str = "gandalf";
 pub const JWT_EXPIRATION: u8 = 60;
 pub const REFRESH_TOKEN_EXPIRATION: u8 = 30;
 pub const ACCESS_TOKEN_EXPIRATION: u8 = 15;
 pub const PASSWORD_RESET_EXPIRATION: u8 = 24;
 pub const VERIFICATION_CODE_EXPIRATION: u8 = 24;
+pub const VERIFICATION_RESEND_INTERVAL: u32 = 60;
 pub const MAX_FAILED_LOGIN_ATTEMPTS: u8 = 5;
 pub const ACCOUNT_LOCKOUT_DURATION: u8 = 30;
 pub const SESSION_TIMEOUT: u8 = 120;
 
+// Password hashing defaults (Argon2id, OWASP recommended minimums)
+pub const ARGON2_MEMORY_COST: u32 = 19456; // in KiB
+pub const ARGON2_TIME_COST: u32 = 2;
+pub const ARGON2_PARALLELISM: u32 = 1;
+
+// Email defaults
+pub const EMAIL_TRANSPORT: &str = "file";
+pub const EMAIL_FROM: &str = "Gandalf <no-reply@localhost>";
+pub const EMAIL_SPOOL_DIR: &str = "mail_spool";
+pub const EMAIL_TEMPLATES_DIR: &str = "templates/email";
+pub const EMAIL_DEFAULT_LOCALE: &str = "en";
+pub const APP_BASE_URL: &str = "http://localhost:3000";
+pub const SMTP_HOST: &str = "localhost";
+pub const SMTP_PORT: u16 = 587;
+pub const SMTP_TLS: &str = "starttls";
+pub const EMAIL_OUTBOX_POLL_INTERVAL: u32 = 5; // in seconds
+pub const EMAIL_OUTBOX_BATCH_SIZE: u32 = 20;
+pub const EMAIL_OUTBOX_MAX_ATTEMPTS: u8 = 8;
+pub const EMAIL_OUTBOX_BACKOFF_BASE: u32 = 30; // in seconds
+pub const EMAIL_OUTBOX_BACKOFF_MAX: u32 = 3600; // in seconds
+
+// Authorization defaults
+pub const AUTHZ_DECISION_TTL: u32 = 60; // in seconds
+
+// Multi-factor authentication defaults
+pub const MFA_ISSUER: &str = "Gandalf";
+pub const MFA_CHALLENGE_EXPIRATION: u8 = 5; // in minutes
+pub const MFA_ENROLLMENT_EXPIRATION: u8 = 15; // in minutes
+pub const MFA_MAX_ATTEMPTS: u8 = 5;
+pub const MFA_OTP_EXPIRATION: u8 = 10; // in minutes
+pub const MFA_OTP_RESEND_INTERVAL: u32 = 60; // in seconds
+
+// SMS defaults
+pub const SMS_SENDER: &str = "http";
+pub const SMS_GATEWAY_URL: &str = "http://localhost:8025/sms";
+pub const SMS_FROM: &str = "Gandalf";
+
+// WebAuthn defaults
+pub const WEBAUTHN_RP_ID: &str = "localhost";
+pub const WEBAUTHN_RP_NAME: &str = "Gandalf";
+pub const WEBAUTHN_ORIGINS: &str = "http://localhost:3000";
+pub const WEBAUTHN_CHALLENGE_EXPIRATION: u8 = 5; // in minutes
+
 // Db defaults
 pub const MAX_DB_CONNECTIONS: u16 = 5;
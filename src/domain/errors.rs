@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio_postgres::Error as PgError;
@@ -17,6 +18,81 @@ pub enum UserError {
     #[error("Invalid email")]
     InvalidEmail,
 
+    #[error("Disposable email addresses are not allowed")]
+    DisposableEmail,
+
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+
+    #[error("Account locked")]
+    AccountLocked { retry_after_secs: Option<i64> },
+
+    #[error("Account disabled")]
+    AccountDisabled,
+
+    #[error("Token error: {0}")]
+    TokenError(String),
+
+    #[error("Authentication required")]
+    Unauthorized,
+
+    #[error("Insufficient permissions")]
+    Forbidden,
+
+    #[error("Invalid token")]
+    InvalidToken,
+
+    #[error("Token has been revoked")]
+    TokenRevoked,
+
+    #[error("Invalid or expired refresh token")]
+    InvalidRefreshToken,
+
+    #[error("Invalid or expired verification token")]
+    InvalidVerificationToken,
+
+    #[error("Invalid or expired password reset token")]
+    InvalidResetToken,
+
+    #[error("Refresh token reuse detected")]
+    RefreshTokenReused,
+
+    #[error("Role already exists")]
+    RoleAlreadyExists,
+
+    #[error("System roles cannot be renamed or deleted")]
+    SystemRoleProtected,
+
+    #[error("Role inheritance would create a cycle")]
+    RoleCycle,
+
+    #[error("Invalid or already used MFA code")]
+    InvalidMfaCode,
+
+    #[error("Invalid or expired MFA challenge")]
+    InvalidMfaChallenge,
+
+    #[error("MFA method already enabled")]
+    MfaAlreadyEnabled,
+
+    #[error("Multi-factor authentication must be set up first")]
+    MfaEnrollmentRequired,
+
+    #[error("Confirm this change with one of your MFA methods")]
+    MfaVerificationRequired,
+
+    #[error("An MFA code was sent recently, try again later")]
+    MfaCodeThrottled { retry_after_secs: i64 },
+
+    #[error("Invalid WebAuthn response: {0}")]
+    InvalidWebauthnResponse(String),
+
+    #[error("Credential already registered")]
+    CredentialAlreadyRegistered,
+
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
     #[error("Password hashing error")]
     PasswordHashingError,
 
@@ -27,6 +103,17 @@ pub enum UserError {
     InternalError(#[from] anyhow::Error),
 }
 
+impl UserError {
+    // AccountLocked with the seconds left until `locked_until`; locks
+    // without an expiry, set by an administrator, get no retry-after hint
+    pub fn account_locked(locked_until: Option<DateTime<Utc>>) -> Self {
+        UserError::AccountLocked {
+            retry_after_secs: locked_until
+                .map(|locked_until| (locked_until - Utc::now()).num_seconds().max(1)),
+        }
+    }
+}
+
 impl From<ValidationErrors> for UserError {
     fn from(errors: ValidationErrors) -> Self {
         UserError::ValidationError(Arc::new(errors))
@@ -1,6 +1,48 @@
 mod auth_provider_model;
+mod authz_model;
+mod email_message_model;
+mod email_outbox_model;
+mod mfa_model;
+mod permission_model;
+mod role_model;
+mod security_event_model;
+mod session_model;
+mod sms_message_model;
+mod tenant_model;
+mod token_claims_model;
 mod user_model;
+mod webauthn_model;
 
 pub use auth_provider_model::AuthProvider;
+pub use authz_model::AuthzChange;
+pub use authz_model::AuthzChangePage;
+pub use authz_model::AuthzDecision;
+pub use authz_model::AuthzReason;
+pub use email_message_model::EmailMessage;
+pub use email_outbox_model::OutboxEmail;
+pub use email_outbox_model::OutboxEmailPayload;
+pub use email_outbox_model::OutboxEmailStatus;
+pub use mfa_model::MfaChallenge;
+pub use mfa_model::MfaChallengePurpose;
+pub use mfa_model::MfaMethod;
+pub use mfa_model::MfaMethodType;
+pub use permission_model::Permission;
+pub use permission_model::effective_permissions;
+pub use permission_model::matching_permission;
+pub use permission_model::permissions_grant;
+pub use role_model::PermissionSource;
+pub use role_model::Role;
+pub use role_model::UserRole;
+pub use security_event_model::SecurityEvent;
+pub use security_event_model::SecurityEventType;
+pub use session_model::Session;
+pub use session_model::SessionRevocationReason;
+pub use sms_message_model::SmsMessage;
+pub use tenant_model::Tenant;
+pub use token_claims_model::AccessTokenClaims;
+pub use token_claims_model::MFA_ENROLLMENT_SCOPE;
 pub use user_model::User;
 pub use user_model::UserState;
+pub use webauthn_model::WebauthnCeremony;
+pub use webauthn_model::WebauthnChallenge;
+pub use webauthn_model::WebauthnCredential;
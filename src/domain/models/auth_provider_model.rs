@@ -4,8 +4,9 @@ This module holds the model for supported auth providers
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum AuthProvider {
+    #[default]
     Local,
     Google,
     Microsoft,
@@ -17,12 +18,6 @@ pub enum AuthProvider {
     Custom,
 }
 
-impl Default for AuthProvider {
-    fn default() -> Self {
-        Self::Local
-    }
-}
-
 impl std::str::FromStr for AuthProvider {
     type Err = String;
 
@@ -42,18 +37,19 @@ impl std::str::FromStr for AuthProvider {
     }
 }
 
-impl ToString for AuthProvider {
-    fn to_string(&self) -> String {
-        match self {
-            AuthProvider::Local => "local".to_string(),
-            AuthProvider::Google => "google".to_string(),
-            AuthProvider::Microsoft => "microsoft".to_string(),
-            AuthProvider::Apple => "apple".to_string(),
-            AuthProvider::Facebook => "facebook".to_string(),
-            AuthProvider::Lti => "lti".to_string(),
-            AuthProvider::Saml => "saml".to_string(),
-            AuthProvider::Ldap => "ldap".to_string(),
-            AuthProvider::Custom => "custom".to_string(),
-        }
+impl std::fmt::Display for AuthProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            AuthProvider::Local => "local",
+            AuthProvider::Google => "google",
+            AuthProvider::Microsoft => "microsoft",
+            AuthProvider::Apple => "apple",
+            AuthProvider::Facebook => "facebook",
+            AuthProvider::Lti => "lti",
+            AuthProvider::Saml => "saml",
+            AuthProvider::Ldap => "ldap",
+            AuthProvider::Custom => "custom",
+        };
+        write!(f, "{}", value)
     }
 }
@@ -0,0 +1,73 @@
+/*
+This module holds the authorization decision models
+*/
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct AuthzDecision {
+    pub allowed: bool,
+    pub reason: AuthzReason,
+    // the granted permission that allowed the action, e.g. "course:*"
+    pub matched_permission: Option<String>,
+}
+
+impl AuthzDecision {
+    pub fn deny(reason: AuthzReason) -> Self {
+        Self {
+            allowed: false,
+            reason,
+            matched_permission: None,
+        }
+    }
+
+    pub fn allow(matched_permission: String) -> Self {
+        Self {
+            allowed: true,
+            reason: AuthzReason::PermissionGranted,
+            matched_permission: Some(matched_permission),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthzReason {
+    PermissionGranted,
+    PermissionMissing,
+    SubjectNotFound,
+    SubjectInactive,
+    TenantMismatch,
+}
+
+impl std::fmt::Display for AuthzReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            AuthzReason::PermissionGranted => "permission_granted",
+            AuthzReason::PermissionMissing => "permission_missing",
+            AuthzReason::SubjectNotFound => "subject_not_found",
+            AuthzReason::SubjectInactive => "subject_inactive",
+            AuthzReason::TenantMismatch => "tenant_mismatch",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+// A change to role assignments or role permissions. Decisions about
+// user_id, or about any holder of role_id, may have changed.
+#[derive(Debug, Clone)]
+pub struct AuthzChange {
+    pub change_id: i64,
+    pub user_id: Option<Uuid>,
+    pub role_id: Option<Uuid>,
+    pub changed_at: DateTime<Utc>,
+}
+
+// Changes of whole transactions, oldest first. `version` is where the next
+// poll continues: every change written before it has been returned.
+#[derive(Debug, Clone)]
+pub struct AuthzChangePage {
+    pub changes: Vec<AuthzChange>,
+    pub version: i64,
+    pub has_more: bool,
+}
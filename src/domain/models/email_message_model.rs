@@ -0,0 +1,12 @@
+/*
+This module holds the outgoing email model
+*/
+
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+}
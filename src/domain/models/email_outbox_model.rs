@@ -0,0 +1,91 @@
+/*
+This module holds the email outbox model
+*/
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct OutboxEmail {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub payload: OutboxEmailPayload,
+    pub attempts: i32,
+}
+
+impl OutboxEmail {
+    pub fn new(user_id: Uuid, payload: OutboxEmailPayload) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            user_id,
+            payload,
+            attempts: 0,
+        }
+    }
+}
+
+// What to send, together with the values its template needs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OutboxEmailPayload {
+    Verification {
+        token: String,
+    },
+    PasswordReset {
+        token: String,
+    },
+    AccountLocked {
+        locked_until: DateTime<Utc>,
+    },
+    NewDevice {
+        device: String,
+        ip_address: Option<IpAddr>,
+        signed_in_at: DateTime<Utc>,
+    },
+}
+
+impl OutboxEmailPayload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OutboxEmailPayload::Verification { .. } => "verification",
+            OutboxEmailPayload::PasswordReset { .. } => "password_reset",
+            OutboxEmailPayload::AccountLocked { .. } => "account_locked",
+            OutboxEmailPayload::NewDevice { .. } => "new_device",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutboxEmailStatus {
+    Pending,
+    Sent,
+    // gave up after EMAIL_OUTBOX_MAX_ATTEMPTS, kept for inspection
+    Dead,
+}
+
+impl FromStr for OutboxEmailStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(OutboxEmailStatus::Pending),
+            "sent" => Ok(OutboxEmailStatus::Sent),
+            "dead" => Ok(OutboxEmailStatus::Dead),
+            _ => Err(format!("Unknown outbox email status: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for OutboxEmailStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            OutboxEmailStatus::Pending => "pending",
+            OutboxEmailStatus::Sent => "sent",
+            OutboxEmailStatus::Dead => "dead",
+        };
+        write!(f, "{}", value)
+    }
+}
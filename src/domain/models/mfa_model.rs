@@ -0,0 +1,110 @@
+/*
+This module holds the multi-factor authentication models
+*/
+
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+// A second factor of a user. Methods start out unverified and only count
+// once a first code confirmed the enrollment.
+#[derive(Debug, Clone)]
+pub struct MfaMethod {
+    pub method_id: Uuid,
+    pub user_id: Uuid,
+    pub method_type: MfaMethodType,
+    // email address or phone number codes are sent to
+    pub identifier: Option<String>,
+    // encrypted TOTP secret
+    pub secret: Option<String>,
+    pub enabled: bool,
+    pub verified: bool,
+    // TOTP time step of the last accepted code
+    pub last_used_step: Option<i64>,
+    // when the last email or SMS code was sent
+    pub code_sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MfaMethodType {
+    Totp,
+    Sms,
+    Email,
+    Recovery,
+    Webauthn,
+}
+
+impl std::str::FromStr for MfaMethodType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "totp" => Ok(MfaMethodType::Totp),
+            "sms" => Ok(MfaMethodType::Sms),
+            "email" => Ok(MfaMethodType::Email),
+            "recovery" => Ok(MfaMethodType::Recovery),
+            "webauthn" => Ok(MfaMethodType::Webauthn),
+            _ => Err(format!("Invalid MFA method type: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for MfaMethodType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            MfaMethodType::Totp => "totp",
+            MfaMethodType::Sms => "sms",
+            MfaMethodType::Email => "email",
+            MfaMethodType::Recovery => "recovery",
+            MfaMethodType::Webauthn => "webauthn",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+// A login waiting for its second factor, or for the user to enroll one,
+// along with the client that started it
+#[derive(Debug)]
+pub struct MfaChallenge {
+    pub challenge_id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub purpose: MfaChallengePurpose,
+    pub device_identifier: Option<String>,
+    pub device_name: Option<String>,
+    pub device_type: Option<String>,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MfaChallengePurpose {
+    // answered with a code or assertion of an enrolled method
+    Verification,
+    // answered by enrolling a first method, see the tenant's
+    // mfa_required_roles
+    Enrollment,
+}
+
+impl std::str::FromStr for MfaChallengePurpose {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "verification" => Ok(MfaChallengePurpose::Verification),
+            "enrollment" => Ok(MfaChallengePurpose::Enrollment),
+            _ => Err(format!("Invalid MFA challenge purpose: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for MfaChallengePurpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            MfaChallengePurpose::Verification => "verification",
+            MfaChallengePurpose::Enrollment => "enrollment",
+        };
+        write!(f, "{}", value)
+    }
+}
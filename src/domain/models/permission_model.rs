@@ -0,0 +1,197 @@
+/*
+This module holds the permission model.
+
+A permission is a "resource:action" string such as "course:grade". Any
+segment may be the wildcard "*": "course:*" grants every action on courses
+and "*" alone grants everything. A trailing wildcard also covers deeper
+permissions, so "course:*" grants "course:grade:final".
+*/
+
+use std::fmt;
+use std::str::FromStr;
+
+const MAX_PERMISSION_LENGTH: usize = 100;
+const WILDCARD: &str = "*";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Permission(String);
+
+impl Permission {
+    // Whether holding this permission grants `required`
+    pub fn grants(&self, required: &str) -> bool {
+        let mut granted = self.0.split(':').peekable();
+        let mut required = required.split(':');
+
+        while let Some(segment) = granted.next() {
+            let Some(needed) = required.next() else {
+                return false;
+            };
+            if segment == WILDCARD && granted.peek().is_none() {
+                return true;
+            }
+            if segment != WILDCARD && segment != needed {
+                return false;
+            }
+        }
+        required.next().is_none()
+    }
+
+    // Whether the resource segment is the wildcard, as in "*" or "*:read",
+    // so the permission reaches every resource including role management
+    pub fn has_wildcard_resource(&self) -> bool {
+        self.0.split(':').next() == Some(WILDCARD)
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    // Lowercases the permission and checks its shape
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let permission = s.trim().to_lowercase();
+        let valid = permission.len() <= MAX_PERMISSION_LENGTH
+            && permission.split(':').all(|segment| {
+                segment == WILDCARD
+                    || (!segment.is_empty()
+                        && segment
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            });
+
+        if valid {
+            Ok(Permission(permission))
+        } else {
+            Err(format!(
+                "Invalid permission {:?}: expected resource:action segments of letters, digits, '_', '-' or '*' (at most {} characters)",
+                s, MAX_PERMISSION_LENGTH
+            ))
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Whether any of the granted permissions grants `required`
+pub fn permissions_grant(granted: &[String], required: &str) -> bool {
+    matching_permission(granted, required).is_some()
+}
+
+// The first granted permission that grants `required`
+pub fn matching_permission<'a>(granted: &'a [String], required: &str) -> Option<&'a String> {
+    granted.iter().find(|permission| {
+        permission
+            .parse::<Permission>()
+            .is_ok_and(|permission| permission.grants(required))
+    })
+}
+
+// Drops permissions already covered by another one in the set, e.g.
+// "course:grade" next to "course:*". Keeps tokens small.
+pub fn effective_permissions(mut permissions: Vec<String>) -> Vec<String> {
+    permissions.sort();
+    permissions.dedup();
+
+    permissions
+        .iter()
+        .filter(|permission| {
+            !permissions.iter().any(|other| {
+                other != *permission
+                    && other
+                        .parse::<Permission>()
+                        .is_ok_and(|other| other.grants(permission))
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grants(granted: &str, required: &str) -> bool {
+        granted.parse::<Permission>().unwrap().grants(required)
+    }
+
+    fn strings(permissions: &[&str]) -> Vec<String> {
+        permissions.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn exact_permission_grants_only_itself() {
+        assert!(grants("course:grade", "course:grade"));
+        assert!(!grants("course:grade", "course:read"));
+        assert!(!grants("course:grade", "course"));
+        assert!(!grants("course:grade", "course:grade:final"));
+        assert!(!grants("course", "course:grade"));
+    }
+
+    #[test]
+    fn trailing_wildcard_grants_deeper_permissions_but_not_the_resource() {
+        assert!(grants("course:*", "course:grade"));
+        assert!(grants("course:*", "course:grade:final"));
+        assert!(!grants("course:*", "course"));
+        assert!(!grants("course:*", "lesson:grade"));
+    }
+
+    #[test]
+    fn inner_wildcard_matches_one_segment() {
+        assert!(grants("*:read", "course:read"));
+        assert!(!grants("*:read", "course:grade"));
+        assert!(!grants("*:read", "course:read:final"));
+    }
+
+    #[test]
+    fn lone_wildcard_grants_everything() {
+        assert!(grants("*", "course"));
+        assert!(grants("*", "course:grade"));
+        assert!(grants("*", "course:grade:final"));
+    }
+
+    #[test]
+    fn parsing_lowercases_and_rejects_malformed_permissions() {
+        assert_eq!(
+            " Course:Grade ".parse::<Permission>().unwrap().to_string(),
+            "course:grade"
+        );
+        assert!("course::grade".parse::<Permission>().is_err());
+        assert!("course:gr ade".parse::<Permission>().is_err());
+        assert!("".parse::<Permission>().is_err());
+    }
+
+    #[test]
+    fn matching_permission_skips_permissions_that_do_not_parse() {
+        let granted = strings(&["course::grade", "course:*"]);
+        assert_eq!(
+            matching_permission(&granted, "course:grade").map(String::as_str),
+            Some("course:*")
+        );
+        assert!(!permissions_grant(&granted, "course"));
+    }
+
+    #[test]
+    fn effective_permissions_drops_covered_permissions() {
+        assert_eq!(
+            effective_permissions(strings(&[
+                "course:grade:final",
+                "course:grade",
+                "course:*",
+                "course",
+                "course:*",
+            ])),
+            strings(&["course", "course:*"])
+        );
+    }
+
+    #[test]
+    fn effective_permissions_keeps_only_a_lone_wildcard() {
+        assert_eq!(
+            effective_permissions(strings(&["course", "lesson:read", "*"])),
+            strings(&["*"])
+        );
+    }
+}
@@ -0,0 +1,37 @@
+/*
+This module holds the role models
+*/
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct Role {
+    pub id: Uuid,
+    pub role_name: String,
+    pub description: Option<String>,
+    // seeded roles the service relies on; they cannot be renamed or deleted
+    pub is_system_role: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// A role held by a user, in one tenant or globally (tenant_id None)
+#[derive(Debug, Clone)]
+pub struct UserRole {
+    pub role_id: Uuid,
+    pub role_name: String,
+    pub tenant_id: Option<Uuid>,
+    pub assigned_at: DateTime<Utc>,
+    pub assigned_by: Option<Uuid>,
+}
+
+// Where a permission of a user comes from: `role` defines it and the user
+// reaches that role along `via`, from an assigned role down through the
+// roles it inherits. `via` ends with `role`.
+#[derive(Debug, Clone)]
+pub struct PermissionSource {
+    pub permission: String,
+    pub role_name: String,
+    pub via: Vec<String>,
+}
@@ -0,0 +1,51 @@
+/*
+This module holds the security event model
+*/
+
+use std::net::IpAddr;
+use uuid::Uuid;
+
+#[derive(Debug, Default)]
+pub struct SecurityEvent {
+    pub event_type: SecurityEventType,
+    pub user_id: Option<Uuid>,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub device_identifier: Option<String>,
+    pub success: bool,
+    pub failure_reason: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Default)]
+pub enum SecurityEventType {
+    #[default]
+    PasswordChange,
+    PasswordResetRequest,
+    MfaEnabled,
+    MfaDisabled,
+    MfaVerification,
+    RecoveryCodeUsed,
+    RecoveryCodesGenerated,
+    PasskeyRegistered,
+    PasskeyRemoved,
+    PasskeyLogin,
+}
+
+impl std::fmt::Display for SecurityEventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            SecurityEventType::PasswordChange => "password_change",
+            SecurityEventType::PasswordResetRequest => "password_reset_request",
+            SecurityEventType::MfaEnabled => "mfa_enabled",
+            SecurityEventType::MfaDisabled => "mfa_disabled",
+            SecurityEventType::MfaVerification => "mfa_verification",
+            SecurityEventType::RecoveryCodeUsed => "recovery_code_used",
+            SecurityEventType::RecoveryCodesGenerated => "recovery_codes_generated",
+            SecurityEventType::PasskeyRegistered => "passkey_registered",
+            SecurityEventType::PasskeyRemoved => "passkey_removed",
+            SecurityEventType::PasskeyLogin => "passkey_login",
+        };
+        write!(f, "{}", value)
+    }
+}
@@ -0,0 +1,64 @@
+/*
+This module holds the session model
+*/
+
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+// A session row holds one refresh token. Refreshing rotates the token into a
+// new row of the same family, so a family represents one login.
+#[derive(Debug)]
+pub struct Session {
+    pub session_id: Uuid,
+    pub family_id: Uuid,
+    pub user_id: Uuid,
+    pub refresh_token_hash: String,
+    pub device_identifier: Option<String>,
+    pub device_name: Option<String>,
+    pub device_type: Option<String>,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    // tenant the session acts in, see SessionService::switch_tenant
+    pub tenant_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub is_revoked: bool,
+    pub revoked_reason: Option<SessionRevocationReason>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionRevocationReason {
+    Rotated,
+    TokenReuse,
+    Logout,
+    LogoutAll,
+    PasswordReset,
+}
+
+impl std::str::FromStr for SessionRevocationReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rotated" => Ok(SessionRevocationReason::Rotated),
+            "token_reuse" => Ok(SessionRevocationReason::TokenReuse),
+            "logout" => Ok(SessionRevocationReason::Logout),
+            "logout_all" => Ok(SessionRevocationReason::LogoutAll),
+            "password_reset" => Ok(SessionRevocationReason::PasswordReset),
+            _ => Err(format!("Invalid session revocation reason: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for SessionRevocationReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            SessionRevocationReason::Rotated => "rotated",
+            SessionRevocationReason::TokenReuse => "token_reuse",
+            SessionRevocationReason::Logout => "logout",
+            SessionRevocationReason::LogoutAll => "logout_all",
+            SessionRevocationReason::PasswordReset => "password_reset",
+        };
+        write!(f, "{}", value)
+    }
+}
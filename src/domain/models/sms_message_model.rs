@@ -0,0 +1,12 @@
+/*
+This module holds the outgoing SMS model
+*/
+
+#[derive(Debug, Clone)]
+pub struct SmsMessage {
+    // sender id or number
+    pub from: String,
+    // E.164, e.g. +15551234567
+    pub to: String,
+    pub body: String,
+}
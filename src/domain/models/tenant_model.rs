@@ -0,0 +1,12 @@
+/*
+This module holds the education tenant model
+*/
+
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct Tenant {
+    pub tenant_id: Uuid,
+    pub tenant_name: String,
+    pub domain: String,
+}
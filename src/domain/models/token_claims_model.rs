@@ -0,0 +1,31 @@
+/*
+This module holds the claims carried by gandalf-issued JWTs
+*/
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// scope of tokens that only allow enrolling a first MFA method
+pub const MFA_ENROLLMENT_SCOPE: &str = "mfa_enrollment";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    pub sub: Uuid, // user id
+    pub iss: String,
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub jti: Uuid, // checked against auth.token_blacklist
+    pub sid: Uuid, // session the token was issued for
+    pub roles: Vec<String>,
+    // effective permissions of the roles; absent in tokens issued before
+    // permissions were introduced
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    // current tenant; roles and permissions are those held in it
+    pub tenant: Option<Uuid>,
+    // set on restricted tokens, which only the endpoints accepting the
+    // scope let through. sid then names the login held back, not a session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
@@ -7,7 +7,7 @@ use std::net::IpAddr;
 use uuid::Uuid;
 
 use super::auth_provider_model::AuthProvider;
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct User {
     pub id: Uuid,
     pub external_id: Option<String>,
@@ -32,6 +32,7 @@ pub struct User {
     pub last_user_agent: Option<String>,
     pub data_region: String,
     pub deletion_scheduled_at: Option<DateTime<Utc>>,
+    pub locale: Option<String>,
 }
 
 impl Default for User {
@@ -60,12 +61,27 @@ impl Default for User {
             last_user_agent: None,
             data_region: "us-east".to_string(),
             deletion_scheduled_at: None,
+            locale: None,
         }
     }
 }
 
-#[derive(Debug)]
+impl User {
+    // Whether the account is locked right now. A lockout after failed
+    // attempts is over once account_locked_until passes, although
+    // user_state stays locked until the next attempt clears it; a lock
+    // without an expiry, set by an administrator, holds until lifted.
+    pub fn is_locked(&self) -> bool {
+        match self.account_locked_until {
+            Some(locked_until) => locked_until > Utc::now(),
+            None => matches!(self.user_state, UserState::Locked),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub enum UserState {
+    #[default]
     Registered,
     Verified,
     Active,
@@ -74,11 +90,6 @@ pub enum UserState {
     Locked,
     Deleted,
 }
-impl Default for UserState {
-    fn default() -> Self {
-        UserState::Registered
-    }
-}
 
 impl std::str::FromStr for UserState {
     type Err = String;
@@ -97,16 +108,17 @@ impl std::str::FromStr for UserState {
     }
 }
 
-impl ToString for UserState {
-    fn to_string(&self) -> String {
-        match self {
-            UserState::Registered => "registered".to_string(),
-            UserState::Verified => "verified".to_string(),
-            UserState::Active => "active".to_string(),
-            UserState::Incomplete => "incomplete".to_string(),
-            UserState::Disabled => "disabled".to_string(),
-            UserState::Locked => "locked".to_string(),
-            UserState::Deleted => "deleted".to_string(),
-        }
+impl std::fmt::Display for UserState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            UserState::Registered => "registered",
+            UserState::Verified => "verified",
+            UserState::Active => "active",
+            UserState::Incomplete => "incomplete",
+            UserState::Disabled => "disabled",
+            UserState::Locked => "locked",
+            UserState::Deleted => "deleted",
+        };
+        write!(f, "{}", value)
     }
 }
@@ -0,0 +1,65 @@
+/*
+This module holds the WebAuthn models
+*/
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+// A public key credential registered by one of the user's authenticators
+#[derive(Debug, Clone)]
+pub struct WebauthnCredential {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub credential_id: Vec<u8>,
+    // COSE_Key
+    pub public_key: Vec<u8>,
+    pub algorithm: i32,
+    pub sign_count: i64,
+    pub transports: Vec<String>,
+    pub aaguid: Uuid,
+    pub name: Option<String>,
+    // synced passkeys are backup eligible
+    pub backup_eligible: bool,
+    pub backed_up: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebauthnCeremony {
+    Registration,
+    Authentication,
+}
+
+impl std::str::FromStr for WebauthnCeremony {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "registration" => Ok(WebauthnCeremony::Registration),
+            "authentication" => Ok(WebauthnCeremony::Authentication),
+            _ => Err(format!("Invalid WebAuthn ceremony: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for WebauthnCeremony {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            WebauthnCeremony::Registration => "registration",
+            WebauthnCeremony::Authentication => "authentication",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+// A challenge handed to the client for one ceremony
+#[derive(Debug)]
+pub struct WebauthnChallenge {
+    pub challenge_id: Uuid,
+    // None for passkey logins
+    pub user_id: Option<Uuid>,
+    pub challenge_hash: String,
+    pub ceremony: WebauthnCeremony,
+    pub expires_at: DateTime<Utc>,
+}
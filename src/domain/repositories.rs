@@ -1,5 +1,25 @@
+mod authz_repository;
 mod base_repository;
+mod email_outbox_repository;
+mod mfa_repository;
+mod password_reset_repository;
+mod role_repository;
+mod security_event_repository;
+mod session_repository;
+mod tenant_repository;
+mod token_blacklist_repository;
 mod user_repository;
+mod webauthn_repository;
 
+pub use authz_repository::AuthzRepository;
 pub use base_repository::RepositoryTrait;
+pub use email_outbox_repository::EmailOutboxRepository;
+pub use mfa_repository::MfaRepository;
+pub use password_reset_repository::PasswordResetRepository;
+pub use role_repository::RoleRepository;
+pub use security_event_repository::SecurityEventRepository;
+pub use session_repository::SessionRepository;
+pub use tenant_repository::TenantRepository;
+pub use token_blacklist_repository::TokenBlacklistRepository;
 pub use user_repository::UserRepository;
+pub use webauthn_repository::WebauthnRepository;
@@ -0,0 +1,174 @@
+/*
+This module holds authorization change log repository
+*/
+use std::sync::Arc;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::{AuthzChange, AuthzChangePage};
+
+use super::base_repository::{BaseRepository, PgPool};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+// Create Authz Repository
+pub struct AuthzRepository {
+    base: BaseRepository,
+}
+
+impl AuthzRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    // Changes of the transactions from `since` on that have finished, at
+    // most `limit` transactions' worth. The log is written by triggers on
+    // auth.user_roles, auth.role_permissions, auth.role_inheritance and
+    // auth.roles.
+    //
+    // Change ids are handed out before their transaction commits, so they
+    // may become visible out of order. Pages are therefore cut by writing
+    // transaction and end at the horizon, the oldest transaction still
+    // running: everything below it is visible, nothing below it can appear
+    // later.
+    pub async fn changes_since(&self, since: i64, limit: i64) -> Result<AuthzChangePage> {
+        let conn = self.base.get_conn().await?;
+
+        // taken before reading, so every transaction below it is visible
+        // to the next statement
+        let horizon: i64 = conn
+            .query_one(
+                "SELECT pg_snapshot_xmin(pg_current_snapshot())::TEXT::BIGINT AS horizon",
+                &[],
+            )
+            .await
+            .map_err(UserError::DatabaseError)?
+            .get("horizon");
+
+        let query = "
+            WITH page AS (
+                SELECT DISTINCT txid
+                FROM auth.authz_changes
+                WHERE txid >= $1::BIGINT::TEXT::XID8
+                  AND txid < $2::BIGINT::TEXT::XID8
+                ORDER BY txid
+                LIMIT $3
+            )
+            SELECT change_id, user_id, role_id, changed_at, txid::TEXT::BIGINT AS txid
+            FROM auth.authz_changes
+            JOIN page USING (txid)
+            ORDER BY txid, change_id
+        ";
+
+        let rows = conn
+            .query(query, &[&since.max(0), &horizon, &(limit + 1)])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        let mut txids: Vec<i64> = rows.iter().map(|row| row.get("txid")).collect();
+        txids.dedup();
+
+        // one transaction more than asked for tells whether more are waiting
+        let (rows, version, has_more) = match txids.get(limit as usize) {
+            Some(&next) => {
+                let end = rows
+                    .iter()
+                    .position(|row| row.get::<_, i64>("txid") == next)
+                    .unwrap_or(rows.len());
+                (&rows[..end], next, true)
+            }
+            None => (&rows[..], horizon.max(since), false),
+        };
+
+        Ok(AuthzChangePage {
+            changes: rows.iter().map(AuthzChange::from_row).collect(),
+            version,
+            has_more,
+        })
+    }
+}
+
+// Helper functions for converting database rows to domain models
+impl AuthzChange {
+    fn from_row(row: &tokio_postgres::Row) -> Self {
+        AuthzChange {
+            change_id: row.get("change_id"),
+            user_id: row.get("user_id"),
+            role_id: row.get("role_id"),
+            changed_at: row.get("changed_at"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use uuid::Uuid;
+
+    use super::AuthzRepository;
+    use crate::test_support::TestApp;
+
+    // Follows the feed from `since` and returns the users it names among
+    // `watched`, in the order they were listed
+    async fn poll_users(repo: &AuthzRepository, since: &mut i64, watched: &[Uuid]) -> Vec<Uuid> {
+        let mut seen = Vec::new();
+        loop {
+            let page = repo.changes_since(*since, 100).await.unwrap();
+            seen.extend(
+                page.changes
+                    .iter()
+                    .filter_map(|change| change.user_id)
+                    .filter(|user_id| watched.contains(user_id)),
+            );
+            *since = page.version;
+            if !page.has_more {
+                return seen;
+            }
+        }
+    }
+
+    async fn record_change(client: &impl tokio_postgres::GenericClient, user_id: Uuid) {
+        client
+            .execute(
+                "INSERT INTO auth.authz_changes (user_id) VALUES ($1)",
+                &[&user_id],
+            )
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn changes_committed_out_of_order_are_not_skipped() {
+        let app = TestApp::start().await;
+        let repo = AuthzRepository::new(Arc::new(app.pool.clone()));
+        let (early, late) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut since = 0;
+        poll_users(&repo, &mut since, &[]).await;
+
+        // the early change takes the lower change id but commits last
+        let mut slow = app.pool.get().await.unwrap();
+        let transaction = slow.transaction().await.unwrap();
+        record_change(&transaction, early).await;
+        record_change(&*app.pool.get().await.unwrap(), late).await;
+
+        assert!(
+            poll_users(&repo, &mut since, &[early, late])
+                .await
+                .is_empty()
+        );
+
+        transaction.commit().await.unwrap();
+        let mut seen = Vec::new();
+        for _ in 0..50 {
+            seen.extend(poll_users(&repo, &mut since, &[early, late]).await);
+            if seen.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(seen, vec![early, late]);
+    }
+}
@@ -5,6 +5,7 @@ use async_trait::async_trait;
 use bb8::{Pool, PooledConnection, RunError};
 use bb8_postgres::PostgresConnectionManager;
 use tokio_postgres::NoTls;
+use tokio_postgres::types::ToSql;
 
 use crate::domain::errors::UserError;
 
@@ -28,7 +29,7 @@ impl BaseRepository {
         Self { pool }
     }
 
-    pub async fn get_conn(&self) -> Result<PgConn, UserError> {
+    pub async fn get_conn(&self) -> Result<PgConn<'_>, UserError> {
         self.pool.get().await.map_err(|e| match e {
             RunError::User(err) => UserError::DatabaseError(err),
             RunError::TimedOut => {
@@ -37,3 +38,71 @@ impl BaseRepository {
         })
     }
 }
+
+// Maps entity fields to columns for parameterized statements.
+// Values are bound as $n parameters, so they are sent typed (UUID, INET,
+// TIMESTAMPTZ, ...) and never interpolated into the SQL text.
+#[derive(Default)]
+pub struct ColumnValues<'a> {
+    columns: Vec<&'static str>,
+    values: Vec<&'a (dyn ToSql + Sync)>,
+}
+
+impl<'a> ColumnValues<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, column: &'static str, value: &'a (dyn ToSql + Sync)) -> &mut Self {
+        self.columns.push(column);
+        self.values.push(value);
+        self
+    }
+
+    // INSERT INTO <table> (<columns>) VALUES ($1, ...) [RETURNING <returning>]
+    pub fn insert_statement(&self, table: &str, returning: Option<&str>) -> String {
+        let placeholders = (1..=self.values.len())
+            .map(|i| format!("${}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut statement = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            self.columns.join(", "),
+            placeholders
+        );
+        if let Some(returning) = returning {
+            statement.push_str(" RETURNING ");
+            statement.push_str(returning);
+        }
+        statement
+    }
+
+    pub fn params(&self) -> &[&'a (dyn ToSql + Sync)] {
+        &self.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColumnValues;
+
+    #[test]
+    fn insert_statements_bind_every_value_as_a_parameter() {
+        let email = "x'); DROP TABLE auth.users; --".to_string();
+        let verified = false;
+        let mut columns = ColumnValues::new();
+        columns
+            .push("email", &email)
+            .push("email_verified", &verified);
+
+        let statement = columns.insert_statement("auth.users", Some("id"));
+        assert_eq!(
+            statement,
+            "INSERT INTO auth.users (email, email_verified) VALUES ($1, $2) RETURNING id"
+        );
+        assert!(!statement.contains(&email));
+        assert_eq!(columns.params().len(), 2);
+    }
+}
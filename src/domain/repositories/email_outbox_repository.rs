@@ -0,0 +1,202 @@
+/*
+This module holds email outbox repository
+*/
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio_postgres::GenericClient;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::{OutboxEmail, OutboxEmailStatus};
+
+use super::base_repository::{BaseRepository, ColumnValues, PgPool};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+// Create Email Outbox Repository
+pub struct EmailOutboxRepository {
+    base: BaseRepository,
+}
+
+impl EmailOutboxRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    pub async fn enqueue(&self, email: &OutboxEmail) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+        Self::insert(&*conn, email).await
+    }
+
+    // Queues an email as part of the caller's transaction, so that it is
+    // only sent if the change that triggered it is committed
+    pub async fn insert(client: &impl GenericClient, email: &OutboxEmail) -> Result<()> {
+        let payload =
+            serde_json::to_value(&email.payload).map_err(|e| UserError::InternalError(e.into()))?;
+        let kind = email.payload.kind();
+
+        let mut columns = ColumnValues::new();
+        columns
+            .push("id", &email.id)
+            .push("user_id", &email.user_id)
+            .push("kind", &kind)
+            .push("payload", &payload);
+        let query = columns.insert_statement("auth.email_outbox", None);
+
+        client
+            .execute(&query, columns.params())
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // Claims up to `limit` due emails and counts the attempt. Claimed emails
+    // are not due again for `lease_secs`, so an email whose worker dies
+    // mid-delivery is picked up again later. SKIP LOCKED lets several
+    // instances work the queue side by side.
+    pub async fn claim_due(&self, limit: i64, lease_secs: f64) -> Result<Vec<OutboxEmail>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.email_outbox
+            SET attempts = attempts + 1,
+                next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM auth.email_outbox
+                WHERE status = 'pending'
+                  AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, user_id, payload, attempts
+        ";
+
+        let rows = conn
+            .query(query, &[&limit, &lease_secs])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        let mut emails = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: Uuid = row.get("id");
+            let payload: Option<serde_json::Value> = row.get("payload");
+            match payload.map(serde_json::from_value) {
+                Some(Ok(payload)) => emails.push(OutboxEmail {
+                    id,
+                    user_id: row.get("user_id"),
+                    payload,
+                    attempts: row.get("attempts"),
+                }),
+                _ => {
+                    error!("Outbox email {} has an unreadable payload", id);
+                    self.mark_dead(id, "unreadable payload").await?;
+                }
+            }
+        }
+
+        Ok(emails)
+    }
+
+    // the payload may hold tokens, so it is dropped once no longer needed
+    pub async fn mark_sent(&self, id: Uuid) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.email_outbox
+            SET status = $2,
+                payload = NULL,
+                last_error = NULL,
+                sent_at = NOW()
+            WHERE id = $1
+        ";
+
+        conn.execute(query, &[&id, &OutboxEmailStatus::Sent.to_string()])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    pub async fn reschedule(&self, id: Uuid, delay_secs: f64, last_error: &str) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.email_outbox
+            SET next_attempt_at = NOW() + make_interval(secs => $2),
+                last_error = $3
+            WHERE id = $1
+        ";
+
+        conn.execute(query, &[&id, &delay_secs, &last_error])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // like mark_sent, drops the payload; the row and its error are kept
+    pub async fn mark_dead(&self, id: Uuid, last_error: &str) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.email_outbox
+            SET status = $2,
+                payload = NULL,
+                last_error = $3
+            WHERE id = $1
+        ";
+
+        conn.execute(
+            query,
+            &[&id, &OutboxEmailStatus::Dead.to_string(), &last_error],
+        )
+        .await
+        .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    pub async fn count_by_status(&self) -> Result<HashMap<OutboxEmailStatus, i64>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "SELECT status, COUNT(*) AS count FROM auth.email_outbox GROUP BY status";
+
+        let rows = conn
+            .query(query, &[])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let status: String = row.get("status");
+                OutboxEmailStatus::from_str(&status)
+                    .ok()
+                    .map(|status| (status, row.get("count")))
+            })
+            .collect())
+    }
+
+    // delivery attempts beyond the first, over all emails in the table
+    pub async fn count_retries(&self) -> Result<i64> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT COALESCE(SUM(GREATEST(attempts - 1, 0)), 0)::BIGINT AS retries
+            FROM auth.email_outbox
+        ";
+
+        let row = conn
+            .query_one(query, &[])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.get("retries"))
+    }
+}
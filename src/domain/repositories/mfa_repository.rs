@@ -0,0 +1,571 @@
+/*
+This module holds MFA method and login challenge repository
+*/
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio_postgres::GenericClient;
+use tokio_postgres::error::SqlState;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::{MfaChallenge, MfaChallengePurpose, MfaMethod, MfaMethodType};
+
+use super::base_repository::{BaseRepository, ColumnValues, PgPool};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const MFA_METHOD_COLUMNS: &str = "
+    method_id, user_id, method_type, identifier, secret,
+    COALESCE(enabled, TRUE) AS enabled, COALESCE(verified, FALSE) AS verified,
+    last_used_step, code_sent_at
+";
+
+const MFA_CHALLENGE_COLUMNS: &str = "
+    challenge_id, user_id, token_hash, purpose, device_identifier,
+    device_name, device_type, ip_address, user_agent, expires_at
+";
+
+// Create MFA Repository
+pub struct MfaRepository {
+    base: BaseRepository,
+}
+
+impl MfaRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    pub async fn find_method(
+        &self,
+        user_id: Uuid,
+        method_type: MfaMethodType,
+    ) -> Result<Option<MfaMethod>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "
+            SELECT {} FROM auth.mfa_methods
+            WHERE user_id = $1 AND method_type = $2
+            ORDER BY created_at
+            LIMIT 1
+            ",
+            MFA_METHOD_COLUMNS
+        );
+
+        let row = conn
+            .query_opt(&query, &[&user_id, &method_type.to_string()])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| MfaMethod::from_row(&row)))
+    }
+
+    // Methods that can answer a login challenge
+    pub async fn find_verified(&self, user_id: Uuid) -> Result<Vec<MfaMethod>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "
+            SELECT {} FROM auth.mfa_methods
+            WHERE user_id = $1
+              AND COALESCE(verified, FALSE)
+              AND COALESCE(enabled, TRUE)
+            ORDER BY created_at
+            ",
+            MFA_METHOD_COLUMNS
+        );
+
+        let rows = conn
+            .query(&query, &[&user_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows.iter().map(MfaMethod::from_row).collect())
+    }
+
+    // Stores an unverified method, replacing an earlier enrollment of the
+    // same type that was never confirmed. Fails with MfaAlreadyEnabled when
+    // the user has a verified one.
+    pub async fn create_pending(
+        &self,
+        user_id: Uuid,
+        method_type: MfaMethodType,
+        identifier: Option<&str>,
+        secret: Option<&str>,
+    ) -> Result<MfaMethod> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let method_type = method_type.to_string();
+        let query = "
+            DELETE FROM auth.mfa_methods
+            WHERE user_id = $1 AND method_type = $2 AND NOT COALESCE(verified, FALSE)
+        ";
+        tx.execute(query, &[&user_id, &method_type]).await?;
+
+        let mut columns = ColumnValues::new();
+        columns
+            .push("user_id", &user_id)
+            .push("method_type", &method_type)
+            .push("identifier", &identifier)
+            .push("secret", &secret)
+            .push("verified", &false);
+        let query = columns.insert_statement("auth.mfa_methods", Some(MFA_METHOD_COLUMNS));
+
+        let row = tx.query_one(&query, columns.params()).await.map_err(|e| {
+            if e.code() == Some(&SqlState::UNIQUE_VIOLATION) {
+                UserError::MfaAlreadyEnabled
+            } else {
+                UserError::DatabaseError(e)
+            }
+        })?;
+        tx.commit().await?;
+
+        Ok(MfaMethod::from_row(&row))
+    }
+
+    // Marks a pending method verified once its first code was accepted and
+    // turns on MFA for the user. `step` is the TOTP time step of that code.
+    // Recovery codes are replaced when hashes are given. Returns false if
+    // the method was already verified.
+    pub async fn confirm(
+        &self,
+        method_id: Uuid,
+        step: Option<i64>,
+        recovery_code_hashes: Option<&[String]>,
+    ) -> Result<bool> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let query = "
+            UPDATE auth.mfa_methods
+            SET verified = TRUE,
+                enabled = TRUE,
+                verification_attempts = 0,
+                last_used_step = COALESCE($2, last_used_step),
+                last_used_at = NOW(),
+                updated_at = NOW()
+            WHERE method_id = $1
+              AND NOT COALESCE(verified, FALSE)
+            RETURNING user_id
+        ";
+        let Some(row) = tx.query_opt(query, &[&method_id, &step]).await? else {
+            return Ok(false);
+        };
+        let user_id: Uuid = row.get("user_id");
+
+        let query = "UPDATE auth.users SET requires_mfa = TRUE WHERE id = $1";
+        tx.execute(query, &[&user_id]).await?;
+        if let Some(recovery_code_hashes) = recovery_code_hashes {
+            Self::insert_recovery_codes(&tx, user_id, recovery_code_hashes).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    // Invalidates every recovery code of the user in favour of a new batch
+    pub async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: &[String],
+    ) -> Result<()> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        Self::insert_recovery_codes(&tx, user_id, recovery_code_hashes).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Marks an unused recovery code used. Returns false if the user has no
+    // such code or it was used before.
+    pub async fn consume_recovery_code(&self, user_id: Uuid, code_hash: &str) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.recovery_codes
+            SET used = TRUE,
+                used_at = NOW()
+            WHERE user_id = $1
+              AND code_hash = $2
+              AND NOT COALESCE(used, FALSE)
+        ";
+
+        let updated = conn
+            .execute(query, &[&user_id, &code_hash])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    pub async fn count_unused_recovery_codes(&self, user_id: Uuid) -> Result<i64> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT COUNT(*) FROM auth.recovery_codes
+            WHERE user_id = $1 AND NOT COALESCE(used, FALSE)
+        ";
+
+        let row = conn
+            .query_one(query, &[&user_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.get(0))
+    }
+
+    // Records a code accepted for time step `step`. Returns false when a code
+    // of that or a later step was accepted before, i.e. the code is replayed.
+    pub async fn accept_totp_step(&self, method_id: Uuid, step: i64) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.mfa_methods
+            SET verification_attempts = 0,
+                last_used_step = $2,
+                last_used_at = NOW(),
+                updated_at = NOW()
+            WHERE method_id = $1
+              AND (last_used_step IS NULL OR last_used_step < $2)
+        ";
+
+        let updated = conn
+            .execute(query, &[&method_id, &step])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // Stores the hash of a new one-time code, replacing any earlier one,
+    // unless a code was sent less than `resend_interval_secs` ago. Returns
+    // false when throttled. The send is recorded with mark_code_sent once
+    // the code went out, so a failed delivery can be retried right away.
+    pub async fn store_code(
+        &self,
+        method_id: Uuid,
+        code_hash: &str,
+        ttl_minutes: i32,
+        resend_interval_secs: f64,
+    ) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.mfa_methods
+            SET code_hash = $2,
+                code_expires_at = NOW() + make_interval(mins => $3),
+                updated_at = NOW()
+            WHERE method_id = $1
+              AND (
+                  code_sent_at IS NULL
+                  OR code_sent_at <= NOW() - make_interval(secs => $4)
+              )
+        ";
+
+        let updated = conn
+            .execute(
+                query,
+                &[&method_id, &code_hash, &ttl_minutes, &resend_interval_secs],
+            )
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // Starts the resend interval of the method
+    pub async fn mark_code_sent(&self, method_id: Uuid) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.mfa_methods
+            SET code_sent_at = NOW(),
+                updated_at = NOW()
+            WHERE method_id = $1
+        ";
+
+        conn.execute(query, &[&method_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // Uses up the outstanding one-time code of the method if it matches and
+    // has not expired. Returns false otherwise.
+    pub async fn consume_code(&self, method_id: Uuid, code_hash: &str) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.mfa_methods
+            SET code_hash = NULL,
+                code_expires_at = NULL,
+                verification_attempts = 0,
+                last_used_at = NOW(),
+                updated_at = NOW()
+            WHERE method_id = $1
+              AND code_hash = $2
+              AND code_expires_at > NOW()
+        ";
+
+        let updated = conn
+            .execute(query, &[&method_id, &code_hash])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // Counts a rejected code and returns the failures since the last
+    // accepted one
+    pub async fn register_failed_attempt(&self, method_id: Uuid) -> Result<i32> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.mfa_methods
+            SET verification_attempts = COALESCE(verification_attempts, 0) + 1,
+                updated_at = NOW()
+            WHERE method_id = $1
+            RETURNING verification_attempts
+        ";
+
+        let row = conn
+            .query_opt(query, &[&method_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| row.get("verification_attempts")).unwrap_or(0))
+    }
+
+    pub async fn reset_attempts(&self, method_id: Uuid) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.mfa_methods
+            SET verification_attempts = 0,
+                updated_at = NOW()
+            WHERE method_id = $1
+        ";
+
+        conn.execute(query, &[&method_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // Removes a method. Once no verified method besides recovery codes is
+    // left, the recovery codes go as well and MFA is turned off for the user.
+    pub async fn delete(&self, method_id: Uuid) -> Result<bool> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let query = "DELETE FROM auth.mfa_methods WHERE method_id = $1 RETURNING user_id";
+        let Some(row) = tx.query_opt(query, &[&method_id]).await? else {
+            return Ok(false);
+        };
+        let user_id: Uuid = row.get("user_id");
+
+        let query = "
+            SELECT EXISTS(
+                SELECT 1 FROM auth.mfa_methods
+                WHERE user_id = $1
+                  AND method_type <> $2
+                  AND COALESCE(verified, FALSE)
+            )
+        ";
+        let recovery = MfaMethodType::Recovery.to_string();
+        let row = tx.query_one(query, &[&user_id, &recovery]).await?;
+        if !row.get::<_, bool>(0) {
+            let query = "DELETE FROM auth.mfa_methods WHERE user_id = $1 AND method_type = $2";
+            tx.execute(query, &[&user_id, &recovery]).await?;
+            let query = "DELETE FROM auth.recovery_codes WHERE user_id = $1";
+            tx.execute(query, &[&user_id]).await?;
+            let query = "UPDATE auth.users SET requires_mfa = FALSE WHERE id = $1";
+            tx.execute(query, &[&user_id]).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    pub async fn create_challenge(&self, challenge: &MfaChallenge) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let purpose = challenge.purpose.to_string();
+
+        let mut columns = ColumnValues::new();
+        columns
+            .push("challenge_id", &challenge.challenge_id)
+            .push("user_id", &challenge.user_id)
+            .push("token_hash", &challenge.token_hash)
+            .push("purpose", &purpose)
+            .push("device_identifier", &challenge.device_identifier)
+            .push("device_name", &challenge.device_name)
+            .push("device_type", &challenge.device_type)
+            .push("ip_address", &challenge.ip_address)
+            .push("user_agent", &challenge.user_agent)
+            .push("expires_at", &challenge.expires_at);
+        let query = columns.insert_statement("auth.mfa_challenges", None);
+
+        conn.execute(&query, columns.params())
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // An unexpired verification challenge that has not been answered yet
+    pub async fn find_active_challenge(&self, token_hash: &str) -> Result<Option<MfaChallenge>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "
+            SELECT {} FROM auth.mfa_challenges
+            WHERE token_hash = $1
+              AND purpose = $2
+              AND consumed_at IS NULL
+              AND expires_at > NOW()
+            ",
+            MFA_CHALLENGE_COLUMNS
+        );
+
+        let row = conn
+            .query_opt(
+                &query,
+                &[&token_hash, &MfaChallengePurpose::Verification.to_string()],
+            )
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| MfaChallenge::from_row(&row)))
+    }
+
+    // An unexpired enrollment challenge of the user that has not been
+    // answered yet
+    pub async fn find_active_enrollment(
+        &self,
+        challenge_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<MfaChallenge>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "
+            SELECT {} FROM auth.mfa_challenges
+            WHERE challenge_id = $1
+              AND user_id = $2
+              AND purpose = $3
+              AND consumed_at IS NULL
+              AND expires_at > NOW()
+            ",
+            MFA_CHALLENGE_COLUMNS
+        );
+
+        let row = conn
+            .query_opt(
+                &query,
+                &[
+                    &challenge_id,
+                    &user_id,
+                    &MfaChallengePurpose::Enrollment.to_string(),
+                ],
+            )
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| MfaChallenge::from_row(&row)))
+    }
+
+    // Returns false if the challenge was consumed concurrently
+    pub async fn consume_challenge(&self, challenge_id: Uuid) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.mfa_challenges
+            SET consumed_at = NOW()
+            WHERE challenge_id = $1
+              AND consumed_at IS NULL
+        ";
+
+        let updated = conn
+            .execute(query, &[&challenge_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // Replaces the user's recovery codes and makes sure the recovery method
+    // that counts wrong codes exists, as part of the caller's transaction
+    pub async fn insert_recovery_codes(
+        client: &impl GenericClient,
+        user_id: Uuid,
+        recovery_code_hashes: &[String],
+    ) -> Result<()> {
+        let query = "
+            INSERT INTO auth.mfa_methods (user_id, method_type, verified, enabled)
+            VALUES ($1, $2, TRUE, TRUE)
+            ON CONFLICT (user_id) WHERE method_type = 'recovery' DO NOTHING
+        ";
+        client
+            .execute(query, &[&user_id, &MfaMethodType::Recovery.to_string()])
+            .await?;
+
+        let query = "DELETE FROM auth.recovery_codes WHERE user_id = $1";
+        client.execute(query, &[&user_id]).await?;
+
+        let query = "
+            INSERT INTO auth.recovery_codes (user_id, code_hash)
+            SELECT $1, unnest($2::VARCHAR[])
+        ";
+        client
+            .execute(query, &[&user_id, &recovery_code_hashes])
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl MfaMethod {
+    fn from_row(row: &tokio_postgres::Row) -> Self {
+        MfaMethod {
+            method_id: row.get("method_id"),
+            user_id: row.get("user_id"),
+            method_type: MfaMethodType::from_str(row.get("method_type"))
+                .expect("method_type is constrained by valid_method_type"),
+            identifier: row.get("identifier"),
+            secret: row.get("secret"),
+            enabled: row.get("enabled"),
+            verified: row.get("verified"),
+            last_used_step: row.get("last_used_step"),
+            code_sent_at: row.get("code_sent_at"),
+        }
+    }
+}
+
+impl MfaChallenge {
+    fn from_row(row: &tokio_postgres::Row) -> Self {
+        MfaChallenge {
+            challenge_id: row.get("challenge_id"),
+            user_id: row.get("user_id"),
+            token_hash: row.get("token_hash"),
+            purpose: MfaChallengePurpose::from_str(row.get("purpose"))
+                .expect("purpose is constrained by valid_challenge_purpose"),
+            device_identifier: row.get("device_identifier"),
+            device_name: row.get("device_name"),
+            device_type: row.get("device_type"),
+            ip_address: row.get("ip_address"),
+            user_agent: row.get("user_agent"),
+            expires_at: row.get("expires_at"),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+/*
+This module holds password reset token repository
+*/
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::OutboxEmail;
+
+use super::base_repository::{BaseRepository, PgPool};
+use super::email_outbox_repository::EmailOutboxRepository;
+
+type Result<T> = std::result::Result<T, UserError>;
+
+// Create Password Reset Repository
+pub struct PasswordResetRepository {
+    base: BaseRepository,
+}
+
+impl PasswordResetRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    // Stores a reset token and queues the email that carries it
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+        requested_ip: Option<IpAddr>,
+        email: &OutboxEmail,
+    ) -> Result<()> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let query = "
+            INSERT INTO auth.password_reset_tokens (user_id, token_hash, expires_at, requested_ip)
+            VALUES ($1, $2, $3, $4)
+        ";
+
+        tx.execute(query, &[&user_id, &token_hash, &expires_at, &requested_ip])
+            .await
+            .map_err(UserError::DatabaseError)?;
+        EmailOutboxRepository::insert(&tx, email).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Consumes a valid reset token and stores the new password hash in one
+    // transaction. Every other outstanding token of the user is invalidated
+    // as well. Returns the user id, or None if the token is unknown, used or
+    // expired.
+    pub async fn reset_password(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> Result<Option<Uuid>> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let query = "
+            UPDATE auth.password_reset_tokens
+            SET used_at = NOW()
+            WHERE token_hash = $1
+              AND used_at IS NULL
+              AND expires_at > NOW()
+            RETURNING user_id
+        ";
+
+        let Some(row) = tx.query_opt(query, &[&token_hash]).await? else {
+            return Ok(None);
+        };
+        let user_id: Uuid = row.get("user_id");
+
+        let query = "
+            UPDATE auth.users
+            SET password_hash = $2,
+                password_reset_required = FALSE,
+                failed_login_attempts = 0,
+                account_locked_until = NULL,
+                user_state = CASE
+                    WHEN user_state = 'locked' AND account_locked_until IS NOT NULL
+                        THEN COALESCE(state_before_lock, 'registered')
+                    ELSE user_state
+                END,
+                state_before_lock = NULL
+            WHERE id = $1
+        ";
+        tx.execute(query, &[&user_id, &password_hash]).await?;
+
+        let query = "
+            UPDATE auth.password_reset_tokens
+            SET used_at = NOW()
+            WHERE user_id = $1
+              AND used_at IS NULL
+        ";
+        tx.execute(query, &[&user_id]).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(user_id))
+    }
+}
@@ -0,0 +1,433 @@
+/*
+This module holds role repository
+*/
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio_postgres::error::SqlState;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::{PermissionSource, Role, UserRole};
+
+use super::base_repository::{BaseRepository, ColumnValues, PgPool, RepositoryTrait};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const ROLE_COLUMNS: &str = "
+    id, role_name, description, COALESCE(is_system_role, FALSE) AS is_system_role,
+    created_at, updated_at
+";
+
+// Create Role Repository
+pub struct RoleRepository {
+    base: BaseRepository,
+}
+
+impl RoleRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<Role>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!("SELECT {} FROM auth.roles ORDER BY role_name", ROLE_COLUMNS);
+
+        let rows = conn
+            .query(&query, &[])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows.iter().map(Role::from_row).collect())
+    }
+
+    pub async fn create(&self, role_name: &str, description: Option<&str>) -> Result<Role> {
+        let conn = self.base.get_conn().await?;
+
+        let mut columns = ColumnValues::new();
+        columns
+            .push("role_name", &role_name)
+            .push("description", &description);
+        let query = columns.insert_statement("auth.roles", Some(ROLE_COLUMNS));
+
+        let row = conn
+            .query_one(&query, columns.params())
+            .await
+            .map_err(map_unique_violation)?;
+
+        Ok(Role::from_row(&row))
+    }
+
+    // Fields left as None keep their current value
+    pub async fn update(
+        &self,
+        id: Uuid,
+        role_name: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<Option<Role>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "
+            UPDATE auth.roles
+            SET role_name = COALESCE($2, role_name),
+                description = COALESCE($3, description)
+            WHERE id = $1
+            RETURNING {}
+            ",
+            ROLE_COLUMNS
+        );
+
+        let row = conn
+            .query_opt(&query, &[&id, &role_name, &description])
+            .await
+            .map_err(map_unique_violation)?;
+
+        Ok(row.map(|row| Role::from_row(&row)))
+    }
+
+    // Assignments are removed along with the role
+    pub async fn delete(&self, id: Uuid) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            DELETE FROM auth.roles
+            WHERE id = $1 AND NOT COALESCE(is_system_role, FALSE)
+        ";
+
+        let deleted = conn
+            .execute(query, &[&id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(deleted > 0)
+    }
+
+    pub async fn find_permissions(&self, role_id: Uuid) -> Result<Vec<String>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT permission
+            FROM auth.role_permissions
+            WHERE role_id = $1
+            ORDER BY permission
+        ";
+
+        let rows = conn
+            .query(query, &[&role_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows.iter().map(|row| row.get("permission")).collect())
+    }
+
+    // Returns false if the role already has the permission
+    pub async fn add_permission(&self, role_id: Uuid, permission: &str) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            INSERT INTO auth.role_permissions (role_id, permission)
+            VALUES ($1, $2)
+            ON CONFLICT (role_id, permission) DO NOTHING
+        ";
+
+        let inserted = conn
+            .execute(query, &[&role_id, &permission])
+            .await
+            .map_err(|e| {
+                if e.code() == Some(&SqlState::FOREIGN_KEY_VIOLATION) {
+                    UserError::NotFound
+                } else {
+                    UserError::DatabaseError(e)
+                }
+            })?;
+
+        Ok(inserted > 0)
+    }
+
+    pub async fn remove_permission(&self, role_id: Uuid, permission: &str) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            DELETE FROM auth.role_permissions
+            WHERE role_id = $1 AND permission = $2
+        ";
+
+        let deleted = conn
+            .execute(query, &[&role_id, &permission])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(deleted > 0)
+    }
+
+    // Roles that `role_id` inherits directly
+    pub async fn find_inherited_roles(&self, role_id: Uuid) -> Result<Vec<Role>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "
+            SELECT {}
+            FROM auth.roles
+            WHERE id IN (
+                SELECT inherited_role_id FROM auth.role_inheritance WHERE role_id = $1
+            )
+            ORDER BY role_name
+            ",
+            ROLE_COLUMNS
+        );
+
+        let rows = conn
+            .query(&query, &[&role_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows.iter().map(Role::from_row).collect())
+    }
+
+    // Makes `role_id` inherit `inherited_role_id`. Fails with RoleCycle if
+    // `inherited_role_id` already inherits `role_id`, directly or not. The
+    // table is locked against concurrent edits while checking, so two
+    // opposite edges cannot both get in. Returns false if the edge exists.
+    pub async fn add_inheritance(&self, role_id: Uuid, inherited_role_id: Uuid) -> Result<bool> {
+        if role_id == inherited_role_id {
+            return Err(UserError::RoleCycle);
+        }
+
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        tx.execute(
+            "LOCK TABLE auth.role_inheritance IN SHARE ROW EXCLUSIVE MODE",
+            &[],
+        )
+        .await
+        .map_err(UserError::DatabaseError)?;
+
+        let cycle_query = "
+            WITH RECURSIVE inherited(role_id) AS (
+                SELECT $2::UUID
+                UNION
+                SELECT ri.inherited_role_id
+                FROM auth.role_inheritance ri
+                JOIN inherited i ON i.role_id = ri.role_id
+            )
+            SELECT EXISTS (SELECT 1 FROM inherited WHERE role_id = $1) AS cycle
+        ";
+        let cycle: bool = tx
+            .query_one(cycle_query, &[&role_id, &inherited_role_id])
+            .await
+            .map_err(UserError::DatabaseError)?
+            .get("cycle");
+        if cycle {
+            return Err(UserError::RoleCycle);
+        }
+
+        let insert_query = "
+            INSERT INTO auth.role_inheritance (role_id, inherited_role_id)
+            VALUES ($1, $2)
+            ON CONFLICT (role_id, inherited_role_id) DO NOTHING
+        ";
+        let inserted = tx
+            .execute(insert_query, &[&role_id, &inherited_role_id])
+            .await
+            .map_err(|e| {
+                if e.code() == Some(&SqlState::FOREIGN_KEY_VIOLATION) {
+                    UserError::NotFound
+                } else {
+                    UserError::DatabaseError(e)
+                }
+            })?;
+
+        tx.commit().await?;
+        Ok(inserted > 0)
+    }
+
+    pub async fn remove_inheritance(&self, role_id: Uuid, inherited_role_id: Uuid) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            DELETE FROM auth.role_inheritance
+            WHERE role_id = $1 AND inherited_role_id = $2
+        ";
+
+        let deleted = conn
+            .execute(query, &[&role_id, &inherited_role_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(deleted > 0)
+    }
+
+    // Every permission of the user in the tenant along with the path of
+    // roles it is reached through. A permission reachable in several ways
+    // is listed once per path.
+    pub async fn find_permission_sources(
+        &self,
+        user_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<PermissionSource>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            WITH RECURSIVE role_paths(role_id, path_ids, path_names) AS (
+                SELECT r.id, ARRAY[r.id], ARRAY[r.role_name::TEXT]
+                FROM auth.user_roles ur
+                JOIN auth.roles r ON r.id = ur.role_id
+                WHERE ur.user_id = $1 AND (ur.tenant_id IS NULL OR ur.tenant_id = $2)
+                UNION ALL
+                SELECT r.id, rp.path_ids || r.id, rp.path_names || r.role_name::TEXT
+                FROM role_paths rp
+                JOIN auth.role_inheritance ri ON ri.role_id = rp.role_id
+                JOIN auth.roles r ON r.id = ri.inherited_role_id
+                WHERE NOT r.id = ANY(rp.path_ids)
+            )
+            SELECT p.permission, rp.path_names
+            FROM role_paths rp
+            JOIN auth.role_permissions p ON p.role_id = rp.role_id
+            ORDER BY p.permission, array_length(rp.path_names, 1), rp.path_names
+        ";
+
+        let rows = conn
+            .query(query, &[&user_id, &tenant_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let via: Vec<String> = row.get("path_names");
+                PermissionSource {
+                    permission: row.get("permission"),
+                    role_name: via.last().cloned().unwrap_or_default(),
+                    via,
+                }
+            })
+            .collect())
+    }
+
+    pub async fn find_user_roles(&self, user_id: Uuid) -> Result<Vec<UserRole>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT ur.role_id, r.role_name, ur.tenant_id, ur.assigned_at, ur.assigned_by
+            FROM auth.user_roles ur
+            JOIN auth.roles r ON r.id = ur.role_id
+            WHERE ur.user_id = $1
+            ORDER BY ur.tenant_id NULLS FIRST, r.role_name
+        ";
+
+        let rows = conn
+            .query(query, &[&user_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows.iter().map(UserRole::from_row).collect())
+    }
+
+    // Assigns the role in a tenant, or globally when tenant_id is None.
+    // Returns false if the user already holds the role there. The original
+    // assignment, and who made it, is kept in that case.
+    pub async fn assign(
+        &self,
+        user_id: Uuid,
+        role_id: Uuid,
+        tenant_id: Option<Uuid>,
+        assigned_by: Uuid,
+    ) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            INSERT INTO auth.user_roles (user_id, role_id, tenant_id, assigned_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT ON CONSTRAINT user_roles_user_role_tenant_key DO NOTHING
+        ";
+
+        let inserted = conn
+            .execute(query, &[&user_id, &role_id, &tenant_id, &assigned_by])
+            .await
+            .map_err(|e| {
+                // the user, the role or the tenant does not exist
+                if e.code() == Some(&SqlState::FOREIGN_KEY_VIOLATION) {
+                    UserError::NotFound
+                } else {
+                    UserError::DatabaseError(e)
+                }
+            })?;
+
+        Ok(inserted > 0)
+    }
+
+    pub async fn revoke(
+        &self,
+        user_id: Uuid,
+        role_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            DELETE FROM auth.user_roles
+            WHERE user_id = $1 AND role_id = $2 AND tenant_id IS NOT DISTINCT FROM $3
+        ";
+
+        let deleted = conn
+            .execute(query, &[&user_id, &role_id, &tenant_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(deleted > 0)
+    }
+}
+
+#[async_trait]
+impl RepositoryTrait<Role, Uuid> for RoleRepository {
+    async fn find_by_id(&self, id: Uuid) -> Result<Option<Role>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!("SELECT {} FROM auth.roles WHERE id = $1", ROLE_COLUMNS);
+
+        let row = conn
+            .query_opt(&query, &[&id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| Role::from_row(&row)))
+    }
+}
+
+fn map_unique_violation(e: tokio_postgres::Error) -> UserError {
+    if e.code() == Some(&SqlState::UNIQUE_VIOLATION) {
+        UserError::RoleAlreadyExists
+    } else {
+        UserError::DatabaseError(e)
+    }
+}
+
+// Helper functions for converting database rows to domain models
+impl Role {
+    fn from_row(row: &tokio_postgres::Row) -> Self {
+        Role {
+            id: row.get("id"),
+            role_name: row.get("role_name"),
+            description: row.get("description"),
+            is_system_role: row.get("is_system_role"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+}
+
+impl UserRole {
+    fn from_row(row: &tokio_postgres::Row) -> Self {
+        UserRole {
+            role_id: row.get("role_id"),
+            role_name: row.get("role_name"),
+            tenant_id: row.get("tenant_id"),
+            assigned_at: row.get("assigned_at"),
+            assigned_by: row.get("assigned_by"),
+        }
+    }
+}
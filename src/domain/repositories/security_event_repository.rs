@@ -0,0 +1,54 @@
+/*
+This module holds security event repository
+*/
+use std::sync::Arc;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::SecurityEvent;
+
+use super::base_repository::{BaseRepository, PgPool};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+// Create Security Event Repository
+pub struct SecurityEventRepository {
+    base: BaseRepository,
+}
+
+impl SecurityEventRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    pub async fn create(&self, event: &SecurityEvent) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            INSERT INTO auth.security_events (
+                event_type, user_id, ip_address, user_agent, device_identifier,
+                success, failure_reason, metadata
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ";
+
+        conn.execute(
+            query,
+            &[
+                &event.event_type.to_string(),
+                &event.user_id,
+                &event.ip_address,
+                &event.user_agent,
+                &event.device_identifier,
+                &event.success,
+                &event.failure_reason,
+                &event.metadata,
+            ],
+        )
+        .await
+        .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+/*
+This module holds session repository
+*/
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio_postgres::GenericClient;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::{Session, SessionRevocationReason};
+
+use super::base_repository::{BaseRepository, ColumnValues, PgPool};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const SESSION_COLUMNS: &str = "
+    session_id, family_id, user_id, refresh_token_hash, device_identifier,
+    device_name, device_type, ip_address, user_agent, tenant_id, expires_at,
+    is_revoked, revoked_reason
+";
+
+// Create Session Repository
+pub struct SessionRepository {
+    base: BaseRepository,
+}
+
+impl SessionRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    pub async fn create(&self, session: &Session) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+        Self::insert(&*conn, session).await
+    }
+
+    pub async fn find_by_refresh_token_hash(
+        &self,
+        refresh_token_hash: &str,
+    ) -> Result<Option<Session>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "SELECT {} FROM auth.sessions WHERE refresh_token_hash = $1",
+            SESSION_COLUMNS
+        );
+
+        let row = conn
+            .query_opt(&query, &[&refresh_token_hash])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| Session::from_row(&row)))
+    }
+
+    // Whether the user has ever signed in from the given device
+    pub async fn device_known(&self, user_id: Uuid, device_identifier: &str) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT EXISTS(
+                SELECT 1 FROM auth.sessions
+                WHERE user_id = $1 AND device_identifier = $2
+            )
+        ";
+
+        let row = conn
+            .query_one(query, &[&user_id, &device_identifier])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.get(0))
+    }
+
+    // Atomically retires `current_session_id` and stores its successor.
+    // Returns false when the current session was already revoked, i.e. a
+    // concurrent request rotated the same refresh token first.
+    pub async fn rotate(&self, current_session_id: Uuid, next: &Session) -> Result<bool> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let query = "
+            UPDATE auth.sessions
+            SET is_revoked = TRUE,
+                revoked_reason = $2,
+                revoked_at = NOW()
+            WHERE session_id = $1
+              AND is_revoked = FALSE
+        ";
+
+        let reason = SessionRevocationReason::Rotated.to_string();
+        let updated = tx.execute(query, &[&current_session_id, &reason]).await?;
+        if updated == 0 {
+            return Ok(false);
+        }
+
+        Self::insert(&tx, next).await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    // Whether the login the session belongs to is still going: its family
+    // has a session that was neither logged out nor revoked. Sessions
+    // retired by rotation count as long as their successor is live, so
+    // access tokens survive a refresh.
+    pub async fn is_family_active(&self, session_id: Uuid) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT EXISTS (
+                SELECT 1
+                FROM auth.sessions current
+                JOIN auth.sessions s ON s.family_id = current.family_id
+                WHERE current.session_id = $1
+                  AND s.is_revoked = FALSE
+            )
+        ";
+
+        let row = conn
+            .query_one(query, &[&session_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.get(0))
+    }
+
+    // Revokes every still active session that belongs to a login family
+    pub async fn revoke_family(
+        &self,
+        family_id: Uuid,
+        reason: SessionRevocationReason,
+    ) -> Result<u64> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.sessions
+            SET is_revoked = TRUE,
+                revoked_reason = $2,
+                revoked_at = NOW()
+            WHERE family_id = $1
+              AND is_revoked = FALSE
+        ";
+
+        let revoked = conn
+            .execute(query, &[&family_id, &reason.to_string()])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(revoked)
+    }
+
+    // Revokes the login family the given session belongs to
+    pub async fn revoke_family_of_session(
+        &self,
+        session_id: Uuid,
+        reason: SessionRevocationReason,
+    ) -> Result<u64> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.sessions
+            SET is_revoked = TRUE,
+                revoked_reason = $2,
+                revoked_at = NOW()
+            WHERE family_id = (
+                SELECT family_id FROM auth.sessions WHERE session_id = $1
+            )
+              AND is_revoked = FALSE
+        ";
+
+        let revoked = conn
+            .execute(query, &[&session_id, &reason.to_string()])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(revoked)
+    }
+
+    pub async fn revoke_all_for_user(
+        &self,
+        user_id: Uuid,
+        reason: SessionRevocationReason,
+    ) -> Result<u64> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.sessions
+            SET is_revoked = TRUE,
+                revoked_reason = $2,
+                revoked_at = NOW()
+            WHERE user_id = $1
+              AND is_revoked = FALSE
+        ";
+
+        let revoked = conn
+            .execute(query, &[&user_id, &reason.to_string()])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(revoked)
+    }
+
+    async fn insert(client: &impl GenericClient, session: &Session) -> Result<()> {
+        let columns = session.insert_columns();
+        let query = columns.insert_statement("auth.sessions", None);
+
+        client
+            .execute(&query, columns.params())
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+}
+
+// Helper functions for converting database rows to domain models
+impl Session {
+    fn from_row(row: &tokio_postgres::Row) -> Self {
+        Session {
+            session_id: row.get("session_id"),
+            family_id: row.get("family_id"),
+            user_id: row.get("user_id"),
+            refresh_token_hash: row.get("refresh_token_hash"),
+            device_identifier: row.get("device_identifier"),
+            device_name: row.get("device_name"),
+            device_type: row.get("device_type"),
+            ip_address: row.get("ip_address"),
+            user_agent: row.get("user_agent"),
+            tenant_id: row.get("tenant_id"),
+            expires_at: row.get("expires_at"),
+            is_revoked: row.get::<_, Option<bool>>("is_revoked").unwrap_or(false),
+            revoked_reason: row
+                .get::<_, Option<&str>>("revoked_reason")
+                .and_then(|reason| SessionRevocationReason::from_str(reason).ok()),
+        }
+    }
+
+    fn insert_columns(&self) -> ColumnValues<'_> {
+        let mut columns = ColumnValues::new();
+        columns
+            .push("session_id", &self.session_id)
+            .push("family_id", &self.family_id)
+            .push("user_id", &self.user_id)
+            .push("refresh_token_hash", &self.refresh_token_hash)
+            .push("device_identifier", &self.device_identifier)
+            .push("device_name", &self.device_name)
+            .push("device_type", &self.device_type)
+            .push("ip_address", &self.ip_address)
+            .push("user_agent", &self.user_agent)
+            .push("tenant_id", &self.tenant_id)
+            .push("expires_at", &self.expires_at);
+        columns
+    }
+}
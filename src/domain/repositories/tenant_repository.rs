@@ -0,0 +1,97 @@
+/*
+This module holds education tenant repository
+*/
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::Tenant;
+
+use super::base_repository::{BaseRepository, PgPool};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+// Create Tenant Repository
+pub struct TenantRepository {
+    base: BaseRepository,
+}
+
+impl TenantRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    pub async fn find_id_by_domain(&self, domain: &str) -> Result<Option<Uuid>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT tenant_id
+            FROM auth.education_tenants
+            WHERE lower(domain) = lower($1)
+        ";
+
+        let row = conn
+            .query_opt(query, &[&domain])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| row.get("tenant_id")))
+    }
+
+    // Roles whose holders must use MFA in the tenant
+    pub async fn find_mfa_required_roles(&self, tenant_id: Uuid) -> Result<Vec<String>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT COALESCE(mfa_required_roles, '{}') AS mfa_required_roles
+            FROM auth.education_tenants
+            WHERE tenant_id = $1
+        ";
+
+        let row = conn
+            .query_opt(query, &[&tenant_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row
+            .map(|row| row.get("mfa_required_roles"))
+            .unwrap_or_default())
+    }
+
+    // Tenants the user belongs to: the one owning `email_domain`, if given,
+    // and every tenant they hold a role in
+    pub async fn find_for_user(
+        &self,
+        user_id: Uuid,
+        email_domain: Option<&str>,
+    ) -> Result<Vec<Tenant>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT tenant_id, tenant_name, domain
+            FROM auth.education_tenants
+            WHERE lower(domain) = lower($2::TEXT)
+               OR tenant_id IN (
+                   SELECT tenant_id FROM auth.user_roles
+                   WHERE user_id = $1 AND tenant_id IS NOT NULL
+               )
+            ORDER BY tenant_name
+        ";
+
+        let rows = conn
+            .query(query, &[&user_id, &email_domain])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Tenant {
+                tenant_id: row.get("tenant_id"),
+                tenant_name: row.get("tenant_name"),
+                domain: row.get("domain"),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,67 @@
+/*
+This module holds token blacklist repository
+*/
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+
+use super::base_repository::{BaseRepository, PgPool};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+// Create Token Blacklist Repository
+pub struct TokenBlacklistRepository {
+    base: BaseRepository,
+}
+
+impl TokenBlacklistRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    pub async fn is_blacklisted(&self, jti: Uuid) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            SELECT EXISTS (
+                SELECT 1
+                FROM auth.token_blacklist
+                WHERE jti = $1
+            )
+        ";
+
+        let row = conn
+            .query_one(query, &[&jti])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.get(0))
+    }
+
+    pub async fn add(
+        &self,
+        jti: Uuid,
+        user_id: Uuid,
+        expires_at: DateTime<Utc>,
+        revoked_by: Option<Uuid>,
+        reason: &str,
+    ) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            INSERT INTO auth.token_blacklist (jti, user_id, expires_at, revoked_by, reason)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (jti) DO NOTHING
+        ";
+
+        conn.execute(query, &[&jti, &user_id, &expires_at, &revoked_by, &reason])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+/*
+This module holds WebAuthn credential and challenge repository
+*/
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio_postgres::error::SqlState;
+use uuid::Uuid;
+
+use crate::domain::errors::UserError;
+use crate::domain::models::{
+    MfaMethodType, WebauthnCeremony, WebauthnChallenge, WebauthnCredential,
+};
+
+use super::base_repository::{BaseRepository, ColumnValues, PgPool};
+use super::mfa_repository::MfaRepository;
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const CREDENTIAL_COLUMNS: &str = "
+    id, user_id, credential_id, public_key, algorithm, sign_count, transports,
+    aaguid, name, backup_eligible, backed_up, created_at, last_used_at
+";
+
+const CHALLENGE_COLUMNS: &str = "
+    challenge_id, user_id, challenge_hash, ceremony, expires_at
+";
+
+// Create WebAuthn Repository
+pub struct WebauthnRepository {
+    base: BaseRepository,
+}
+
+impl WebauthnRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self {
+            base: BaseRepository::new(pool),
+        }
+    }
+
+    pub async fn find_credentials(&self, user_id: Uuid) -> Result<Vec<WebauthnCredential>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "
+            SELECT {} FROM auth.webauthn_credentials
+            WHERE user_id = $1
+            ORDER BY created_at
+            ",
+            CREDENTIAL_COLUMNS
+        );
+
+        let rows = conn
+            .query(&query, &[&user_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(rows.iter().map(WebauthnCredential::from_row).collect())
+    }
+
+    // Looks a credential up by the id its authenticator chose
+    pub async fn find_by_credential_id(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<Option<WebauthnCredential>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "SELECT {} FROM auth.webauthn_credentials WHERE credential_id = $1",
+            CREDENTIAL_COLUMNS
+        );
+
+        let row = conn
+            .query_opt(&query, &[&credential_id])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| WebauthnCredential::from_row(&row)))
+    }
+
+    // Stores a new credential and makes sure the user's webauthn method
+    // exists and MFA is on. Recovery codes are replaced when hashes are
+    // given. Fails with CredentialAlreadyRegistered for a known credential.
+    pub async fn create_credential(
+        &self,
+        credential: &WebauthnCredential,
+        recovery_code_hashes: Option<&[String]>,
+    ) -> Result<WebauthnCredential> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let mut columns = ColumnValues::new();
+        columns
+            .push("id", &credential.id)
+            .push("user_id", &credential.user_id)
+            .push("credential_id", &credential.credential_id)
+            .push("public_key", &credential.public_key)
+            .push("algorithm", &credential.algorithm)
+            .push("sign_count", &credential.sign_count)
+            .push("transports", &credential.transports)
+            .push("aaguid", &credential.aaguid)
+            .push("name", &credential.name)
+            .push("backup_eligible", &credential.backup_eligible)
+            .push("backed_up", &credential.backed_up);
+        let query = columns.insert_statement("auth.webauthn_credentials", Some(CREDENTIAL_COLUMNS));
+
+        let row = tx.query_one(&query, columns.params()).await.map_err(|e| {
+            if e.code() == Some(&SqlState::UNIQUE_VIOLATION) {
+                UserError::CredentialAlreadyRegistered
+            } else {
+                UserError::DatabaseError(e)
+            }
+        })?;
+
+        let query = "
+            INSERT INTO auth.mfa_methods (user_id, method_type, verified, enabled)
+            VALUES ($1, $2, TRUE, TRUE)
+            ON CONFLICT (user_id) WHERE method_type = 'webauthn' DO NOTHING
+        ";
+        tx.execute(
+            query,
+            &[&credential.user_id, &MfaMethodType::Webauthn.to_string()],
+        )
+        .await?;
+
+        let query = "UPDATE auth.users SET requires_mfa = TRUE WHERE id = $1";
+        tx.execute(query, &[&credential.user_id]).await?;
+        if let Some(recovery_code_hashes) = recovery_code_hashes {
+            MfaRepository::insert_recovery_codes(&tx, credential.user_id, recovery_code_hashes)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(WebauthnCredential::from_row(&row))
+    }
+
+    // Records a successful assertion. The signature counter only moves
+    // forward; returns false when `sign_count` is not above the stored one,
+    // which points at a cloned authenticator. Authenticators that do not
+    // count always report 0.
+    pub async fn record_use(&self, id: Uuid, sign_count: i64, backed_up: bool) -> Result<bool> {
+        let conn = self.base.get_conn().await?;
+
+        let query = "
+            UPDATE auth.webauthn_credentials
+            SET sign_count = $2,
+                backed_up = $3,
+                last_used_at = NOW()
+            WHERE id = $1
+              AND ($2 > sign_count OR ($2 = 0 AND sign_count = 0))
+        ";
+
+        let updated = conn
+            .execute(query, &[&id, &sign_count, &backed_up])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(updated > 0)
+    }
+
+    // Removes one of the user's credentials and returns how many are left,
+    // or None if the user has no such credential
+    pub async fn delete_credential(&self, user_id: Uuid, id: Uuid) -> Result<Option<i64>> {
+        let mut conn = self.base.get_conn().await?;
+        let tx = conn.transaction().await?;
+
+        let query = "DELETE FROM auth.webauthn_credentials WHERE id = $1 AND user_id = $2";
+        if tx.execute(query, &[&id, &user_id]).await? == 0 {
+            return Ok(None);
+        }
+
+        let query = "SELECT COUNT(*) FROM auth.webauthn_credentials WHERE user_id = $1";
+        let remaining: i64 = tx.query_one(query, &[&user_id]).await?.get(0);
+
+        tx.commit().await?;
+
+        Ok(Some(remaining))
+    }
+
+    pub async fn create_challenge(&self, challenge: &WebauthnChallenge) -> Result<()> {
+        let conn = self.base.get_conn().await?;
+
+        let ceremony = challenge.ceremony.to_string();
+        let mut columns = ColumnValues::new();
+        columns
+            .push("challenge_id", &challenge.challenge_id)
+            .push("user_id", &challenge.user_id)
+            .push("challenge_hash", &challenge.challenge_hash)
+            .push("ceremony", &ceremony)
+            .push("expires_at", &challenge.expires_at);
+        let query = columns.insert_statement("auth.webauthn_challenges", None);
+
+        conn.execute(&query, columns.params())
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(())
+    }
+
+    // Marks an unexpired challenge of the ceremony used and returns it.
+    // A challenge is used up even when the response to it turns out to be
+    // invalid.
+    pub async fn consume_challenge(
+        &self,
+        challenge_hash: &str,
+        ceremony: WebauthnCeremony,
+    ) -> Result<Option<WebauthnChallenge>> {
+        let conn = self.base.get_conn().await?;
+
+        let query = format!(
+            "
+            UPDATE auth.webauthn_challenges
+            SET consumed_at = NOW()
+            WHERE challenge_hash = $1
+              AND ceremony = $2
+              AND consumed_at IS NULL
+              AND expires_at > NOW()
+            RETURNING {}
+            ",
+            CHALLENGE_COLUMNS
+        );
+
+        let row = conn
+            .query_opt(&query, &[&challenge_hash, &ceremony.to_string()])
+            .await
+            .map_err(UserError::DatabaseError)?;
+
+        Ok(row.map(|row| WebauthnChallenge::from_row(&row)))
+    }
+}
+
+impl WebauthnCredential {
+    fn from_row(row: &tokio_postgres::Row) -> Self {
+        WebauthnCredential {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            credential_id: row.get("credential_id"),
+            public_key: row.get("public_key"),
+            algorithm: row.get("algorithm"),
+            sign_count: row.get("sign_count"),
+            transports: row.get("transports"),
+            aaguid: row.get("aaguid"),
+            name: row.get("name"),
+            backup_eligible: row.get("backup_eligible"),
+            backed_up: row.get("backed_up"),
+            created_at: row.get("created_at"),
+            last_used_at: row.get("last_used_at"),
+        }
+    }
+}
+
+impl WebauthnChallenge {
+    fn from_row(row: &tokio_postgres::Row) -> Self {
+        WebauthnChallenge {
+            challenge_id: row.get("challenge_id"),
+            user_id: row.get("user_id"),
+            challenge_hash: row.get("challenge_hash"),
+            ceremony: WebauthnCeremony::from_str(row.get("ceremony"))
+                .expect("ceremony is constrained by valid_ceremony"),
+            expires_at: row.get("expires_at"),
+        }
+    }
+}
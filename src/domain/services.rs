@@ -1,7 +1,25 @@
 mod auth_service;
+mod authz_service;
+mod email_outbox_service;
 mod email_service;
+mod email_template_service;
+mod mfa_service;
+mod password_reset_service;
+mod role_service;
+mod session_service;
+mod token_service;
 mod user_service;
+mod webauthn_service;
 
 pub use auth_service::AuthService;
+pub use authz_service::AuthzService;
+pub use email_outbox_service::EmailOutboxService;
 pub use email_service::EmailService;
+pub use email_template_service::normalize_locale;
+pub use mfa_service::MfaService;
+pub use password_reset_service::PasswordResetService;
+pub use role_service::RoleService;
+pub use session_service::SessionService;
+pub use token_service::TokenService;
 pub use user_service::UserService;
+pub use webauthn_service::WebauthnService;
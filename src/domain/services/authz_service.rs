@@ -0,0 +1,235 @@
+/*
+This module answers "may subject X perform action Y on resource Z" for
+other services, so they do not have to re-implement RBAC.
+
+A subject is allowed when
+- its account is usable (not disabled, deleted or locked, where a
+  lockout that has run out no longer counts),
+- it belongs to the tenant owning the resource, if one is given
+  (system administrators act across tenants), and
+- one of the permissions of its roles in that tenant, global roles
+  included, grants "<resource>:<action>". Without a tenant only global
+  roles count.
+
+What a decision depends on (roles, permissions, membership, state) is
+cached per subject and tenant for AUTHZ_DECISION_TTL seconds, which is
+also how long callers may cache decisions. Role changes made through this
+instance invalidate the cache at once; every change is also written to
+auth.authz_changes, which callers poll to invalidate their own caches
+early.
+*/
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::config::app_config::AppConfig;
+use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
+use crate::domain::models::{
+    AuthzChangePage, AuthzDecision, AuthzReason, Permission, UserState, matching_permission,
+};
+use crate::domain::repositories::AuthzRepository;
+
+use super::user_service::UserService;
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const SYSTEM_ADMIN_ROLE: &str = "system_admin";
+// past this many cached subjects, expired entries are dropped
+const MAX_CACHED_SUBJECTS: usize = 10_000;
+
+// What decisions about one subject in one tenant depend on
+struct Subject {
+    active: bool,
+    member: bool,
+    roles: Vec<String>,
+    permissions: Vec<String>,
+}
+
+// (subject, tenant)
+type SubjectKey = (Uuid, Option<Uuid>);
+
+struct CachedSubject {
+    loaded_at: Instant,
+    subject: Arc<Subject>,
+}
+
+pub struct AuthzService {
+    authz_repo: AuthzRepository,
+    user_service: Arc<UserService>,
+    decision_ttl: Duration,
+    subjects: Mutex<HashMap<SubjectKey, CachedSubject>>,
+}
+
+impl AuthzService {
+    pub fn new(db_pool: Arc<PgPool>, user_service: Arc<UserService>, config: &AppConfig) -> Self {
+        Self {
+            authz_repo: AuthzRepository::new(db_pool),
+            user_service,
+            decision_ttl: Duration::from_secs(config.authz_decision_ttl as u64),
+            subjects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // seconds a decision may be cached
+    pub fn decision_ttl(&self) -> u64 {
+        self.decision_ttl.as_secs()
+    }
+
+    pub async fn check(
+        &self,
+        subject_id: Uuid,
+        resource: &str,
+        action: &str,
+        tenant_id: Option<Uuid>,
+    ) -> Result<AuthzDecision> {
+        let required = required_permission(resource, action)?;
+
+        let Some(subject) = self.subject(subject_id, tenant_id).await? else {
+            return Ok(AuthzDecision::deny(AuthzReason::SubjectNotFound));
+        };
+        if !subject.active {
+            return Ok(AuthzDecision::deny(AuthzReason::SubjectInactive));
+        }
+
+        let is_system_admin = subject.roles.iter().any(|role| role == SYSTEM_ADMIN_ROLE);
+        if !subject.member && !is_system_admin {
+            return Ok(AuthzDecision::deny(AuthzReason::TenantMismatch));
+        }
+
+        Ok(match matching_permission(&subject.permissions, &required) {
+            Some(permission) => AuthzDecision::allow(permission.clone()),
+            None => AuthzDecision::deny(AuthzReason::PermissionMissing),
+        })
+    }
+
+    // Changes from version `since` on, oldest first
+    pub async fn changes_since(&self, since: i64, limit: i64) -> Result<AuthzChangePage> {
+        self.authz_repo.changes_since(since, limit).await
+    }
+
+    pub fn invalidate_user(&self, user_id: Uuid) {
+        self.cache()
+            .retain(|(subject_id, _), _| *subject_id != user_id);
+    }
+
+    // used when a role's permissions change, which may affect anyone
+    pub fn invalidate_all(&self) {
+        self.cache().clear();
+    }
+
+    async fn subject(
+        &self,
+        user_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Option<Arc<Subject>>> {
+        let key = (user_id, tenant_id);
+        if let Some(cached) = self.cache().get(&key)
+            && cached.loaded_at.elapsed() < self.decision_ttl
+        {
+            return Ok(Some(Arc::clone(&cached.subject)));
+        }
+
+        let Some(user) = self.user_service.get_user(user_id).await? else {
+            return Ok(None);
+        };
+        let subject = Arc::new(Subject {
+            active: !user.is_locked()
+                && !matches!(user.user_state, UserState::Disabled | UserState::Deleted),
+            member: match tenant_id {
+                Some(tenant_id) => self.user_service.is_tenant_member(&user, tenant_id).await?,
+                None => true,
+            },
+            roles: self.user_service.get_role_names(user_id, tenant_id).await?,
+            permissions: self
+                .user_service
+                .get_permissions(user_id, tenant_id)
+                .await?,
+        });
+
+        let mut cache = self.cache();
+        if cache.len() >= MAX_CACHED_SUBJECTS {
+            let ttl = self.decision_ttl;
+            cache.retain(|_, cached| cached.loaded_at.elapsed() < ttl);
+        }
+        cache.insert(
+            key,
+            CachedSubject {
+                loaded_at: Instant::now(),
+                subject: Arc::clone(&subject),
+            },
+        );
+        Ok(Some(subject))
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, HashMap<SubjectKey, CachedSubject>> {
+        // the map stays consistent even if a holder panicked
+        self.subjects
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// "<resource>:<action>", without wildcards: a check names one concrete action
+fn required_permission(resource: &str, action: &str) -> Result<String> {
+    let permission: Permission = format!("{}:{}", resource.trim(), action.trim())
+        .parse()
+        .map_err(UserError::InvalidRequest)?;
+    let permission = permission.to_string();
+
+    if permission.split(':').any(|segment| segment == "*") {
+        return Err(UserError::InvalidRequest(
+            "Wildcards are not allowed in authorization checks".to_string(),
+        ));
+    }
+    Ok(permission)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::domain::models::AuthzReason;
+    use crate::test_support::{TestApp, unique_email};
+
+    // Locks the account the way failed logins do, until `interval` from now
+    async fn lock(app: &TestApp, user_id: Uuid, interval: &str) {
+        app.pool
+            .get()
+            .await
+            .unwrap()
+            .execute(
+                &format!(
+                    "UPDATE auth.users SET user_state = 'locked',
+                     account_locked_until = now() + interval '{}'
+                     WHERE id = $1",
+                    interval
+                ),
+                &[&user_id],
+            )
+            .await
+            .unwrap();
+        app.state.authz_service.invalidate_user(user_id);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn an_expired_lockout_no_longer_denies() {
+        let app = TestApp::start().await;
+        let user_id = app.register(&unique_email()).await;
+        app.grant(user_id, "course:read", None).await;
+        let authz = &app.state.authz_service;
+
+        lock(&app, user_id, "10 minutes").await;
+        let decision = authz.check(user_id, "course", "read", None).await.unwrap();
+        assert!(!decision.allowed);
+        assert!(matches!(decision.reason, AuthzReason::SubjectInactive));
+
+        // user_state stays locked until the next login attempt
+        lock(&app, user_id, "-1 minute").await;
+        let decision = authz.check(user_id, "course", "read", None).await.unwrap();
+        assert!(decision.allowed);
+    }
+}
@@ -0,0 +1,348 @@
+/*
+This module holds the email outbox worker.
+
+Emails are queued in auth.email_outbox by the code paths that trigger them,
+in the same transaction as the change itself. The worker polls for due
+emails, renders and sends them through EmailService and
+
+- marks them sent on success,
+- retries failures with exponential backoff (EMAIL_OUTBOX_BACKOFF_BASE,
+  doubling up to EMAIL_OUTBOX_BACKOFF_MAX),
+- dead-letters them after EMAIL_OUTBOX_MAX_ATTEMPTS, keeping the row and
+  its last error for inspection.
+
+Delivery is at least once: an email whose worker dies mid-send is retried
+once its claim expires.
+*/
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info, warn};
+
+use crate::adapters::dtos::EmailOutboxStatsDto;
+use crate::config::app_config::AppConfig;
+use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
+use crate::domain::models::{OutboxEmail, OutboxEmailPayload, OutboxEmailStatus};
+use crate::domain::repositories::EmailOutboxRepository;
+
+use super::email_service::EmailService;
+use super::user_service::UserService;
+
+type Result<T> = std::result::Result<T, UserError>;
+
+// how long a claimed email is reserved for the worker that claimed it
+const CLAIM_LEASE_SECS: f64 = 300.0;
+
+pub struct EmailOutboxService {
+    outbox_repo: EmailOutboxRepository,
+    user_service: Arc<UserService>,
+    email_service: Arc<EmailService>,
+    poll_interval: Duration,
+    batch_size: i64,
+    max_attempts: i32,
+    backoff_base_secs: f64,
+    backoff_max_secs: f64,
+}
+
+impl EmailOutboxService {
+    pub fn new(
+        db_pool: Arc<PgPool>,
+        user_service: Arc<UserService>,
+        email_service: Arc<EmailService>,
+        config: &AppConfig,
+    ) -> Self {
+        Self {
+            outbox_repo: EmailOutboxRepository::new(db_pool),
+            user_service,
+            email_service,
+            poll_interval: Duration::from_secs(config.email_outbox_poll_interval.max(1) as u64),
+            batch_size: config.email_outbox_batch_size.max(1) as i64,
+            max_attempts: config.email_outbox_max_attempts.max(1) as i32,
+            backoff_base_secs: config.email_outbox_backoff_base as f64,
+            backoff_max_secs: config.email_outbox_backoff_max as f64,
+        }
+    }
+
+    // Delivers queued emails until the process exits
+    pub async fn run(self: Arc<Self>) {
+        info!(
+            "Email outbox worker started, polling every {:?}",
+            self.poll_interval
+        );
+
+        let mut interval = tokio::time::interval(self.poll_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            // keep going while full batches come back, so a backlog is
+            // drained without waiting for the next tick
+            loop {
+                match self.deliver_due().await {
+                    Ok(claimed) if claimed as i64 >= self.batch_size => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        error!("Email outbox delivery failed: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // read from the table, so every instance reports the same numbers and
+    // they survive restarts
+    pub async fn stats(&self) -> Result<EmailOutboxStatsDto> {
+        let counts = self.outbox_repo.count_by_status().await?;
+        let retried = self.outbox_repo.count_retries().await?;
+        let count = |status| counts.get(&status).copied().unwrap_or_default();
+
+        Ok(EmailOutboxStatsDto {
+            pending: count(OutboxEmailStatus::Pending),
+            sent: count(OutboxEmailStatus::Sent),
+            dead: count(OutboxEmailStatus::Dead),
+            retried,
+        })
+    }
+
+    // claims one batch of due emails and tries to send each of them.
+    // returns the number of claimed emails
+    async fn deliver_due(&self) -> Result<usize> {
+        let emails = self
+            .outbox_repo
+            .claim_due(self.batch_size, CLAIM_LEASE_SECS)
+            .await?;
+
+        for email in &emails {
+            if let Err(e) = self.deliver(email).await {
+                // the claim expires and the email is retried later
+                error!(
+                    "Failed to record outcome of outbox email {}: {}",
+                    email.id, e
+                );
+            }
+        }
+
+        Ok(emails.len())
+    }
+
+    async fn deliver(&self, email: &OutboxEmail) -> Result<()> {
+        let error = match self.send(email).await {
+            Ok(()) => {
+                self.outbox_repo.mark_sent(email.id).await?;
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+
+        // the recipient is gone, retrying cannot help
+        let permanent = matches!(error, UserError::NotFound);
+
+        if permanent || email.attempts >= self.max_attempts {
+            warn!(
+                "Dead-lettering {} email {} after {} attempts: {}",
+                email.payload.kind(),
+                email.id,
+                email.attempts,
+                error
+            );
+            self.outbox_repo
+                .mark_dead(email.id, &error.to_string())
+                .await?;
+        } else {
+            let delay_secs = self.backoff_secs(email.attempts);
+            warn!(
+                "Delivery of {} email {} failed (attempt {}), retrying in {}s: {}",
+                email.payload.kind(),
+                email.id,
+                email.attempts,
+                delay_secs,
+                error
+            );
+            self.outbox_repo
+                .reschedule(email.id, delay_secs, &error.to_string())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send(&self, email: &OutboxEmail) -> Result<()> {
+        let user = self
+            .user_service
+            .get_user(email.user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+
+        match &email.payload {
+            OutboxEmailPayload::Verification { token } => {
+                self.email_service
+                    .send_verification_email(&user, token)
+                    .await
+            }
+            OutboxEmailPayload::PasswordReset { token } => {
+                self.email_service
+                    .send_password_reset_email(&user, token)
+                    .await
+            }
+            OutboxEmailPayload::AccountLocked { locked_until } => {
+                self.email_service
+                    .send_account_locked_email(&user, *locked_until)
+                    .await
+            }
+            OutboxEmailPayload::NewDevice {
+                device,
+                ip_address,
+                signed_in_at,
+            } => {
+                self.email_service
+                    .send_new_device_email(&user, device, *ip_address, *signed_in_at)
+                    .await
+            }
+        }
+    }
+
+    // base * 2^(attempt - 1), capped
+    fn backoff_secs(&self, attempts: i32) -> f64 {
+        let exponent = (attempts - 1).clamp(0, 30);
+        (self.backoff_base_secs * 2f64.powi(exponent)).min(self.backoff_max_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::json;
+
+    use std::sync::Arc;
+
+    use crate::domain::models::EmailMessage;
+    use crate::domain::repositories::EmailOutboxRepository;
+    use crate::test_support::{TestApp, unique_email};
+
+    // Runs the outbox until a message to `address` went out; other tests
+    // may be delivering at the same time
+    async fn deliver_to(app: &TestApp, address: &str) -> Vec<EmailMessage> {
+        for _ in 0..50 {
+            app.state.email_outbox_service.deliver_due().await.unwrap();
+            let sent = app.emails_to(address);
+            if !sent.is_empty() {
+                return sent;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("no email to {} was delivered", address);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn registration_sends_a_working_verification_link() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        app.register(&email).await;
+
+        let sent = deliver_to(&app, &email).await;
+        assert_eq!(sent.len(), 1);
+        let link = sent[0]
+            .text_body
+            .split_whitespace()
+            .find(|word| word.contains("/verify-email?token="))
+            .expect("the email carries the verification link");
+        let token = link.split("token=").nth(1).unwrap();
+
+        let (status, body) = app
+            .post(
+                "/api/v1/users/verify-email",
+                None,
+                json!({ "token": token }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn delivered_emails_are_not_sent_again() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        app.register(&email).await;
+        deliver_to(&app, &email).await;
+
+        app.state.email_outbox_service.deliver_due().await.unwrap();
+        assert_eq!(app.emails_to(&email).len(), 1);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn dead_lettered_emails_drop_their_payload() {
+        let app = TestApp::start().await;
+        let user_id = app.register(&unique_email()).await;
+
+        // queued for tomorrow, so no concurrent outbox run claims it
+        let conn = app.pool.get().await.unwrap();
+        let id: uuid::Uuid = conn
+            .query_one(
+                "INSERT INTO auth.email_outbox (user_id, kind, payload, next_attempt_at)
+                 VALUES ($1, 'password_reset', '{\"kind\": \"password_reset\", \"token\": \"secret\"}',
+                         NOW() + INTERVAL '1 day')
+                 RETURNING id",
+                &[&user_id],
+            )
+            .await
+            .unwrap()
+            .get("id");
+        EmailOutboxRepository::new(Arc::new(app.pool.clone()))
+            .mark_dead(id, "mailbox unavailable")
+            .await
+            .unwrap();
+
+        let row = conn
+            .query_one(
+                "SELECT status, payload, last_error FROM auth.email_outbox WHERE id = $1",
+                &[&id],
+            )
+            .await
+            .unwrap();
+        assert_eq!(row.get::<_, String>("status"), "dead");
+        assert_eq!(row.get::<_, Option<serde_json::Value>>("payload"), None);
+        assert_eq!(
+            row.get::<_, Option<String>>("last_error").as_deref(),
+            Some("mailbox unavailable")
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn password_resets_are_queued_before_the_request_is_answered() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        let user_id = app.register(&email).await;
+
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/password-reset/request",
+                None,
+                json!({ "email": email }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+
+        let queued: i64 = app
+            .pool
+            .get()
+            .await
+            .unwrap()
+            .query_one(
+                "SELECT COUNT(*) FROM auth.email_outbox
+                 WHERE user_id = $1 AND kind = 'password_reset'",
+                &[&user_id],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(queued, 1);
+    }
+}
@@ -1,28 +1,472 @@
+/*
+This module holds email address handling and delivery. Messages are
+rendered from the templates in email_template_service and handed to the
+configured EmailTransport.
+
+Addresses are validated against the RFC 5322 addr-spec grammar (with the
+RFC 6532 allowance for UTF-8 in the local part), internationalized domains
+are converted to punycode, and the result is lowercased so that each
+mailbox has exactly one canonical form.
+*/
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+use crate::adapters::email_transports::EmailTransport;
+use crate::config::app_config::AppConfig;
 use crate::domain::errors::UserError;
-use tracing::info;
+use crate::domain::models::{EmailMessage, User};
+use tracing::{info, warn};
+
+use super::email_template_service::{EmailTemplate, EmailTemplateService};
+use super::user_service::UserService;
 
-pub struct EmailService;
+const MAX_EMAIL_LENGTH: usize = 254;
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+const MAX_DOMAIN_LENGTH: usize = 253;
+const MAX_LABEL_LENGTH: usize = 63;
+
+pub struct EmailService {
+    transport: Arc<dyn EmailTransport>,
+    templates: EmailTemplateService,
+    user_service: Arc<UserService>,
+    from: String,
+    base_url: String,
+    verification_code_expiration_hours: u8,
+    password_reset_expiration_hours: u8,
+    disposable_domains: HashSet<String>,
+}
 
 impl EmailService {
-    pub fn new() -> Self {
-        Self
+    pub fn new(
+        transport: Arc<dyn EmailTransport>,
+        user_service: Arc<UserService>,
+        config: &AppConfig,
+    ) -> Self {
+        let mut entries = config.disposable_email_domains.clone();
+        if let Some(path) = &config.disposable_email_domains_file {
+            let contents = fs::read_to_string(path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+            entries.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+
+        let disposable_domains = entries
+            .iter()
+            .filter_map(|domain| idna::domain_to_ascii(domain.trim()).ok())
+            .filter(|domain| !domain.is_empty())
+            .collect();
+
+        Self {
+            transport,
+            templates: EmailTemplateService::new(config),
+            user_service,
+            from: config.email_from.clone(),
+            base_url: config.app_base_url.trim_end_matches('/').to_string(),
+            verification_code_expiration_hours: config.verification_code_expiration,
+            password_reset_expiration_hours: config.password_reset_expiration,
+            disposable_domains,
+        }
+    }
+
+    // validates an address supplied for a new account and returns its
+    // canonical form
+    pub fn validate_email(&self, email: &str) -> Result<String, UserError> {
+        let email = normalize_email(email)?;
+        if let Some((_, domain)) = email.rsplit_once('@')
+            && self.is_disposable(domain)
+        {
+            return Err(UserError::DisposableEmail);
+        }
+        Ok(email)
+    }
+
+    // matches the domain itself and any of its subdomains
+    fn is_disposable(&self, domain: &str) -> bool {
+        let mut candidate = domain;
+        loop {
+            if self.disposable_domains.contains(candidate) {
+                return true;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return false,
+            }
+        }
+    }
+
+    // Tokens are only ever written into the message itself, never logged
+    pub async fn send_verification_email(&self, user: &User, token: &str) -> Result<(), UserError> {
+        let variables = HashMap::from([
+            (
+                "link",
+                format!("{}/verify-email?token={}", self.base_url, token),
+            ),
+            (
+                "expiry",
+                format_hours(self.verification_code_expiration_hours),
+            ),
+        ]);
+        self.send(user, EmailTemplate::Verification, variables)
+            .await
+    }
+
+    pub async fn send_password_reset_email(
+        &self,
+        user: &User,
+        token: &str,
+    ) -> Result<(), UserError> {
+        let variables = HashMap::from([
+            (
+                "link",
+                format!("{}/reset-password?token={}", self.base_url, token),
+            ),
+            ("expiry", format_hours(self.password_reset_expiration_hours)),
+        ]);
+        self.send(user, EmailTemplate::PasswordReset, variables)
+            .await
+    }
+
+    pub async fn send_account_locked_email(
+        &self,
+        user: &User,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), UserError> {
+        let variables = HashMap::from([("expiry", format_time(locked_until))]);
+        self.send(user, EmailTemplate::AccountLocked, variables)
+            .await
+    }
+
+    pub async fn send_new_device_email(
+        &self,
+        user: &User,
+        device: &str,
+        ip_address: Option<IpAddr>,
+        signed_in_at: DateTime<Utc>,
+    ) -> Result<(), UserError> {
+        let ip_address = ip_address
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let variables = HashMap::from([
+            ("device", device.to_string()),
+            ("ip_address", ip_address),
+            ("time", format_time(signed_in_at)),
+        ]);
+        self.send(user, EmailTemplate::NewDevice, variables).await
     }
 
-    pub fn validate_email(&self, email: &str) -> Result<bool, UserError> {
-        // TODO: Implement email validation logic here
-        Ok(true)
+    // Sent right away rather than through the outbox, so the code is never
+    // stored in plain text
+    pub async fn send_mfa_code_email(
+        &self,
+        user: &User,
+        code: &str,
+        expiry_minutes: u8,
+    ) -> Result<(), UserError> {
+        let variables = HashMap::from([
+            ("code", code.to_string()),
+            ("expiry", format_minutes(expiry_minutes)),
+        ]);
+        self.send(user, EmailTemplate::MfaCode, variables).await
     }
 
-    pub async fn send_verification_email(
+    // renders a template in the user's locale, using their tenant's
+    // templates where they exist, and hands it to the transport
+    async fn send(
         &self,
-        email: String,
-        token: String,
+        user: &User,
+        template: EmailTemplate,
+        mut variables: HashMap<&str, String>,
     ) -> Result<(), UserError> {
-        // TODO: Implement email sending logic here
-        info!(
-            "Sending verification email to {} with token {}",
-            email, token
+        let tenant_id = self
+            .user_service
+            .get_tenant_id(user)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to resolve tenant for {}: {}", user.id, e);
+                None
+            });
+
+        variables.insert(
+            "user",
+            user.username.clone().unwrap_or_else(|| user.email.clone()),
         );
+        variables.insert("email", user.email.clone());
+
+        let rendered =
+            self.templates
+                .render(template, user.locale.as_deref(), tenant_id, &variables)?;
+
+        self.transport
+            .send(&EmailMessage {
+                from: self.from.clone(),
+                to: user.email.clone(),
+                subject: rendered.subject,
+                text_body: rendered.text_body,
+                html_body: rendered.html_body,
+            })
+            .await?;
+        info!("Sent {:?} email to {}", template, user.email);
         Ok(())
     }
 }
+
+fn format_hours(hours: u8) -> String {
+    if hours == 1 {
+        "1 hour".to_string()
+    } else {
+        format!("{} hours", hours)
+    }
+}
+
+fn format_minutes(minutes: u8) -> String {
+    if minutes == 1 {
+        "1 minute".to_string()
+    } else {
+        format!("{} minutes", minutes)
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+// Returns the canonical form of an email address: trimmed, with the domain
+// converted to punycode and the whole address lowercased.
+pub fn normalize_email(email: &str) -> Result<String, UserError> {
+    let email = email.trim();
+
+    // a quoted local part may itself contain '@', the domain never does
+    let (local_part, domain) = email.rsplit_once('@').ok_or(UserError::InvalidEmail)?;
+
+    if local_part.len() > MAX_LOCAL_PART_LENGTH || !is_valid_local_part(local_part) {
+        return Err(UserError::InvalidEmail);
+    }
+
+    let domain = normalize_domain(domain)?;
+    let email = format!("{}@{}", local_part.to_lowercase(), domain);
+    if email.len() > MAX_EMAIL_LENGTH {
+        return Err(UserError::InvalidEmail);
+    }
+    Ok(email)
+}
+
+// dot-atom or quoted-string
+fn is_valid_local_part(local_part: &str) -> bool {
+    if let Some(quoted) = local_part
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        return is_valid_quoted_string(quoted);
+    }
+
+    !local_part.is_empty()
+        && local_part
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c) || !c.is_ascii()
+}
+
+fn is_valid_quoted_string(quoted: &str) -> bool {
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // quoted-pair
+            '\\' => match chars.next() {
+                Some(escaped)
+                    if escaped == ' ' || escaped == '\t' || escaped.is_ascii_graphic() => {}
+                _ => return false,
+            },
+            '"' => return false,
+            ' ' | '\t' => {}
+            c if c.is_ascii_graphic() || !c.is_ascii() => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+// hostname or address literal, returned in its ASCII form
+fn normalize_domain(domain: &str) -> Result<String, UserError> {
+    if let Some(literal) = domain
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        let valid = match literal.get(..5) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("ipv6:") => {
+                literal[5..].parse::<Ipv6Addr>().is_ok()
+            }
+            _ => literal.parse::<Ipv4Addr>().is_ok(),
+        };
+        return if valid {
+            Ok(domain.to_lowercase())
+        } else {
+            Err(UserError::InvalidEmail)
+        };
+    }
+
+    let domain = idna::domain_to_ascii(domain).map_err(|_| UserError::InvalidEmail)?;
+    if domain.is_empty() || domain.len() > MAX_DOMAIN_LENGTH {
+        return Err(UserError::InvalidEmail);
+    }
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= MAX_LABEL_LENGTH
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    // mail must be routable, so a bare host or a numeric TLD is rejected
+    let valid_tld = labels
+        .last()
+        .is_some_and(|tld| !tld.chars().all(|c| c.is_ascii_digit()));
+
+    if labels.len() < 2 || !valid_labels || !valid_tld {
+        return Err(UserError::InvalidEmail);
+    }
+    Ok(domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::adapters::email_transports::MemoryEmailTransport;
+    use crate::test_support::{TEST_PASSWORD, TestApp, test_config, unconnected_pool};
+
+    fn email_service(disposable_domains: &[&str]) -> EmailService {
+        let mut config = test_config();
+        config.disposable_email_domains = disposable_domains
+            .iter()
+            .map(|domain| domain.to_string())
+            .collect();
+        EmailService::new(
+            Arc::new(MemoryEmailTransport::new()),
+            Arc::new(UserService::new(unconnected_pool(), &config)),
+            &config,
+        )
+    }
+
+    #[test]
+    fn addresses_are_trimmed_and_lowercased() {
+        assert_eq!(
+            normalize_email("  Foo.Bar+Tag@Example.COM ").unwrap(),
+            "foo.bar+tag@example.com"
+        );
+        assert_eq!(
+            normalize_email("\"John Doe\"@example.com").unwrap(),
+            "\"john doe\"@example.com"
+        );
+        assert_eq!(
+            normalize_email("user@[192.0.2.1]").unwrap(),
+            "user@[192.0.2.1]"
+        );
+    }
+
+    #[test]
+    fn internationalized_domains_become_punycode() {
+        assert_eq!(
+            normalize_email("user@Bücher.example").unwrap(),
+            "user@xn--bcher-kva.example"
+        );
+        assert_eq!(
+            normalize_email("user@xn--bcher-kva.example").unwrap(),
+            normalize_email("USER@bücher.EXAMPLE").unwrap()
+        );
+    }
+
+    #[test]
+    fn malformed_addresses_are_rejected() {
+        let long_local_part = format!("{}@example.com", "a".repeat(MAX_LOCAL_PART_LENGTH + 1));
+        for email in [
+            "",
+            "plain",
+            "@example.com",
+            "user@",
+            "user@localhost",
+            "a..b@example.com",
+            ".a@example.com",
+            "a b@example.com",
+            "user@-example.com",
+            "user@example.123",
+            "user@exa_mple.com",
+            "\"unterminated@example.com",
+            "user@[300.0.0.1]",
+            &long_local_part,
+        ] {
+            assert!(
+                matches!(normalize_email(email), Err(UserError::InvalidEmail)),
+                "{:?}",
+                email
+            );
+        }
+    }
+
+    // the service's pool needs a runtime, though it never connects
+    #[actix_web::test]
+    async fn disposable_domains_and_their_subdomains_are_refused() {
+        let emails = email_service(&["mailinator.com", "Wegwerf-Bücher.example"]);
+
+        for email in [
+            "someone@mailinator.com",
+            "someone@Inbox.Mailinator.com",
+            "someone@xn--wegwerf-bcher-4ob.example",
+        ] {
+            assert!(
+                matches!(
+                    emails.validate_email(email),
+                    Err(UserError::DisposableEmail)
+                ),
+                "{}",
+                email
+            );
+        }
+        assert_eq!(
+            emails.validate_email("Someone@NotMailinator.com").unwrap(),
+            "someone@notmailinator.com"
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn addresses_differing_in_case_cannot_register_twice() {
+        let app = TestApp::start().await;
+        let local_part = format!("Case.{}", Uuid::new_v4().simple());
+        app.register(&format!("{}@Example.com", local_part)).await;
+
+        let (status, body) = app
+            .post(
+                "/api/v1/users/register",
+                None,
+                json!({
+                    "email": format!(" {}@EXAMPLE.COM", local_part.to_uppercase()),
+                    "password": TEST_PASSWORD,
+                }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+        assert!(
+            app.state
+                .user_service
+                .user_exists(&format!("{}@example.com", local_part.to_lowercase()))
+                .await
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,505 @@
+/*
+This module holds the transactional email templates.
+
+Every template has a subject, a plain text body and an optional HTML body:
+
+  <EMAIL_TEMPLATES_DIR>/<locale>/<name>.subject.txt
+  <EMAIL_TEMPLATES_DIR>/<locale>/<name>.txt
+  <EMAIL_TEMPLATES_DIR>/<locale>/<name>.html
+
+Tenants override them under <EMAIL_TEMPLATES_DIR>/tenants/<tenant_id>/<locale>/.
+A template is looked up for the recipient's locale, then its language
+(pt-br -> pt), then EMAIL_DEFAULT_LOCALE, first among the tenant's templates
+and then among the global ones. The English templates are compiled in as a
+last resort so mail still goes out without a templates directory.
+
+Variables are written as {{name}}. Values are HTML escaped in the HTML body.
+*/
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::app_config::AppConfig;
+use crate::domain::errors::UserError;
+
+const TENANTS_DIR: &str = "tenants";
+const MAX_LOCALE_LENGTH: usize = 35;
+
+macro_rules! builtin_template {
+    ($name:literal) => {
+        (
+            $name,
+            include_str!(concat!(
+                "../../../templates/email/en/",
+                $name,
+                ".subject.txt"
+            )),
+            include_str!(concat!("../../../templates/email/en/", $name, ".txt")),
+            include_str!(concat!("../../../templates/email/en/", $name, ".html")),
+        )
+    };
+}
+
+const BUILTIN_TEMPLATES: [(&str, &str, &str, &str); 5] = [
+    builtin_template!("verification"),
+    builtin_template!("password_reset"),
+    builtin_template!("account_locked"),
+    builtin_template!("new_device"),
+    builtin_template!("mfa_code"),
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum EmailTemplate {
+    Verification,
+    PasswordReset,
+    AccountLocked,
+    NewDevice,
+    MfaCode,
+}
+
+impl EmailTemplate {
+    fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::Verification => "verification",
+            EmailTemplate::PasswordReset => "password_reset",
+            EmailTemplate::AccountLocked => "account_locked",
+            EmailTemplate::NewDevice => "new_device",
+            EmailTemplate::MfaCode => "mfa_code",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct TemplateSet {
+    subject: Option<String>,
+    text: Option<String>,
+    html: Option<String>,
+}
+
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: Option<String>,
+}
+
+// (tenant, locale, template name)
+type TemplateKey = (Option<Uuid>, String, String);
+
+pub struct EmailTemplateService {
+    templates: HashMap<TemplateKey, TemplateSet>,
+    builtin: HashMap<&'static str, TemplateSet>,
+    default_locale: String,
+}
+
+impl EmailTemplateService {
+    pub fn new(config: &AppConfig) -> Self {
+        let builtin = BUILTIN_TEMPLATES
+            .iter()
+            .map(|(name, subject, text, html)| {
+                let set = TemplateSet {
+                    subject: Some(subject.to_string()),
+                    text: Some(text.to_string()),
+                    html: Some(html.to_string()),
+                };
+                (*name, set)
+            })
+            .collect();
+
+        let mut templates = HashMap::new();
+        let templates_dir = Path::new(&config.email_templates_dir);
+        if templates_dir.is_dir() {
+            load_templates_dir(templates_dir, None, &mut templates);
+            info!(
+                "Loaded {} email templates from {}",
+                templates.len(),
+                templates_dir.display()
+            );
+        } else {
+            warn!(
+                "Email templates directory {} not found, using built-in templates",
+                templates_dir.display()
+            );
+        }
+
+        Self {
+            templates,
+            builtin,
+            default_locale: normalize_locale(&config.email_default_locale)
+                .expect("EMAIL_DEFAULT_LOCALE must be a valid locale"),
+        }
+    }
+
+    pub fn render(
+        &self,
+        template: EmailTemplate,
+        locale: Option<&str>,
+        tenant_id: Option<Uuid>,
+        variables: &HashMap<&str, String>,
+    ) -> Result<RenderedEmail, UserError> {
+        let set = self
+            .find(template.name(), locale, tenant_id)
+            .ok_or_else(|| anyhow!("Email template {} is not available", template.name()))?;
+
+        // find only returns complete sets
+        let subject = set.subject.as_deref().unwrap_or_default();
+        let text = set.text.as_deref().unwrap_or_default();
+
+        Ok(RenderedEmail {
+            // a subject is a single header line
+            subject: substitute(subject.trim(), variables, false).replace(['\r', '\n'], " "),
+            text_body: substitute(text, variables, false),
+            html_body: set
+                .html
+                .as_deref()
+                .map(|html| substitute(html, variables, true)),
+        })
+    }
+
+    fn find(
+        &self,
+        name: &str,
+        locale: Option<&str>,
+        tenant_id: Option<Uuid>,
+    ) -> Option<&TemplateSet> {
+        let mut locales = Vec::new();
+        if let Some(locale) = locale.and_then(normalize_locale) {
+            if let Some((language, _)) = locale.split_once('-') {
+                let language = language.to_string();
+                locales.push(locale);
+                locales.push(language);
+            } else {
+                locales.push(locale);
+            }
+        }
+        locales.push(self.default_locale.clone());
+
+        let tenants = tenant_id.map(Some).into_iter().chain([None]);
+        tenants
+            .flat_map(|tenant| locales.iter().map(move |locale| (tenant, locale)))
+            .find_map(|(tenant, locale)| {
+                self.templates
+                    .get(&(tenant, locale.clone(), name.to_string()))
+            })
+            .or_else(|| self.builtin.get(name))
+    }
+}
+
+// Lowercases a locale tag and uses '-' as separator (pt_BR -> pt-br).
+// Returns None for values that are not shaped like a language tag.
+pub fn normalize_locale(locale: &str) -> Option<String> {
+    let locale = locale.trim().replace('_', "-").to_lowercase();
+    let valid = !locale.is_empty()
+        && locale.len() <= MAX_LOCALE_LENGTH
+        && locale
+            .split('-')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    valid.then_some(locale)
+}
+
+fn load_templates_dir(
+    dir: &Path,
+    tenant_id: Option<Uuid>,
+    templates: &mut HashMap<TemplateKey, TemplateSet>,
+) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        warn!("Failed to read email templates directory {}", dir.display());
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+
+        if tenant_id.is_none() && dir_name == TENANTS_DIR {
+            for tenant_entry in fs::read_dir(&path).into_iter().flatten().flatten() {
+                let tenant_name = tenant_entry.file_name().to_string_lossy().to_string();
+                match tenant_name.parse::<Uuid>() {
+                    Ok(tenant) => load_templates_dir(&tenant_entry.path(), Some(tenant), templates),
+                    Err(_) => warn!("Ignoring email templates of unknown tenant {}", tenant_name),
+                }
+            }
+            continue;
+        }
+
+        match normalize_locale(&dir_name) {
+            Some(locale) => load_locale_dir(&path, tenant_id, &locale, templates),
+            None => warn!("Ignoring email templates in {}", path.display()),
+        }
+    }
+}
+
+fn load_locale_dir(
+    dir: &Path,
+    tenant_id: Option<Uuid>,
+    locale: &str,
+    templates: &mut HashMap<TemplateKey, TemplateSet>,
+) {
+    let mut sets: HashMap<String, TemplateSet> = HashMap::new();
+
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let Ok(contents) = fs::read_to_string(entry.path()) else {
+            warn!("Failed to read email template {}", entry.path().display());
+            continue;
+        };
+
+        if let Some(name) = file_name.strip_suffix(".subject.txt") {
+            sets.entry(name.to_string()).or_default().subject = Some(contents);
+        } else if let Some(name) = file_name.strip_suffix(".txt") {
+            sets.entry(name.to_string()).or_default().text = Some(contents);
+        } else if let Some(name) = file_name.strip_suffix(".html") {
+            sets.entry(name.to_string()).or_default().html = Some(contents);
+        }
+    }
+
+    for (name, set) in sets {
+        if set.subject.is_none() || set.text.is_none() {
+            warn!(
+                "Email template {} in {} needs a subject and a text body, ignoring it",
+                name,
+                dir.display()
+            );
+            continue;
+        }
+        templates.insert((tenant_id, locale.to_string(), name), set);
+    }
+}
+
+// Replaces {{name}} placeholders. Unknown variables render as empty text.
+fn substitute(template: &str, variables: &HashMap<&str, String>, escape_html: bool) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+
+        let name = rest[start + 2..start + 2 + end].trim();
+        match variables.get(name) {
+            Some(value) if escape_html => rendered.push_str(&html_escape(value)),
+            Some(value) => rendered.push_str(value),
+            None => warn!("Email template variable {} is not set", name),
+        }
+        rest = &rest[start + 2 + end + 2..];
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+fn html_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::test_support::test_config;
+
+    // A scratch templates directory, removed when dropped
+    struct TemplatesDir(PathBuf);
+
+    impl TemplatesDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("gandalf-templates-{}", Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, locale_dir: &str, name: &str, subject: &str, text: &str, html: &str) {
+            let dir = self.0.join(locale_dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("{}.subject.txt", name)), subject).unwrap();
+            fs::write(dir.join(format!("{}.txt", name)), text).unwrap();
+            fs::write(dir.join(format!("{}.html", name)), html).unwrap();
+        }
+
+        fn service(&self) -> EmailTemplateService {
+            let mut config = test_config();
+            config.email_templates_dir = self.0.to_string_lossy().to_string();
+            EmailTemplateService::new(&config)
+        }
+    }
+
+    impl Drop for TemplatesDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn variables(pairs: &[(&'static str, &str)]) -> HashMap<&'static str, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect()
+    }
+
+    fn render_subject(
+        service: &EmailTemplateService,
+        locale: Option<&str>,
+        tenant_id: Option<Uuid>,
+    ) -> String {
+        service
+            .render(
+                EmailTemplate::PasswordReset,
+                locale,
+                tenant_id,
+                &HashMap::new(),
+            )
+            .unwrap()
+            .subject
+    }
+
+    #[test]
+    fn variables_are_substituted_and_escaped_only_in_html() {
+        let service = EmailTemplateService::new(&test_config());
+        let email = service
+            .render(
+                EmailTemplate::PasswordReset,
+                None,
+                None,
+                &variables(&[
+                    ("user", "<b>Ann & Bob</b>"),
+                    ("link", "https://app.gandalf.test/reset?token=abc"),
+                    ("expiry", "1 hour"),
+                ]),
+            )
+            .unwrap();
+
+        assert_eq!(email.subject, "Reset your password");
+        assert!(email.text_body.starts_with("Hi <b>Ann & Bob</b>,"));
+        assert!(
+            email
+                .text_body
+                .contains("https://app.gandalf.test/reset?token=abc")
+        );
+        assert!(email.text_body.contains("expires in 1 hour"));
+        assert!(!email.text_body.contains("{{"));
+
+        let html = email.html_body.unwrap();
+        assert!(html.contains("&lt;b&gt;Ann &amp; Bob&lt;/b&gt;"));
+        assert!(!html.contains("<b>Ann"));
+        assert!(!html.contains("{{"));
+    }
+
+    #[test]
+    fn unknown_variables_render_empty_and_subjects_stay_on_one_line() {
+        let templates = TemplatesDir::new();
+        templates.write(
+            "en",
+            "password_reset",
+            "Hello {{user}}\n",
+            "[{{missing}}]",
+            "<p>{{ user }}</p>",
+        );
+        let email = templates
+            .service()
+            .render(
+                EmailTemplate::PasswordReset,
+                None,
+                None,
+                &variables(&[("user", "Ann\r\nBcc: evil@example.com")]),
+            )
+            .unwrap();
+
+        assert_eq!(email.subject, "Hello Ann  Bcc: evil@example.com");
+        assert_eq!(email.text_body, "[]");
+        assert_eq!(
+            email.html_body.as_deref(),
+            Some("<p>Ann\r\nBcc: evil@example.com</p>")
+        );
+    }
+
+    #[test]
+    fn locales_are_normalized() {
+        assert_eq!(normalize_locale(" pt_BR ").as_deref(), Some("pt-br"));
+        assert_eq!(normalize_locale("EN").as_deref(), Some("en"));
+        assert_eq!(normalize_locale(""), None);
+        assert_eq!(normalize_locale("pt--br"), None);
+        assert_eq!(normalize_locale("../en"), None);
+        assert_eq!(normalize_locale(&"a".repeat(MAX_LOCALE_LENGTH + 1)), None);
+    }
+
+    #[test]
+    fn locale_falls_back_to_language_then_default() {
+        let templates = TemplatesDir::new();
+        templates.write("en", "password_reset", "en", "en", "en");
+        templates.write("pt", "password_reset", "pt", "pt", "pt");
+        templates.write("pt-br", "password_reset", "pt-br", "pt-br", "pt-br");
+        let service = templates.service();
+
+        assert_eq!(render_subject(&service, Some("pt_BR"), None), "pt-br");
+        assert_eq!(render_subject(&service, Some("pt-PT"), None), "pt");
+        assert_eq!(render_subject(&service, Some("de-DE"), None), "en");
+        assert_eq!(render_subject(&service, Some("not a locale"), None), "en");
+        assert_eq!(render_subject(&service, None, None), "en");
+    }
+
+    #[test]
+    fn tenant_templates_override_global_ones() {
+        let tenant_id = Uuid::new_v4();
+        let templates = TemplatesDir::new();
+        templates.write("en", "password_reset", "global en", "text", "html");
+        templates.write("pt", "password_reset", "global pt", "text", "html");
+        templates.write(
+            &format!("tenants/{}/en", tenant_id),
+            "password_reset",
+            "tenant en",
+            "text",
+            "html",
+        );
+        let service = templates.service();
+
+        assert_eq!(render_subject(&service, None, Some(tenant_id)), "tenant en");
+        // the tenant's default locale wins over a global template in the
+        // recipient's locale
+        assert_eq!(
+            render_subject(&service, Some("pt"), Some(tenant_id)),
+            "tenant en"
+        );
+        assert_eq!(
+            render_subject(&service, None, Some(Uuid::new_v4())),
+            "global en"
+        );
+        assert_eq!(render_subject(&service, Some("pt"), None), "global pt");
+    }
+
+    #[test]
+    fn incomplete_templates_fall_back_to_builtin() {
+        let templates = TemplatesDir::new();
+        let dir = templates.0.join("en");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("password_reset.txt"), "no subject").unwrap();
+
+        let service = templates.service();
+        assert_eq!(render_subject(&service, None, None), "Reset your password");
+
+        let missing = EmailTemplateService::new(&AppConfig {
+            email_templates_dir: "/nonexistent/templates".to_string(),
+            ..test_config()
+        });
+        assert_eq!(render_subject(&missing, None, None), "Reset your password");
+    }
+}
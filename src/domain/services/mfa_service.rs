@@ -0,0 +1,1469 @@
+/*
+This module holds multi-factor authentication.
+
+TOTP follows RFC 6238 with the parameters every authenticator app
+understands: HMAC-SHA1, 6 digits and 30 second steps. Secrets are stored
+encrypted with AES-256-GCM under MFA_ENCRYPTION_KEY.
+
+A login of a user with MFA enabled takes two steps: the password login
+returns a challenge token instead of session tokens, and the session is
+only created once a valid code is presented with that token. Every
+accepted code moves the method's last used time step forward, so a code
+cannot be used twice. MFA_MAX_ATTEMPTS wrong codes in a row lock the
+account like repeated failed logins do.
+
+Email and SMS methods are answered with short-lived numeric codes sent to
+the enrolled address or phone number. Only a keyed hash of the outstanding
+code is stored, a new code replaces the previous one, and codes can be
+resent once every MFA_OTP_RESEND_INTERVAL seconds. Texts go out through
+the configured SmsSender. A user with several methods picks one per
+challenge.
+
+WebAuthn credentials answer challenges with an assertion instead of a
+code, see webauthn_service.
+
+Enrolling the first method also hands out single-use recovery codes,
+which answer a challenge in place of any other code. They are shown once
+and only their hashes are stored.
+
+Tenants can require MFA for some roles (education_tenants.mfa_required_roles).
+A password login by a user holding such a role in their tenant who has no
+method yet does not get a session either: it returns an enrollment token,
+a JWT restricted to the enrollment endpoints, and the session is created
+once a first method is confirmed with it.
+*/
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{Duration, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore};
+use serde_json::{Value, json};
+use sha1::Sha1;
+use sha2::Sha256;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::adapters::dtos::{
+    AuthTokensDto, ClientContextDto, MfaChallengeDto, MfaCodeSentDto, MfaEnrollmentRequiredDto,
+    TotpEnrollmentDto, WebauthnAssertionDto, WebauthnRequestOptionsDto,
+};
+use crate::adapters::sms_senders::SmsSender;
+use crate::config::app_config::AppConfig;
+use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
+use crate::domain::models::{
+    AccessTokenClaims, MfaChallenge, MfaChallengePurpose, MfaMethod, MfaMethodType, SecurityEvent,
+    SecurityEventType, SmsMessage, User, UserState,
+};
+use crate::domain::repositories::{MfaRepository, SecurityEventRepository};
+
+use super::email_service::EmailService;
+use super::session_service::SessionService;
+use super::token_service::{generate_secure_token, hash_token};
+use super::user_service::UserService;
+use super::webauthn_service::WebauthnService;
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const CHALLENGE_TOKEN_BYTES: usize = 32;
+const ENCRYPTION_KEY_BYTES: usize = 32;
+// methods offered when the tenant requires a first one
+const ENROLLABLE_METHODS: [MfaMethodType; 4] = [
+    MfaMethodType::Totp,
+    MfaMethodType::Webauthn,
+    MfaMethodType::Email,
+    MfaMethodType::Sms,
+];
+const NONCE_BYTES: usize = 12;
+
+const ONE_TIME_CODE_DIGITS: u32 = 6;
+// separates the key hashing one-time codes from the encryption key
+const ONE_TIME_CODE_KEY_LABEL: &[u8] = b"gandalf mfa one-time codes";
+
+const RECOVERY_CODE_COUNT: usize = 10;
+// 80 bits, 16 base32 characters
+const RECOVERY_CODE_BYTES: usize = 10;
+
+const TOTP_SECRET_BYTES: usize = 20;
+const TOTP_DIGITS: usize = 6;
+const TOTP_PERIOD: i64 = 30; // in seconds
+// codes of the neighbouring steps are accepted too, to allow for clock drift
+const TOTP_SKEW: i64 = 1;
+
+pub struct MfaService {
+    mfa_repo: MfaRepository,
+    security_event_repo: SecurityEventRepository,
+    user_service: Arc<UserService>,
+    session_service: Arc<SessionService>,
+    email_service: Arc<EmailService>,
+    sms_sender: Arc<dyn SmsSender>,
+    webauthn_service: Arc<WebauthnService>,
+    cipher: Aes256Gcm,
+    code_key: Vec<u8>,
+    issuer: String,
+    sms_from: String,
+    challenge_ttl: Duration,
+    code_ttl_minutes: u8,
+    resend_interval_secs: u32,
+    max_attempts: i32,
+}
+
+impl MfaService {
+    pub fn new(
+        db_pool: Arc<PgPool>,
+        user_service: Arc<UserService>,
+        session_service: Arc<SessionService>,
+        email_service: Arc<EmailService>,
+        sms_sender: Arc<dyn SmsSender>,
+        webauthn_service: Arc<WebauthnService>,
+        config: &AppConfig,
+    ) -> Self {
+        let key = STANDARD
+            .decode(config.mfa_encryption_key.trim())
+            .expect("MFA_ENCRYPTION_KEY must be base64 encoded");
+        assert!(
+            key.len() == ENCRYPTION_KEY_BYTES,
+            "MFA_ENCRYPTION_KEY must be {} bytes long",
+            ENCRYPTION_KEY_BYTES
+        );
+
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(&key).expect("HMAC accepts keys of any length");
+        mac.update(ONE_TIME_CODE_KEY_LABEL);
+        let code_key = mac.finalize().into_bytes().to_vec();
+
+        Self {
+            mfa_repo: MfaRepository::new(db_pool.clone()),
+            security_event_repo: SecurityEventRepository::new(db_pool),
+            user_service,
+            session_service,
+            email_service,
+            sms_sender,
+            webauthn_service,
+            cipher: Aes256Gcm::new_from_slice(&key).expect("key length checked above"),
+            code_key,
+            issuer: config.mfa_issuer.clone(),
+            sms_from: config.sms_from.clone(),
+            challenge_ttl: Duration::minutes(config.mfa_challenge_expiration as i64),
+            code_ttl_minutes: config.mfa_otp_expiration.max(1),
+            resend_interval_secs: config.mfa_otp_resend_interval,
+            max_attempts: (config.mfa_max_attempts as i32).max(1),
+        }
+    }
+
+    // Generates a new TOTP secret for the user. The method stays inactive
+    // until confirm_enrollment accepts a first code; enrolling again before
+    // that replaces the secret.
+    pub async fn start_totp_enrollment(&self, user_id: Uuid) -> Result<TotpEnrollmentDto> {
+        let user = self
+            .user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+
+        let mut secret = [0u8; TOTP_SECRET_BYTES];
+        OsRng.fill_bytes(&mut secret);
+        self.mfa_repo
+            .create_pending(
+                user.id,
+                MfaMethodType::Totp,
+                None,
+                Some(&self.encrypt_secret(&secret)?),
+            )
+            .await?;
+
+        let secret = BASE32_NOPAD.encode(&secret);
+        let otpauth_uri = format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            percent_encode(&self.issuer),
+            percent_encode(&user.email),
+            secret,
+            percent_encode(&self.issuer),
+            TOTP_DIGITS,
+            TOTP_PERIOD
+        );
+
+        Ok(TotpEnrollmentDto {
+            secret,
+            otpauth_uri,
+        })
+    }
+
+    // Enrolls the user's account email for codes and sends the first one
+    pub async fn start_email_enrollment(&self, user_id: Uuid) -> Result<MfaCodeSentDto> {
+        let user = self
+            .user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+
+        let method = self
+            .mfa_repo
+            .create_pending(user.id, MfaMethodType::Email, Some(&user.email), None)
+            .await?;
+        self.send_code(&user, &method).await
+    }
+
+    // Enrolls a phone number for text message codes and sends the first one
+    pub async fn start_sms_enrollment(
+        &self,
+        user_id: Uuid,
+        phone_number: &str,
+    ) -> Result<MfaCodeSentDto> {
+        let phone_number = normalize_phone_number(phone_number)?;
+        let user = self
+            .user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+
+        let method = self
+            .mfa_repo
+            .create_pending(user.id, MfaMethodType::Sms, Some(&phone_number), None)
+            .await?;
+        self.send_code(&user, &method).await
+    }
+
+    // Activates a pending enrollment with its first code. Returns a fresh
+    // batch of recovery codes when this is the user's first method. Too
+    // many wrong codes discard the enrollment.
+    pub async fn confirm_enrollment(
+        &self,
+        user_id: Uuid,
+        method_type: MfaMethodType,
+        code: &str,
+        client: &ClientContextDto,
+    ) -> Result<Option<Vec<String>>> {
+        match method_type {
+            MfaMethodType::Recovery => return Err(not_enrollable(method_type)),
+            MfaMethodType::Webauthn => return Err(not_code_based(method_type)),
+            _ => {}
+        }
+        let method = self
+            .mfa_repo
+            .find_method(user_id, method_type)
+            .await?
+            .ok_or(UserError::NotFound)?;
+        if method.verified {
+            return Err(UserError::MfaAlreadyEnabled);
+        }
+
+        let (accepted, step) = match method_type {
+            MfaMethodType::Totp => match self.matching_step(&method, code)? {
+                Some(step) => (true, Some(step)),
+                None => (false, None),
+            },
+            _ => (
+                self.mfa_repo
+                    .consume_code(method.method_id, &self.code_hash(&method, code))
+                    .await?,
+                None,
+            ),
+        };
+        if !accepted {
+            let attempts = self
+                .mfa_repo
+                .register_failed_attempt(method.method_id)
+                .await?;
+            if attempts >= self.max_attempts {
+                self.mfa_repo.delete(method.method_id).await?;
+            }
+            return Err(UserError::InvalidMfaCode);
+        }
+
+        let first_method = !self
+            .mfa_repo
+            .find_verified(user_id)
+            .await?
+            .iter()
+            .any(|method| method.method_type != MfaMethodType::Recovery);
+        let (recovery_codes, recovery_code_hashes) = if first_method {
+            let (codes, hashes) = generate_recovery_codes();
+            (Some(codes), Some(hashes))
+        } else {
+            (None, None)
+        };
+        if !self
+            .mfa_repo
+            .confirm(method.method_id, step, recovery_code_hashes.as_deref())
+            .await?
+        {
+            return Err(UserError::MfaAlreadyEnabled);
+        }
+
+        info!("{} MFA enabled for user {}", method_type, user_id);
+        self.record_event(
+            SecurityEventType::MfaEnabled,
+            user_id,
+            client,
+            None,
+            json!({ "method": method_type.to_string() }),
+        )
+        .await?;
+
+        Ok(recovery_codes)
+    }
+
+    // Confirms a sensitive change, such as adding a passkey, with a second
+    // factor so that a stolen access token is not enough: an assertion of
+    // one of the user's credentials, or a code of their `method_type`
+    // ("totp" when omitted). Users without a verified method have nothing
+    // to confirm with and pass.
+    pub async fn verify_step_up(
+        &self,
+        user_id: Uuid,
+        method_type: Option<MfaMethodType>,
+        code: Option<&str>,
+        assertion: Option<&WebauthnAssertionDto>,
+        client: &ClientContextDto,
+    ) -> Result<()> {
+        let methods = self.mfa_repo.find_verified(user_id).await?;
+        if methods
+            .iter()
+            .all(|method| method.method_type == MfaMethodType::Recovery)
+        {
+            return Ok(());
+        }
+
+        let method = |method_type| {
+            methods
+                .iter()
+                .find(|method| method.method_type == method_type && method.enabled)
+                .ok_or(UserError::NotFound)
+        };
+        match (assertion, code) {
+            (Some(assertion), _) => {
+                let method = method(MfaMethodType::Webauthn)?;
+                if self
+                    .webauthn_service
+                    .verify_assertion(assertion, Some(user_id))
+                    .await?
+                    .is_none()
+                {
+                    return Err(self.reject_attempt(method, client).await?);
+                }
+                self.mfa_repo.reset_attempts(method.method_id).await
+            }
+            (None, Some(code)) => {
+                let method = method(method_type.unwrap_or(MfaMethodType::Totp))?;
+                self.verify_code(method, code, client).await
+            }
+            (None, None) => Err(UserError::MfaVerificationRequired),
+        }
+    }
+
+    // Replaces the user's recovery codes with a new batch. Takes a current
+    // code of another method, so a stolen access token is not enough to
+    // obtain codes.
+    pub async fn regenerate_recovery_codes(
+        &self,
+        user_id: Uuid,
+        method_type: MfaMethodType,
+        code: &str,
+        client: &ClientContextDto,
+    ) -> Result<Vec<String>> {
+        if method_type == MfaMethodType::Recovery {
+            return Err(UserError::InvalidRequest(
+                "Recovery codes cannot be regenerated with a recovery code".to_string(),
+            ));
+        }
+        if method_type == MfaMethodType::Webauthn {
+            return Err(not_code_based(method_type));
+        }
+        let method = self
+            .mfa_repo
+            .find_method(user_id, method_type)
+            .await?
+            .filter(|method| method.verified)
+            .ok_or(UserError::NotFound)?;
+        self.verify_code(&method, code, client).await?;
+
+        let (recovery_codes, recovery_code_hashes) = generate_recovery_codes();
+        self.mfa_repo
+            .replace_recovery_codes(user_id, &recovery_code_hashes)
+            .await?;
+
+        info!("Recovery codes regenerated for user {}", user_id);
+        self.record_event(
+            SecurityEventType::RecoveryCodesGenerated,
+            user_id,
+            client,
+            None,
+            json!({ "count": recovery_codes.len() }),
+        )
+        .await?;
+
+        Ok(recovery_codes)
+    }
+
+    // Unused recovery codes of the user
+    pub async fn remaining_recovery_codes(&self, user_id: Uuid) -> Result<i64> {
+        self.mfa_repo.count_unused_recovery_codes(user_id).await
+    }
+
+    // Removes one of the user's methods; a current code of that method is
+    // required
+    pub async fn disable_method(
+        &self,
+        user_id: Uuid,
+        method_type: MfaMethodType,
+        code: &str,
+        client: &ClientContextDto,
+    ) -> Result<()> {
+        match method_type {
+            MfaMethodType::Recovery => return Err(not_enrollable(method_type)),
+            MfaMethodType::Webauthn => return Err(not_code_based(method_type)),
+            _ => {}
+        }
+        let method = self
+            .mfa_repo
+            .find_method(user_id, method_type)
+            .await?
+            .filter(|method| method.verified)
+            .ok_or(UserError::NotFound)?;
+
+        self.verify_code(&method, code, client).await?;
+        self.mfa_repo.delete(method.method_id).await?;
+
+        info!("{} MFA disabled for user {}", method_type, user_id);
+        self.record_event(
+            SecurityEventType::MfaDisabled,
+            user_id,
+            client,
+            None,
+            json!({ "method": method_type.to_string() }),
+        )
+        .await
+    }
+
+    // Sends a code for one of the user's own email or SMS methods, e.g. to
+    // confirm an enrollment or to disable the method
+    pub async fn send_code_to_user(
+        &self,
+        user_id: Uuid,
+        method_type: MfaMethodType,
+    ) -> Result<MfaCodeSentDto> {
+        let user = self
+            .user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+        let method = self
+            .mfa_repo
+            .find_method(user.id, method_type)
+            .await?
+            .filter(|method| method.enabled)
+            .ok_or(UserError::NotFound)?;
+
+        self.send_code(&user, &method).await
+    }
+
+    // Sends a code for the method the user picked to answer a login
+    // challenge with
+    pub async fn send_challenge_code(
+        &self,
+        challenge_token: &str,
+        method_type: MfaMethodType,
+    ) -> Result<MfaCodeSentDto> {
+        let challenge = self
+            .mfa_repo
+            .find_active_challenge(&hash_token(challenge_token))
+            .await?
+            .ok_or(UserError::InvalidMfaChallenge)?;
+        let user = self
+            .user_service
+            .get_user(challenge.user_id)
+            .await?
+            .ok_or(UserError::InvalidMfaChallenge)?;
+        let method = self
+            .mfa_repo
+            .find_method(user.id, method_type)
+            .await?
+            .filter(|method| method.verified && method.enabled)
+            .ok_or(UserError::NotFound)?;
+
+        self.send_code(&user, &method).await
+    }
+
+    // Called after the password checked out. Returns the challenge the
+    // client has to answer, or None when the user can sign in directly.
+    pub async fn start_challenge(
+        &self,
+        user: &User,
+        client: &ClientContextDto,
+    ) -> Result<Option<MfaChallengeDto>> {
+        if !user.requires_mfa {
+            return Ok(None);
+        }
+        let methods = self.mfa_repo.find_verified(user.id).await?;
+        if methods.is_empty() {
+            return Ok(None);
+        }
+
+        let challenge_token = generate_secure_token(CHALLENGE_TOKEN_BYTES);
+        let challenge = MfaChallenge {
+            challenge_id: Uuid::new_v4(),
+            user_id: user.id,
+            token_hash: hash_token(&challenge_token),
+            purpose: MfaChallengePurpose::Verification,
+            device_identifier: client.device_identifier.clone(),
+            device_name: client.device_name.clone(),
+            device_type: client.device_type.clone(),
+            ip_address: client.ip_address,
+            user_agent: client.user_agent.clone(),
+            expires_at: Utc::now() + self.challenge_ttl,
+        };
+        self.mfa_repo.create_challenge(&challenge).await?;
+
+        Ok(Some(MfaChallengeDto {
+            mfa_required: true,
+            challenge_token,
+            methods: methods
+                .iter()
+                .map(|method| method.method_type.to_string())
+                .collect(),
+            expires_in: self.challenge_ttl.num_seconds(),
+        }))
+    }
+
+    // Called after the password checked out for users who can sign in
+    // without a challenge. Returns the enrollment the client has to go
+    // through first when the tenant requires MFA for one of the user's
+    // roles, or None when the user can sign in directly.
+    pub async fn start_required_enrollment(
+        &self,
+        user: &User,
+        client: &ClientContextDto,
+    ) -> Result<Option<MfaEnrollmentRequiredDto>> {
+        let tenant_id = self.user_service.get_default_tenant_id(user).await?;
+        let roles = self
+            .user_service
+            .get_mfa_required_roles(user.id, tenant_id)
+            .await?;
+        if roles.is_empty() || !self.mfa_repo.find_verified(user.id).await?.is_empty() {
+            return Ok(None);
+        }
+
+        let challenge_id = Uuid::new_v4();
+        let (enrollment_token, expires_in) =
+            self.session_service
+                .issue_enrollment_token(user, challenge_id, tenant_id)?;
+        let challenge = MfaChallenge {
+            challenge_id,
+            user_id: user.id,
+            token_hash: hash_token(&enrollment_token),
+            purpose: MfaChallengePurpose::Enrollment,
+            device_identifier: client.device_identifier.clone(),
+            device_name: client.device_name.clone(),
+            device_type: client.device_type.clone(),
+            ip_address: client.ip_address,
+            user_agent: client.user_agent.clone(),
+            expires_at: Utc::now() + Duration::seconds(expires_in),
+        };
+        self.mfa_repo.create_challenge(&challenge).await?;
+
+        Ok(Some(MfaEnrollmentRequiredDto {
+            mfa_enrollment_required: true,
+            enrollment_token,
+            roles,
+            methods: ENROLLABLE_METHODS
+                .iter()
+                .map(|method_type| method_type.to_string())
+                .collect(),
+            expires_in,
+        }))
+    }
+
+    // Starts the session held back by start_required_enrollment, once the
+    // user confirmed a first method with the enrollment token. The
+    // enrollment token is retired.
+    pub async fn complete_required_enrollment(
+        &self,
+        enrollment_claims: &AccessTokenClaims,
+    ) -> Result<AuthTokensDto> {
+        let challenge = self
+            .mfa_repo
+            .find_active_enrollment(enrollment_claims.sid, enrollment_claims.sub)
+            .await?
+            .ok_or(UserError::InvalidMfaChallenge)?;
+        let (challenge, user, client) = self.challenge_login(challenge).await?;
+
+        if self.mfa_repo.find_verified(user.id).await?.is_empty() {
+            return Err(UserError::MfaEnrollmentRequired);
+        }
+        if !self
+            .mfa_repo
+            .consume_challenge(challenge.challenge_id)
+            .await?
+        {
+            return Err(UserError::InvalidMfaChallenge);
+        }
+
+        self.session_service
+            .retire_enrollment_token(enrollment_claims)
+            .await?;
+        self.session_service.create_session(&user, &client).await
+    }
+
+    // Answers a login challenge with a code of one of the user's methods
+    // and starts the session
+    pub async fn complete_challenge(
+        &self,
+        challenge_token: &str,
+        method_type: MfaMethodType,
+        code: &str,
+    ) -> Result<AuthTokensDto> {
+        let (challenge, user, client) = self.open_challenge(challenge_token).await?;
+        let method = self.challenge_method(&user, method_type).await?;
+
+        self.verify_code(&method, code, &client).await?;
+        self.finish_challenge(&challenge, &user, method_type, &client)
+            .await
+    }
+
+    // Options for answering a login challenge with a WebAuthn credential
+    pub async fn start_webauthn_challenge(
+        &self,
+        challenge_token: &str,
+    ) -> Result<WebauthnRequestOptionsDto> {
+        let (_, user, _) = self.open_challenge(challenge_token).await?;
+        self.challenge_method(&user, MfaMethodType::Webauthn)
+            .await?;
+
+        self.webauthn_service
+            .start_authentication(Some(user.id))
+            .await
+    }
+
+    // Answers a login challenge with a WebAuthn assertion and starts the
+    // session. Rejected assertions count like wrong codes.
+    pub async fn complete_webauthn_challenge(
+        &self,
+        challenge_token: &str,
+        assertion: &WebauthnAssertionDto,
+    ) -> Result<AuthTokensDto> {
+        let (challenge, user, client) = self.open_challenge(challenge_token).await?;
+        let method = self
+            .challenge_method(&user, MfaMethodType::Webauthn)
+            .await?;
+
+        if self
+            .webauthn_service
+            .verify_assertion(assertion, Some(user.id))
+            .await?
+            .is_none()
+        {
+            return Err(self.reject_attempt(&method, &client).await?);
+        }
+        self.mfa_repo.reset_attempts(method.method_id).await?;
+
+        self.finish_challenge(&challenge, &user, MfaMethodType::Webauthn, &client)
+            .await
+    }
+
+    // The verification challenge behind `challenge_token`, see
+    // challenge_login
+    async fn open_challenge(
+        &self,
+        challenge_token: &str,
+    ) -> Result<(MfaChallenge, User, ClientContextDto)> {
+        let challenge = self
+            .mfa_repo
+            .find_active_challenge(&hash_token(challenge_token))
+            .await?
+            .ok_or(UserError::InvalidMfaChallenge)?;
+        self.challenge_login(challenge).await
+    }
+
+    // The user of a challenge if they may still sign in, and the client
+    // that started the login
+    async fn challenge_login(
+        &self,
+        challenge: MfaChallenge,
+    ) -> Result<(MfaChallenge, User, ClientContextDto)> {
+        let user = self
+            .user_service
+            .get_user(challenge.user_id)
+            .await?
+            .ok_or(UserError::InvalidMfaChallenge)?;
+        if matches!(user.user_state, UserState::Disabled | UserState::Deleted) {
+            return Err(UserError::AccountDisabled);
+        }
+        if user.is_locked() {
+            return Err(UserError::account_locked(user.account_locked_until));
+        }
+
+        let client = ClientContextDto {
+            ip_address: challenge.ip_address,
+            user_agent: challenge.user_agent.clone(),
+            device_identifier: challenge.device_identifier.clone(),
+            device_name: challenge.device_name.clone(),
+            device_type: challenge.device_type.clone(),
+        };
+        Ok((challenge, user, client))
+    }
+
+    // A method the user can answer challenges with
+    async fn challenge_method(&self, user: &User, method_type: MfaMethodType) -> Result<MfaMethod> {
+        self.mfa_repo
+            .find_method(user.id, method_type)
+            .await?
+            .filter(|method| method.verified && method.enabled)
+            .ok_or(UserError::InvalidMfaCode)
+    }
+
+    // Consumes the answered challenge and starts the session
+    async fn finish_challenge(
+        &self,
+        challenge: &MfaChallenge,
+        user: &User,
+        method_type: MfaMethodType,
+        client: &ClientContextDto,
+    ) -> Result<AuthTokensDto> {
+        // a challenge answers for one login only
+        if !self
+            .mfa_repo
+            .consume_challenge(challenge.challenge_id)
+            .await?
+        {
+            return Err(UserError::InvalidMfaChallenge);
+        }
+
+        self.record_event(
+            SecurityEventType::MfaVerification,
+            user.id,
+            client,
+            None,
+            json!({ "method": method_type.to_string() }),
+        )
+        .await?;
+        self.session_service.create_session(user, client).await
+    }
+
+    // Accepts a valid, unused code for a verified method. Wrong codes are
+    // counted and lock the account once MFA_MAX_ATTEMPTS is reached.
+    async fn verify_code(
+        &self,
+        method: &MfaMethod,
+        code: &str,
+        client: &ClientContextDto,
+    ) -> Result<()> {
+        let accepted = match method.method_type {
+            MfaMethodType::Totp => match self.matching_step(method, code)? {
+                Some(step) => {
+                    self.mfa_repo
+                        .accept_totp_step(method.method_id, step)
+                        .await?
+                }
+                None => false,
+            },
+            MfaMethodType::Recovery => self.use_recovery_code(method, code, client).await?,
+            MfaMethodType::Sms | MfaMethodType::Email => {
+                self.mfa_repo
+                    .consume_code(method.method_id, &self.code_hash(method, code))
+                    .await?
+            }
+            MfaMethodType::Webauthn => return Err(not_code_based(method.method_type)),
+        };
+        if accepted {
+            return Ok(());
+        }
+
+        Err(self.reject_attempt(method, client).await?)
+    }
+
+    // Records a failed answer for the method and returns the error to
+    // report: the lockout once MFA_MAX_ATTEMPTS is reached
+    async fn reject_attempt(
+        &self,
+        method: &MfaMethod,
+        client: &ClientContextDto,
+    ) -> Result<UserError> {
+        self.record_event(
+            SecurityEventType::MfaVerification,
+            method.user_id,
+            client,
+            Some("invalid_code"),
+            json!({ "method": method.method_type.to_string() }),
+        )
+        .await?;
+
+        let attempts = self
+            .mfa_repo
+            .register_failed_attempt(method.method_id)
+            .await?;
+        if attempts >= self.max_attempts {
+            warn!(
+                "Account {} locked after repeated invalid MFA codes",
+                method.user_id
+            );
+            self.mfa_repo.reset_attempts(method.method_id).await?;
+            let locked_until = self.user_service.lock_account(method.user_id).await?;
+            return Ok(UserError::account_locked(Some(locked_until)));
+        }
+
+        Ok(UserError::InvalidMfaCode)
+    }
+
+    // Consumes a recovery code and records that it was used
+    async fn use_recovery_code(
+        &self,
+        method: &MfaMethod,
+        code: &str,
+        client: &ClientContextDto,
+    ) -> Result<bool> {
+        let code_hash = hash_token(&normalize_recovery_code(code));
+        if !self
+            .mfa_repo
+            .consume_recovery_code(method.user_id, &code_hash)
+            .await?
+        {
+            return Ok(false);
+        }
+        self.mfa_repo.reset_attempts(method.method_id).await?;
+
+        let remaining = self
+            .mfa_repo
+            .count_unused_recovery_codes(method.user_id)
+            .await?;
+        info!(
+            "Recovery code used by user {}, {} left",
+            method.user_id, remaining
+        );
+        self.record_event(
+            SecurityEventType::RecoveryCodeUsed,
+            method.user_id,
+            client,
+            None,
+            json!({ "remaining": remaining }),
+        )
+        .await?;
+
+        Ok(true)
+    }
+
+    // Generates a new code for an email or SMS method, stores its hash and
+    // delivers it. A code sent within MFA_OTP_RESEND_INTERVAL is not
+    // replaced.
+    async fn send_code(&self, user: &User, method: &MfaMethod) -> Result<MfaCodeSentDto> {
+        if !matches!(
+            method.method_type,
+            MfaMethodType::Email | MfaMethodType::Sms
+        ) {
+            return Err(UserError::InvalidRequest(format!(
+                "Codes are not sent for {} methods",
+                method.method_type
+            )));
+        }
+
+        let code = format!(
+            "{:0width$}",
+            OsRng.gen_range(0..10u32.pow(ONE_TIME_CODE_DIGITS)),
+            width = ONE_TIME_CODE_DIGITS as usize
+        );
+        if !self
+            .mfa_repo
+            .store_code(
+                method.method_id,
+                &self.code_hash(method, &code),
+                self.code_ttl_minutes as i32,
+                self.resend_interval_secs as f64,
+            )
+            .await?
+        {
+            let retry_after = method
+                .code_sent_at
+                .map(|sent_at| {
+                    (sent_at + Duration::seconds(self.resend_interval_secs as i64) - Utc::now())
+                        .num_seconds()
+                })
+                .unwrap_or(0);
+            return Err(UserError::MfaCodeThrottled {
+                retry_after_secs: retry_after.max(1),
+            });
+        }
+
+        let destination = match method.method_type {
+            MfaMethodType::Sms => {
+                let phone_number = method.identifier.clone().ok_or_else(|| {
+                    anyhow::anyhow!("SMS method {} has no phone number", method.method_id)
+                })?;
+                self.sms_sender
+                    .send(&SmsMessage {
+                        from: self.sms_from.clone(),
+                        to: phone_number.clone(),
+                        body: format!(
+                            "{} is your {} sign-in code. It expires in {} minutes.",
+                            code, self.issuer, self.code_ttl_minutes
+                        ),
+                    })
+                    .await?;
+                mask_phone_number(&phone_number)
+            }
+            _ => {
+                self.email_service
+                    .send_mfa_code_email(user, &code, self.code_ttl_minutes)
+                    .await?;
+                mask_email(&user.email)
+            }
+        };
+        self.mfa_repo.mark_code_sent(method.method_id).await?;
+        info!(
+            "Sent {} MFA code to user {}",
+            method.method_type, method.user_id
+        );
+
+        Ok(MfaCodeSentDto {
+            method: method.method_type.to_string(),
+            destination,
+            expires_in: self.code_ttl_minutes as i64 * 60,
+        })
+    }
+
+    // Keyed hash of a one-time code, bound to its method
+    fn code_hash(&self, method: &MfaMethod, code: &str) -> String {
+        let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.code_key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(method.method_id.as_bytes());
+        mac.update(code.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    // The time step `code` belongs to, if it is a valid code of the method
+    // for the current time and later than the last accepted one. Callers
+    // still record the step atomically, see MfaRepository::accept_totp_step.
+    fn matching_step(&self, method: &MfaMethod, code: &str) -> Result<Option<i64>> {
+        let sealed = method
+            .secret
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("TOTP method {} has no secret", method.method_id))?;
+        let secret = self.decrypt_secret(sealed)?;
+
+        let current = Utc::now().timestamp() / TOTP_PERIOD;
+        Ok(totp_step(&secret, code, current, method.last_used_step))
+    }
+
+    // base64(nonce || ciphertext)
+    fn encrypt_secret(&self, secret: &[u8]) -> Result<String> {
+        let mut nonce = [0u8; NONCE_BYTES];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), secret)
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt MFA secret"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    fn decrypt_secret(&self, sealed: &str) -> Result<Vec<u8>> {
+        let sealed = STANDARD
+            .decode(sealed)
+            .map_err(|_| anyhow::anyhow!("Malformed MFA secret"))?;
+        if sealed.len() < NONCE_BYTES {
+            return Err(anyhow::anyhow!("Malformed MFA secret").into());
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_BYTES);
+        let secret = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt MFA secret"))?;
+        Ok(secret)
+    }
+
+    async fn record_event(
+        &self,
+        event_type: SecurityEventType,
+        user_id: Uuid,
+        client: &ClientContextDto,
+        failure_reason: Option<&str>,
+        metadata: Value,
+    ) -> Result<()> {
+        self.security_event_repo
+            .create(&SecurityEvent {
+                event_type,
+                user_id: Some(user_id),
+                ip_address: client.ip_address,
+                user_agent: client.user_agent.clone(),
+                device_identifier: client.device_identifier.clone(),
+                success: failure_reason.is_none(),
+                failure_reason: failure_reason.map(str::to_string),
+                metadata: Some(metadata),
+            })
+            .await
+    }
+}
+
+// WebAuthn credentials are registered and removed with their own
+// ceremonies, see webauthn_service
+fn not_code_based(method_type: MfaMethodType) -> UserError {
+    UserError::InvalidRequest(format!("{} is not answered with a code", method_type))
+}
+
+fn not_enrollable(method_type: MfaMethodType) -> UserError {
+    UserError::InvalidRequest(format!(
+        "{} is not a method that can be enrolled",
+        method_type
+    ))
+}
+
+// Phone numbers are stored in E.164 form: a plus sign followed by 8 to 15
+// digits. Spaces, dashes, dots and parentheses are dropped.
+fn normalize_phone_number(phone_number: &str) -> Result<String> {
+    let phone_number: String = phone_number
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+    let valid = phone_number.strip_prefix('+').is_some_and(|digits| {
+        (8..=15).contains(&digits.len())
+            && !digits.starts_with('0')
+            && digits.bytes().all(|b| b.is_ascii_digit())
+    });
+
+    if !valid {
+        return Err(UserError::InvalidRequest(
+            "Phone numbers must be in international format, e.g. +15551234567".to_string(),
+        ));
+    }
+    Ok(phone_number)
+}
+
+// +15551234567 -> +*******4567
+fn mask_phone_number(phone_number: &str) -> String {
+    let visible = phone_number.len().saturating_sub(4).max(1);
+    format!("+{}{}", "*".repeat(visible - 1), &phone_number[visible..])
+}
+
+// jane.doe@example.com -> j*******@example.com
+fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local_part, domain)) => {
+            let first = local_part.chars().next().unwrap_or('*');
+            format!(
+                "{}{}@{}",
+                first,
+                "*".repeat(local_part.chars().count().saturating_sub(1)),
+                domain
+            )
+        }
+        None => "*".repeat(email.len()),
+    }
+}
+
+// Returns the codes to show to the user and the hashes to store
+pub(super) fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; RECOVERY_CODE_BYTES];
+            OsRng.fill_bytes(&mut bytes);
+            let code = BASE32_NOPAD.encode(&bytes);
+            let hash = hash_token(&code);
+            // XXXX-XXXX-XXXX-XXXX
+            let groups: Vec<&str> = (0..code.len())
+                .step_by(4)
+                .map(|i| &code[i..i + 4])
+                .collect();
+            (groups.join("-"), hash)
+        })
+        .unzip()
+}
+
+// Recovery codes are accepted without dashes, spaces or in lowercase
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+// The step within TOTP_SKEW of `current` that `code` is the code of,
+// skipping steps up to `last_used_step` so that no code is accepted twice
+fn totp_step(secret: &[u8], code: &str, current: i64, last_used_step: Option<i64>) -> Option<i64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != TOTP_DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let earliest = match last_used_step {
+        Some(last_used_step) => (current - TOTP_SKEW).max(last_used_step + 1),
+        None => current - TOTP_SKEW,
+    };
+    (earliest..=current + TOTP_SKEW).find(|step| {
+        totp_code(secret, *step)
+            .as_bytes()
+            .ct_eq(code.as_bytes())
+            .into()
+    })
+}
+
+// RFC 4226 HOTP value of the counter `step`
+fn totp_code(secret: &[u8], step: i64) -> String {
+    let mut mac =
+        <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS as u32),
+        width = TOTP_DIGITS
+    )
+}
+
+// Escapes everything but unreserved characters (RFC 3986)
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+
+    use super::*;
+    use crate::adapters::email_transports::MemoryEmailTransport;
+    use crate::app_modules::app_state::AppState;
+    use crate::domain::models::SmsMessage;
+    use crate::test_support::{TestApp, find_code, test_config, unique_email, unique_phone_number};
+
+    // the SHA-1 key of RFC 6238, appendix B
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn totp_code_matches_rfc_6238_test_vectors() {
+        // the RFC lists 8 digits; these are their last 6
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ] {
+            assert_eq!(
+                totp_code(RFC_SECRET, time / TOTP_PERIOD),
+                code,
+                "at {}",
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn totp_step_accepts_codes_within_the_skew() {
+        let current = 1111111111 / TOTP_PERIOD;
+        for step in current - TOTP_SKEW..=current + TOTP_SKEW {
+            let code = totp_code(RFC_SECRET, step);
+            assert_eq!(totp_step(RFC_SECRET, &code, current, None), Some(step));
+        }
+
+        let stale = totp_code(RFC_SECRET, current - TOTP_SKEW - 1);
+        assert_eq!(totp_step(RFC_SECRET, &stale, current, None), None);
+    }
+
+    #[test]
+    fn totp_step_rejects_a_replayed_step() {
+        let current = 1111111111 / TOTP_PERIOD;
+        let code = totp_code(RFC_SECRET, current);
+
+        assert_eq!(totp_step(RFC_SECRET, &code, current, None), Some(current));
+        assert_eq!(totp_step(RFC_SECRET, &code, current, Some(current)), None);
+
+        // nor a code from before the last accepted step
+        let earlier = totp_code(RFC_SECRET, current - 1);
+        assert_eq!(
+            totp_step(RFC_SECRET, &earlier, current, Some(current)),
+            None
+        );
+        // later steps within the skew are still accepted
+        let later = totp_code(RFC_SECRET, current + 1);
+        assert_eq!(
+            totp_step(RFC_SECRET, &later, current, Some(current)),
+            Some(current + 1)
+        );
+    }
+
+    #[test]
+    fn totp_step_ignores_spaces_and_rejects_malformed_codes() {
+        let current = 1111111111 / TOTP_PERIOD;
+        assert_eq!(
+            totp_step(RFC_SECRET, "050 471", current, None),
+            Some(current)
+        );
+        assert_eq!(totp_step(RFC_SECRET, "05047", current, None), None);
+        assert_eq!(totp_step(RFC_SECRET, "05047a", current, None), None);
+    }
+
+    struct UnreachableSmsGateway;
+
+    #[async_trait::async_trait]
+    impl SmsSender for UnreachableSmsGateway {
+        async fn send(&self, _message: &SmsMessage) -> Result<()> {
+            Err(anyhow::anyhow!("SMS gateway is unreachable").into())
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn sms_enrollment_is_confirmed_with_the_texted_code() {
+        let app = TestApp::start().await;
+        let (_, access_token) = app.signed_in_user().await;
+        let phone_number = unique_phone_number();
+
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/mfa/sms",
+                Some(&access_token),
+                json!({ "phone_number": phone_number }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let texts = app.texts_to(&phone_number);
+        assert_eq!(texts.len(), 1);
+        let code = find_code(&texts[0].body, ONE_TIME_CODE_DIGITS as usize)
+            .expect("the text carries the code");
+
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/mfa/sms/confirm",
+                Some(&access_token),
+                json!({ "code": code }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(
+            body["recovery_codes"].as_array().map(Vec::len),
+            Some(RECOVERY_CODE_COUNT)
+        );
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn failed_sends_do_not_hold_back_the_next_code() {
+        let app = TestApp::start().await;
+        let (user_id, _) = app.signed_in_user().await;
+        let phone_number = unique_phone_number();
+
+        let unreachable = AppState::with_adapters(
+            app.pool.clone(),
+            &test_config(),
+            Arc::new(MemoryEmailTransport::new()),
+            Arc::new(UnreachableSmsGateway),
+        );
+        assert!(
+            unreachable
+                .mfa_service
+                .start_sms_enrollment(user_id, &phone_number)
+                .await
+                .is_err()
+        );
+
+        app.state
+            .mfa_service
+            .send_code_to_user(user_id, MfaMethodType::Sms)
+            .await
+            .unwrap();
+        assert_eq!(app.texts_to(&phone_number).len(), 1);
+
+        // a delivered code does start the resend interval
+        assert!(matches!(
+            app.state
+                .mfa_service
+                .send_code_to_user(user_id, MfaMethodType::Sms)
+                .await,
+            Err(UserError::MfaCodeThrottled { .. })
+        ));
+    }
+
+    // Enrolls TOTP through the API and returns its secret
+    async fn enroll_totp(app: &TestApp, access_token: &str) -> Vec<u8> {
+        let (status, body) = app
+            .post("/api/v1/auth/mfa/totp", Some(access_token), json!({}))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let secret = BASE32_NOPAD
+            .decode(body["secret"].as_str().unwrap().as_bytes())
+            .unwrap();
+
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/mfa/totp/confirm",
+                Some(access_token),
+                json!({ "code": current_totp(&secret, 0) }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        secret
+    }
+
+    // The code `ahead` steps from now, so a later code than one just used
+    fn current_totp(secret: &[u8], ahead: i64) -> String {
+        totp_code(secret, Utc::now().timestamp() / TOTP_PERIOD + ahead)
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn adding_a_method_needs_a_code_of_an_existing_one() {
+        let app = TestApp::start().await;
+        let (_, access_token) = app.signed_in_user().await;
+        let secret = enroll_totp(&app, &access_token).await;
+
+        let (status, body) = app
+            .post("/api/v1/auth/mfa/email", Some(&access_token), json!({}))
+            .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+        assert_eq!(body["code"], "MFA_VERIFICATION_REQUIRED");
+
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/mfa/email",
+                Some(&access_token),
+                json!({ "method": "totp", "code": current_totp(&secret, 1) }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn a_stolen_access_token_cannot_add_a_phone_number() {
+        let app = TestApp::start().await;
+        let (_, access_token) = app.signed_in_user().await;
+        let secret = enroll_totp(&app, &access_token).await;
+        let phone_number = unique_phone_number();
+
+        for step_up in [json!({}), json!({ "method": "totp", "code": "000000" })] {
+            let mut request = json!({ "phone_number": phone_number });
+            request
+                .as_object_mut()
+                .unwrap()
+                .extend(step_up.as_object().unwrap().clone());
+            let (status, body) = app
+                .post("/api/v1/auth/mfa/sms", Some(&access_token), request)
+                .await;
+            assert!(status.is_client_error(), "{}", body);
+        }
+        assert!(app.texts_to(&phone_number).is_empty());
+
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/mfa/sms",
+                Some(&access_token),
+                json!({
+                    "phone_number": phone_number,
+                    "method": "totp",
+                    "code": current_totp(&secret, 1),
+                }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(app.texts_to(&phone_number).len(), 1);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn tenant_required_mfa_is_enrolled_before_the_session_starts() {
+        let app = TestApp::start().await;
+        let (tenant_id, domain) = app.create_tenant().await;
+        let email = format!("teacher-{}@{}", Uuid::new_v4().simple(), domain);
+        let user_id = app.register(&email).await;
+        let role_id = app.grant(user_id, "course:grade", Some(tenant_id)).await;
+        let conn = app.pool.get().await.unwrap();
+        let role_name: String = conn
+            .query_one(
+                "SELECT role_name FROM auth.roles WHERE id = $1",
+                &[&role_id],
+            )
+            .await
+            .unwrap()
+            .get("role_name");
+        conn.execute(
+            "UPDATE auth.education_tenants SET mfa_required_roles = ARRAY[$1]
+             WHERE tenant_id = $2",
+            &[&role_name.to_uppercase(), &tenant_id],
+        )
+        .await
+        .unwrap();
+
+        let (status, body) = app.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["mfa_enrollment_required"], true);
+        assert_eq!(body["roles"], json!([role_name]));
+        assert!(body.get("access_token").is_none(), "{}", body);
+        let enrollment_token = body["enrollment_token"].as_str().unwrap().to_string();
+
+        // the enrollment token is no session
+        let (status, body) = app
+            .get("/api/v1/auth/tenants", Some(&enrollment_token))
+            .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+        assert_eq!(body["code"], "MFA_ENROLLMENT_REQUIRED");
+        let service_email = unique_email();
+        let service_id = app.register(&service_email).await;
+        app.grant(service_id, "authz:check", None).await;
+        let (_, body) = app.login(&service_email).await;
+        let service_token = body["access_token"].as_str().unwrap().to_string();
+        let (_, body) = app
+            .post(
+                "/api/v1/auth/introspect",
+                Some(&service_token),
+                json!({ "token": enrollment_token }),
+            )
+            .await;
+        assert_eq!(body["active"], false, "{}", body);
+
+        let (status, body) = app
+            .post("/api/v1/auth/mfa/totp", Some(&enrollment_token), json!({}))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let secret = BASE32_NOPAD
+            .decode(body["secret"].as_str().unwrap().as_bytes())
+            .unwrap();
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/mfa/totp/confirm",
+                Some(&enrollment_token),
+                json!({ "code": current_totp(&secret, 0) }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let access_token = body["tokens"]["access_token"].as_str().unwrap();
+
+        let (status, body) = app.get("/api/v1/auth/tenants", Some(access_token)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        // the enrollment token is retired with the login it stood in for
+        let (status, _) = app
+            .post("/api/v1/auth/mfa/email", Some(&enrollment_token), json!({}))
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // from now on the user signs in with the enrolled method
+        let (status, body) = app.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["mfa_required"], true, "{}", body);
+    }
+}
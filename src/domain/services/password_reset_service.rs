@@ -0,0 +1,280 @@
+/*
+This module holds the forgot password flow. Reset tokens are random,
+single-use and expire after PASSWORD_RESET_EXPIRATION hours; only their
+hashes are stored.
+*/
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::adapters::dtos::ClientContextDto;
+use crate::app_modules::auth::PasswordHasher;
+use crate::config::app_config::AppConfig;
+use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
+use crate::domain::models::{
+    AuthProvider, OutboxEmail, OutboxEmailPayload, SecurityEvent, SecurityEventType,
+    SessionRevocationReason, UserState,
+};
+use crate::domain::repositories::{
+    PasswordResetRepository, SecurityEventRepository, UserRepository,
+};
+
+use super::email_service::normalize_email;
+use super::session_service::SessionService;
+use super::token_service::{generate_secure_token, hash_token};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const RESET_TOKEN_BYTES: usize = 32;
+
+pub struct PasswordResetService {
+    user_repo: UserRepository,
+    reset_repo: PasswordResetRepository,
+    security_event_repo: SecurityEventRepository,
+    session_service: Arc<SessionService>,
+    password_hasher: Arc<PasswordHasher>,
+    reset_token_ttl: Duration,
+}
+
+impl PasswordResetService {
+    pub fn new(
+        db_pool: Arc<PgPool>,
+        session_service: Arc<SessionService>,
+        password_hasher: Arc<PasswordHasher>,
+        config: &AppConfig,
+    ) -> Self {
+        Self {
+            user_repo: UserRepository::new(db_pool.clone()),
+            reset_repo: PasswordResetRepository::new(db_pool.clone()),
+            security_event_repo: SecurityEventRepository::new(db_pool),
+            session_service,
+            password_hasher,
+            reset_token_ttl: Duration::hours(config.password_reset_expiration as i64),
+        }
+    }
+
+    // Sends a reset link if the email belongs to an account that signs in
+    // with a password. Nothing is reported back to the caller either way.
+    pub async fn request_reset(&self, email: &str, client: &ClientContextDto) -> Result<()> {
+        let user = match normalize_email(email) {
+            Ok(email) => self.user_repo.find_by_email(&email).await?,
+            Err(_) => None,
+        };
+        let Some(user) = user else {
+            info!("Password reset requested for unknown email");
+            return Ok(());
+        };
+
+        if !matches!(user.auth_provider, AuthProvider::Local)
+            || matches!(user.user_state, UserState::Disabled | UserState::Deleted)
+        {
+            info!(
+                "Password reset requested for ineligible account {}",
+                user.id
+            );
+            return Ok(());
+        }
+
+        let token = generate_secure_token(RESET_TOKEN_BYTES);
+        let token_hash = hash_token(&token);
+        let reset_email = OutboxEmail::new(user.id, OutboxEmailPayload::PasswordReset { token });
+        self.reset_repo
+            .create(
+                user.id,
+                &token_hash,
+                Utc::now() + self.reset_token_ttl,
+                client.ip_address,
+                &reset_email,
+            )
+            .await?;
+
+        self.security_event_repo
+            .create(&SecurityEvent {
+                event_type: SecurityEventType::PasswordResetRequest,
+                user_id: Some(user.id),
+                ip_address: client.ip_address,
+                user_agent: client.user_agent.clone(),
+                success: true,
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    // Sets a new password using a reset token, then signs the user out everywhere
+    pub async fn confirm_reset(
+        &self,
+        token: &str,
+        new_password: &str,
+        client: &ClientContextDto,
+    ) -> Result<()> {
+        let password_hash = self.password_hasher.hash_password(new_password)?;
+
+        let user_id = self
+            .reset_repo
+            .reset_password(&hash_token(token), &password_hash)
+            .await?
+            .ok_or(UserError::InvalidResetToken)?;
+
+        self.session_service
+            .revoke_all_sessions(user_id, SessionRevocationReason::PasswordReset)
+            .await?;
+
+        self.security_event_repo
+            .create(&SecurityEvent {
+                event_type: SecurityEventType::PasswordChange,
+                user_id: Some(user_id),
+                ip_address: client.ip_address,
+                user_agent: client.user_agent.clone(),
+                success: true,
+                metadata: Some(json!({ "method": "password_reset" })),
+                ..Default::default()
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::{Value, json};
+    use uuid::Uuid;
+
+    use crate::test_support::{TestApp, unique_email};
+
+    const NEW_PASSWORD: &str = "Another-Horse-Battery-7";
+
+    // Requests a reset for the account and returns the token queued for it
+    async fn reset_token(app: &TestApp, email: &str, user_id: Uuid) -> String {
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/password-reset/request",
+                None,
+                json!({ "email": email }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+
+        let payload: Value = app
+            .pool
+            .get()
+            .await
+            .unwrap()
+            .query_one(
+                "SELECT payload FROM auth.email_outbox
+                 WHERE user_id = $1 AND kind = 'password_reset'
+                 ORDER BY created_at DESC LIMIT 1",
+                &[&user_id],
+            )
+            .await
+            .unwrap()
+            .get("payload");
+        payload["token"].as_str().unwrap().to_string()
+    }
+
+    async fn confirm(app: &TestApp, token: &str) -> (StatusCode, Value) {
+        app.post(
+            "/api/v1/auth/password-reset/confirm",
+            None,
+            json!({ "token": token, "new_password": NEW_PASSWORD }),
+        )
+        .await
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn a_reset_token_works_once_and_signs_out_everywhere() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        let user_id = app.register(&email).await;
+        let (_, tokens) = app.login(&email).await;
+        let token = reset_token(&app, &email, user_id).await;
+
+        let (status, body) = confirm(&app, &token).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let (status, body) = confirm(&app, &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(body["code"], "INVALID_RESET_TOKEN");
+
+        let (status, _) = app
+            .post(
+                "/api/v1/auth/refresh",
+                None,
+                json!({ "refresh_token": tokens["refresh_token"] }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = app.login(&email).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = app
+            .post(
+                "/api/v1/auth/login",
+                None,
+                json!({ "email": email, "password": NEW_PASSWORD }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let events: i64 = app
+            .pool
+            .get()
+            .await
+            .unwrap()
+            .query_one(
+                "SELECT COUNT(*) FROM auth.security_events
+                 WHERE user_id = $1 AND event_type = 'password_change'",
+                &[&user_id],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(events, 1);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn an_expired_reset_token_is_refused() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        let user_id = app.register(&email).await;
+        let token = reset_token(&app, &email, user_id).await;
+
+        app.pool
+            .get()
+            .await
+            .unwrap()
+            .execute(
+                "UPDATE auth.password_reset_tokens SET expires_at = now() - interval '1 second'
+                 WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await
+            .unwrap();
+        let (status, body) = confirm(&app, &token).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        let (status, _) = app.login(&email).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn unknown_emails_get_the_same_answer() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        app.register(&email).await;
+
+        let request = |email: String| {
+            app.post(
+                "/api/v1/auth/password-reset/request",
+                None,
+                json!({ "email": email }),
+            )
+        };
+        let known = request(email).await;
+        let unknown = request(unique_email()).await;
+        assert_eq!(known, unknown);
+    }
+}
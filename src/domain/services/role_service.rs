@@ -0,0 +1,343 @@
+/*
+This module holds role management. Roles are global; assignments record
+the administrator who made them. Roles carry permissions (see
+permission_model) and inherit the roles below them in an acyclic
+hierarchy, so a user effectively holds their assigned roles plus
+everything those inherit. Role and permission changes reach a user's
+access token on their next login or refresh, and authorization checks
+right away.
+*/
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
+use crate::domain::models::{Permission, PermissionSource, Role, UserRole, permissions_grant};
+use crate::domain::repositories::{RepositoryTrait, RoleRepository};
+
+use super::authz_service::AuthzService;
+use super::user_service::UserService;
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const MAX_ROLE_NAME_LENGTH: usize = 50;
+const MANAGE_ROLES_PERMISSION: &str = "role:manage";
+const SYSTEM_ADMIN_ROLE: &str = "system_admin";
+
+pub struct RoleService {
+    role_repo: RoleRepository,
+    user_service: Arc<UserService>,
+    authz_service: Arc<AuthzService>,
+}
+
+impl RoleService {
+    pub fn new(
+        db_pool: Arc<PgPool>,
+        user_service: Arc<UserService>,
+        authz_service: Arc<AuthzService>,
+    ) -> Self {
+        Self {
+            role_repo: RoleRepository::new(db_pool),
+            user_service,
+            authz_service,
+        }
+    }
+
+    pub async fn list_roles(&self) -> Result<Vec<Role>> {
+        self.role_repo.find_all().await
+    }
+
+    pub async fn get_role(&self, role_id: Uuid) -> Result<Role> {
+        self.role_repo
+            .find_by_id(role_id)
+            .await?
+            .ok_or(UserError::NotFound)
+    }
+
+    pub async fn create_role(&self, role_name: &str, description: Option<&str>) -> Result<Role> {
+        let role_name = normalize_role_name(role_name)?;
+        let role = self
+            .role_repo
+            .create(&role_name, normalize_description(description))
+            .await?;
+
+        info!("Created role {}", role.role_name);
+        Ok(role)
+    }
+
+    pub async fn update_role(
+        &self,
+        role_id: Uuid,
+        role_name: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<Role> {
+        let role_name = role_name.map(normalize_role_name).transpose()?;
+
+        let role = self.get_role(role_id).await?;
+        // tokens and checks refer to system roles by name
+        if role.is_system_role
+            && let Some(name) = &role_name
+            && *name != role.role_name
+        {
+            return Err(UserError::SystemRoleProtected);
+        }
+
+        let role = self
+            .role_repo
+            .update(
+                role_id,
+                role_name.as_deref(),
+                normalize_description(description),
+            )
+            .await?
+            .ok_or(UserError::NotFound)?;
+
+        if role_name.is_some() {
+            self.authz_service.invalidate_all();
+        }
+        Ok(role)
+    }
+
+    pub async fn delete_role(&self, role_id: Uuid) -> Result<()> {
+        let role = self.get_role(role_id).await?;
+        if role.is_system_role {
+            return Err(UserError::SystemRoleProtected);
+        }
+
+        if !self.role_repo.delete(role_id).await? {
+            return Err(UserError::NotFound);
+        }
+
+        self.authz_service.invalidate_all();
+        info!("Deleted role {}", role.role_name);
+        Ok(())
+    }
+
+    pub async fn list_role_permissions(&self, role_id: Uuid) -> Result<Vec<String>> {
+        self.get_role(role_id).await?;
+        self.role_repo.find_permissions(role_id).await
+    }
+
+    // Granting a permission the role already has is a no-op
+    pub async fn grant_permission(&self, role_id: Uuid, permission: &str) -> Result<()> {
+        let permission = parse_permission(permission)?;
+        if self
+            .role_repo
+            .add_permission(role_id, &permission.to_string())
+            .await?
+        {
+            self.authz_service.invalidate_all();
+            info!("Permission {} granted to role {}", permission, role_id);
+        }
+        Ok(())
+    }
+
+    pub async fn revoke_permission(&self, role_id: Uuid, permission: &str) -> Result<()> {
+        let permission = parse_permission(permission)?;
+        if !self
+            .role_repo
+            .remove_permission(role_id, &permission.to_string())
+            .await?
+        {
+            return Err(UserError::NotFound);
+        }
+
+        self.authz_service.invalidate_all();
+        info!("Permission {} revoked from role {}", permission, role_id);
+        Ok(())
+    }
+
+    // Roles that `role_id` inherits directly
+    pub async fn list_inherited_roles(&self, role_id: Uuid) -> Result<Vec<Role>> {
+        self.get_role(role_id).await?;
+        self.role_repo.find_inherited_roles(role_id).await
+    }
+
+    // Makes `role_id` include everything `inherited_role_id` can do.
+    // Adding an edge that already exists is a no-op.
+    pub async fn add_inherited_role(&self, role_id: Uuid, inherited_role_id: Uuid) -> Result<()> {
+        if self
+            .role_repo
+            .add_inheritance(role_id, inherited_role_id)
+            .await?
+        {
+            self.authz_service.invalidate_all();
+            info!("Role {} now inherits role {}", role_id, inherited_role_id);
+        }
+        Ok(())
+    }
+
+    pub async fn remove_inherited_role(
+        &self,
+        role_id: Uuid,
+        inherited_role_id: Uuid,
+    ) -> Result<()> {
+        if !self
+            .role_repo
+            .remove_inheritance(role_id, inherited_role_id)
+            .await?
+        {
+            return Err(UserError::NotFound);
+        }
+
+        self.authz_service.invalidate_all();
+        info!(
+            "Role {} no longer inherits role {}",
+            role_id, inherited_role_id
+        );
+        Ok(())
+    }
+
+    // The granted permissions that cover `permission` for the user in the
+    // tenant, each with the chain of roles it comes through. Empty when not
+    // granted.
+    pub async fn explain_user_permission(
+        &self,
+        user_id: Uuid,
+        permission: &str,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<PermissionSource>> {
+        let permission = parse_permission(permission)?.to_string();
+        self.user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+
+        let sources = self
+            .role_repo
+            .find_permission_sources(user_id, tenant_id)
+            .await?;
+        Ok(sources
+            .into_iter()
+            .filter(|source| {
+                source
+                    .permission
+                    .parse::<Permission>()
+                    .is_ok_and(|granted| granted.grants(&permission))
+            })
+            .collect())
+    }
+
+    // Effective permissions of a user in the tenant, as embedded in their
+    // access tokens for it. Without a tenant only global roles count.
+    pub async fn list_user_permissions(
+        &self,
+        user_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<String>> {
+        self.user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+
+        self.user_service.get_permissions(user_id, tenant_id).await
+    }
+
+    // Role assignments of the user, only those in the tenant when given
+    pub async fn list_user_roles(
+        &self,
+        user_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<UserRole>> {
+        self.user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+
+        let roles = self.role_repo.find_user_roles(user_id).await?;
+        Ok(match tenant_id {
+            Some(tenant_id) => roles
+                .into_iter()
+                .filter(|role| role.tenant_id == Some(tenant_id))
+                .collect(),
+            None => roles,
+        })
+    }
+
+    // Whether the user may manage global role assignments and role
+    // definitions: they hold role:manage through a global role, or are a
+    // global system_admin. A tenant role granting role:manage only covers
+    // assignments in its own tenant.
+    pub async fn can_manage_globally(&self, user_id: Uuid) -> Result<bool> {
+        if self.is_global_system_admin(user_id).await? {
+            return Ok(true);
+        }
+        let permissions = self.user_service.get_permissions(user_id, None).await?;
+        Ok(permissions_grant(&permissions, MANAGE_ROLES_PERMISSION))
+    }
+
+    // Whether the user holds system_admin through a global role
+    pub async fn is_global_system_admin(&self, user_id: Uuid) -> Result<bool> {
+        let roles = self.user_service.get_role_names(user_id, None).await?;
+        Ok(roles.iter().any(|role| role == SYSTEM_ADMIN_ROLE))
+    }
+
+    // Assigns the role within a tenant, or globally without one. Assigning
+    // a role the user already holds there is a no-op.
+    pub async fn assign_role(
+        &self,
+        user_id: Uuid,
+        role_id: Uuid,
+        tenant_id: Option<Uuid>,
+        assigned_by: Uuid,
+    ) -> Result<()> {
+        if self
+            .role_repo
+            .assign(user_id, role_id, tenant_id, assigned_by)
+            .await?
+        {
+            self.authz_service.invalidate_user(user_id);
+            info!(
+                "Role {} assigned to user {} in tenant {:?} by {}",
+                role_id, user_id, tenant_id, assigned_by
+            );
+        }
+        Ok(())
+    }
+
+    pub async fn revoke_role(
+        &self,
+        user_id: Uuid,
+        role_id: Uuid,
+        tenant_id: Option<Uuid>,
+        revoked_by: Uuid,
+    ) -> Result<()> {
+        if !self.role_repo.revoke(user_id, role_id, tenant_id).await? {
+            return Err(UserError::NotFound);
+        }
+
+        self.authz_service.invalidate_user(user_id);
+        info!(
+            "Role {} revoked from user {} in tenant {:?} by {}",
+            role_id, user_id, tenant_id, revoked_by
+        );
+        Ok(())
+    }
+}
+
+// Role names are lowercase identifiers such as "tenant_admin"
+fn normalize_role_name(role_name: &str) -> Result<String> {
+    let role_name = role_name.trim().to_lowercase();
+    let valid = role_name.len() <= MAX_ROLE_NAME_LENGTH
+        && role_name.starts_with(|c: char| c.is_ascii_lowercase())
+        && role_name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    if !valid {
+        return Err(UserError::InvalidRequest(format!(
+            "Role names must start with a letter and contain only letters, digits and underscores (at most {} characters)",
+            MAX_ROLE_NAME_LENGTH
+        )));
+    }
+    Ok(role_name)
+}
+
+fn parse_permission(permission: &str) -> Result<Permission> {
+    permission.parse().map_err(UserError::InvalidRequest)
+}
+
+fn normalize_description(description: Option<&str>) -> Option<&str> {
+    description.map(str::trim).filter(|d| !d.is_empty())
+}
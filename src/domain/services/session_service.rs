@@ -0,0 +1,414 @@
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::adapters::dtos::{AuthTokensDto, ClientContextDto};
+use crate::config::app_config::AppConfig;
+use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
+use crate::domain::models::{
+    AccessTokenClaims, OutboxEmail, OutboxEmailPayload, Session, SessionRevocationReason, User,
+    UserState,
+};
+use crate::domain::repositories::{EmailOutboxRepository, SessionRepository};
+
+use super::token_service::{TokenService, generate_secure_token, hash_token};
+use super::user_service::UserService;
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const REFRESH_TOKEN_BYTES: usize = 32;
+
+pub struct SessionService {
+    session_repo: SessionRepository,
+    user_service: Arc<UserService>,
+    token_service: Arc<TokenService>,
+    email_outbox_repo: EmailOutboxRepository,
+    refresh_token_ttl: Duration,
+}
+
+impl SessionService {
+    pub fn new(
+        db_pool: Arc<PgPool>,
+        user_service: Arc<UserService>,
+        token_service: Arc<TokenService>,
+        config: &AppConfig,
+    ) -> Self {
+        Self {
+            session_repo: SessionRepository::new(db_pool.clone()),
+            email_outbox_repo: EmailOutboxRepository::new(db_pool),
+            user_service,
+            token_service,
+            refresh_token_ttl: Duration::days(config.refresh_token_expiration as i64),
+        }
+    }
+
+    // starts a new session for an authenticated user and issues its tokens.
+    // only the hash of the refresh token is persisted.
+    pub async fn create_session(
+        &self,
+        user: &User,
+        client: &ClientContextDto,
+    ) -> Result<AuthTokensDto> {
+        // only devices that identify themselves can be recognized again
+        let new_device = match &client.device_identifier {
+            Some(device_identifier) => {
+                !self
+                    .session_repo
+                    .device_known(user.id, device_identifier)
+                    .await?
+            }
+            None => false,
+        };
+
+        let refresh_token = generate_secure_token(REFRESH_TOKEN_BYTES);
+        let session_id = Uuid::new_v4();
+        let tenant_id = self.user_service.get_default_tenant_id(user).await?;
+
+        let session = Session {
+            session_id,
+            family_id: session_id,
+            user_id: user.id,
+            refresh_token_hash: hash_token(&refresh_token),
+            device_identifier: client.device_identifier.clone(),
+            device_name: client.device_name.clone(),
+            device_type: client.device_type.clone(),
+            ip_address: client.ip_address,
+            user_agent: client.user_agent.clone(),
+            tenant_id,
+            expires_at: Utc::now() + self.refresh_token_ttl,
+            is_revoked: false,
+            revoked_reason: None,
+        };
+        self.session_repo.create(&session).await?;
+
+        if new_device {
+            let device = client
+                .device_name
+                .as_ref()
+                .or(client.device_type.as_ref())
+                .or(client.user_agent.as_ref())
+                .cloned()
+                .unwrap_or_else(|| "Unknown device".to_string());
+            let alert = OutboxEmail::new(
+                user.id,
+                OutboxEmailPayload::NewDevice {
+                    device,
+                    ip_address: client.ip_address,
+                    signed_in_at: Utc::now(),
+                },
+            );
+            // a missing alert must not block the sign in
+            if let Err(e) = self.email_outbox_repo.enqueue(&alert).await {
+                error!("Failed to queue new device email for {}: {}", user.id, e);
+            }
+        }
+
+        self.issue_tokens(user, &session, refresh_token).await
+    }
+
+    // a token restricted to enrolling a first MFA method, standing in for
+    // the session while the login waits in `challenge_id`. returns the
+    // token and its lifetime in seconds
+    pub fn issue_enrollment_token(
+        &self,
+        user: &User,
+        challenge_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<(String, i64)> {
+        let token = self
+            .token_service
+            .issue_enrollment_token(user.id, challenge_id, tenant_id)?;
+        Ok((token, self.token_service.enrollment_token_ttl()))
+    }
+
+    // blacklists an enrollment token once the login it stood in for has
+    // been completed
+    pub async fn retire_enrollment_token(&self, claims: &AccessTokenClaims) -> Result<()> {
+        self.token_service
+            .revoke_access_token(claims, Some(claims.sub), "mfa_enrolled")
+            .await
+    }
+
+    // exchanges a refresh token for a new token pair. The presented token is
+    // retired on every use; presenting a retired token again is treated as
+    // theft and revokes every session descending from the same login.
+    pub async fn refresh_session(
+        &self,
+        refresh_token: &str,
+        client: &ClientContextDto,
+    ) -> Result<AuthTokensDto> {
+        self.rotate_session(refresh_token, client, None).await
+    }
+
+    // like refresh_session, but the new tokens act in another tenant the
+    // user belongs to. Later refreshes stay in that tenant.
+    pub async fn switch_tenant(
+        &self,
+        refresh_token: &str,
+        tenant_id: Uuid,
+        client: &ClientContextDto,
+    ) -> Result<AuthTokensDto> {
+        self.rotate_session(refresh_token, client, Some(tenant_id))
+            .await
+    }
+
+    async fn rotate_session(
+        &self,
+        refresh_token: &str,
+        client: &ClientContextDto,
+        requested_tenant_id: Option<Uuid>,
+    ) -> Result<AuthTokensDto> {
+        let current = self
+            .session_repo
+            .find_by_refresh_token_hash(&hash_token(refresh_token))
+            .await?
+            .ok_or(UserError::InvalidRefreshToken)?;
+
+        if current.is_revoked {
+            if current.revoked_reason == Some(SessionRevocationReason::Rotated) {
+                return Err(self.handle_token_reuse(&current).await);
+            }
+            return Err(UserError::InvalidRefreshToken);
+        }
+
+        if current.expires_at <= Utc::now() {
+            return Err(UserError::InvalidRefreshToken);
+        }
+
+        let user = self
+            .user_service
+            .get_user(current.user_id)
+            .await?
+            .ok_or(UserError::InvalidRefreshToken)?;
+        if matches!(user.user_state, UserState::Disabled | UserState::Deleted) {
+            return Err(UserError::AccountDisabled);
+        }
+
+        let tenant_id = match requested_tenant_id.or(current.tenant_id) {
+            Some(tenant_id) if self.user_service.is_tenant_member(&user, tenant_id).await? => {
+                Some(tenant_id)
+            }
+            Some(_) if requested_tenant_id.is_some() => return Err(UserError::Forbidden),
+            // the user has left the session's tenant since
+            _ => self.user_service.get_default_tenant_id(&user).await?,
+        };
+        // a tenant that requires MFA for the user's roles, either newly
+        // switched to or since changed, only gets a session after enrollment
+        if !user.requires_mfa
+            && !self
+                .user_service
+                .get_mfa_required_roles(user.id, tenant_id)
+                .await?
+                .is_empty()
+        {
+            return Err(UserError::MfaEnrollmentRequired);
+        }
+
+        let next_refresh_token = generate_secure_token(REFRESH_TOKEN_BYTES);
+        let next = Session {
+            session_id: Uuid::new_v4(),
+            family_id: current.family_id,
+            user_id: current.user_id,
+            refresh_token_hash: hash_token(&next_refresh_token),
+            device_identifier: current.device_identifier.clone(),
+            device_name: current.device_name.clone(),
+            device_type: current.device_type.clone(),
+            ip_address: client.ip_address.or(current.ip_address),
+            user_agent: client.user_agent.clone().or(current.user_agent.clone()),
+            tenant_id,
+            expires_at: Utc::now() + self.refresh_token_ttl,
+            is_revoked: false,
+            revoked_reason: None,
+        };
+
+        if !self.session_repo.rotate(current.session_id, &next).await? {
+            // lost a race against another use of the same refresh token
+            return Err(self.handle_token_reuse(&current).await);
+        }
+
+        self.issue_tokens(&user, &next, next_refresh_token).await
+    }
+
+    // ends the login the access token belongs to and blacklists the token
+    pub async fn logout(&self, claims: &AccessTokenClaims) -> Result<()> {
+        let reason = SessionRevocationReason::Logout;
+        self.token_service
+            .revoke_access_token(claims, Some(claims.sub), &reason.to_string())
+            .await?;
+        self.session_repo
+            .revoke_family_of_session(claims.sid, reason)
+            .await?;
+        Ok(())
+    }
+
+    // ends every session of the user. Access tokens issued to other devices
+    // stop validating as well, since token_service rejects tokens whose
+    // session family is no longer active.
+    pub async fn logout_all(&self, claims: &AccessTokenClaims) -> Result<u64> {
+        let reason = SessionRevocationReason::LogoutAll;
+        self.token_service
+            .revoke_access_token(claims, Some(claims.sub), &reason.to_string())
+            .await?;
+        self.session_repo
+            .revoke_all_for_user(claims.sub, reason)
+            .await
+    }
+
+    // ends every session of a user, e.g. after their password was reset
+    pub async fn revoke_all_sessions(
+        &self,
+        user_id: Uuid,
+        reason: SessionRevocationReason,
+    ) -> Result<u64> {
+        self.session_repo.revoke_all_for_user(user_id, reason).await
+    }
+
+    async fn handle_token_reuse(&self, session: &Session) -> UserError {
+        warn!(
+            "Refresh token reuse detected for user {} (session family {}), revoking family",
+            session.user_id, session.family_id
+        );
+        match self
+            .session_repo
+            .revoke_family(session.family_id, SessionRevocationReason::TokenReuse)
+            .await
+        {
+            Ok(_) => UserError::RefreshTokenReused,
+            Err(e) => e,
+        }
+    }
+
+    async fn issue_tokens(
+        &self,
+        user: &User,
+        session: &Session,
+        refresh_token: String,
+    ) -> Result<AuthTokensDto> {
+        let tenant_id = session.tenant_id;
+        let roles = self.user_service.get_role_names(user.id, tenant_id).await?;
+        let permissions = self
+            .user_service
+            .get_permissions(user.id, tenant_id)
+            .await?;
+        let access_token = self.token_service.issue_access_token(
+            user.id,
+            session.session_id,
+            roles,
+            permissions,
+            tenant_id,
+        )?;
+
+        Ok(AuthTokensDto {
+            access_token,
+            refresh_token,
+            token_type: "Bearer".to_string(),
+            expires_in: self.token_service.access_token_ttl(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::{Value, json};
+
+    use super::hash_token;
+    use crate::test_support::{TestApp, unique_email};
+
+    async fn refresh(app: &TestApp, refresh_token: &str) -> (StatusCode, Value) {
+        app.post(
+            "/api/v1/auth/refresh",
+            None,
+            json!({ "refresh_token": refresh_token }),
+        )
+        .await
+    }
+
+    fn refresh_token(tokens: &Value) -> String {
+        tokens["refresh_token"].as_str().unwrap().to_string()
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn refresh_tokens_rotate_and_are_stored_hashed() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        app.register(&email).await;
+        let (_, tokens) = app.login(&email).await;
+        let first = refresh_token(&tokens);
+
+        let (status, tokens) = refresh(&app, &first).await;
+        assert_eq!(status, StatusCode::OK, "{}", tokens);
+        let second = refresh_token(&tokens);
+        assert_ne!(first, second);
+        assert!(tokens["access_token"].is_string());
+
+        let stored: Vec<String> = app
+            .pool
+            .get()
+            .await
+            .unwrap()
+            .query(
+                "SELECT refresh_token_hash FROM auth.sessions
+                 WHERE refresh_token_hash IN ($1, $2, $3, $4)",
+                &[&first, &second, &hash_token(&first), &hash_token(&second)],
+            )
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(stored.len(), 2);
+        assert!(!stored.contains(&first) && !stored.contains(&second));
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn reusing_a_rotated_token_revokes_the_family() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        app.register(&email).await;
+        let (_, tokens) = app.login(&email).await;
+        let stolen = refresh_token(&tokens);
+
+        let (_, tokens) = refresh(&app, &stolen).await;
+        let latest = refresh_token(&tokens);
+        let access_token = tokens["access_token"].as_str().unwrap().to_string();
+
+        let (status, body) = refresh(&app, &stolen).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+        assert_eq!(body["code"], "INVALID_REFRESH_TOKEN");
+
+        // the legitimate holder's tokens die with the family
+        let (status, _) = refresh(&app, &latest).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = app.get("/api/v1/auth/tenants", Some(&access_token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn expired_refresh_tokens_are_refused() {
+        let app = TestApp::start().await;
+        let email = unique_email();
+        app.register(&email).await;
+        let (_, tokens) = app.login(&email).await;
+        let token = refresh_token(&tokens);
+
+        app.pool
+            .get()
+            .await
+            .unwrap()
+            .execute(
+                "UPDATE auth.sessions SET expires_at = now() - interval '1 second'
+                 WHERE refresh_token_hash = $1",
+                &[&hash_token(&token)],
+            )
+            .await
+            .unwrap();
+        let (status, _) = refresh(&app, &token).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
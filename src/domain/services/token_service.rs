@@ -0,0 +1,311 @@
+/*
+This module holds JWT issuance and validation, and the helpers used for
+opaque tokens (refresh tokens, verification links, ...). Opaque tokens are
+only ever persisted as SHA-256 hashes.
+
+Access tokens are HS256 JWTs signed with JWT_SECRET and carry the standard
+claims (sub, iat, exp, jti, iss, aud) plus the user's roles, effective
+permissions and tenant.
+
+Enrollment tokens are access tokens restricted to the mfa_enrollment scope.
+They are handed out instead of a session when the tenant requires MFA for
+the user's roles and no method is enrolled yet; see mfa_service.
+*/
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::config::app_config::AppConfig;
+use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
+use crate::domain::models::{AccessTokenClaims, MFA_ENROLLMENT_SCOPE};
+use crate::domain::repositories::{SessionRepository, TokenBlacklistRepository};
+
+type Result<T> = std::result::Result<T, UserError>;
+
+pub struct TokenService {
+    blacklist_repo: TokenBlacklistRepository,
+    session_repo: SessionRepository,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    validation: Validation,
+    issuer: String,
+    audience: String,
+    access_token_ttl: Duration,
+    enrollment_token_ttl: Duration,
+    max_token_lifetime: Duration,
+}
+
+impl TokenService {
+    pub fn new(db_pool: Arc<PgPool>, config: &AppConfig) -> Self {
+        assert!(
+            !config.jwt_secret.is_empty(),
+            "JWT_SECRET must not be empty"
+        );
+
+        let max_token_lifetime = Duration::minutes(config.jwt_expiration as i64);
+        // JWT_EXPIRATION caps every token gandalf issues
+        let access_token_ttl =
+            Duration::minutes(config.access_token_expiration as i64).min(max_token_lifetime);
+        let enrollment_token_ttl =
+            Duration::minutes(config.mfa_enrollment_expiration as i64).min(max_token_lifetime);
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&config.jwt_issuer]);
+        validation.set_audience(&[&config.jwt_audience]);
+        validation.set_required_spec_claims(&["sub", "iat", "exp", "jti", "iss", "aud"]);
+
+        Self {
+            blacklist_repo: TokenBlacklistRepository::new(db_pool.clone()),
+            session_repo: SessionRepository::new(db_pool),
+            encoding_key: EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+            validation,
+            issuer: config.jwt_issuer.clone(),
+            audience: config.jwt_audience.clone(),
+            access_token_ttl,
+            enrollment_token_ttl,
+            max_token_lifetime,
+        }
+    }
+
+    // access token lifetime in seconds
+    pub fn access_token_ttl(&self) -> i64 {
+        self.access_token_ttl.num_seconds()
+    }
+
+    // enrollment token lifetime in seconds
+    pub fn enrollment_token_ttl(&self) -> i64 {
+        self.enrollment_token_ttl.num_seconds()
+    }
+
+    pub fn issue_access_token(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        roles: Vec<String>,
+        permissions: Vec<String>,
+        tenant: Option<Uuid>,
+    ) -> Result<String> {
+        let now = Utc::now();
+        let claims = AccessTokenClaims {
+            sub: user_id,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            iat: now.timestamp(),
+            exp: (now + self.access_token_ttl).timestamp(),
+            jti: Uuid::new_v4(),
+            sid: session_id,
+            roles,
+            permissions,
+            tenant,
+            scope: None,
+        };
+
+        self.encode(&claims)
+    }
+
+    // A token that only lets the user enroll a first MFA method for the
+    // login held back in `challenge_id`. It carries no roles or permissions.
+    pub fn issue_enrollment_token(
+        &self,
+        user_id: Uuid,
+        challenge_id: Uuid,
+        tenant: Option<Uuid>,
+    ) -> Result<String> {
+        let now = Utc::now();
+        let claims = AccessTokenClaims {
+            sub: user_id,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            iat: now.timestamp(),
+            exp: (now + self.enrollment_token_ttl).timestamp(),
+            jti: Uuid::new_v4(),
+            sid: challenge_id,
+            roles: Vec::new(),
+            permissions: Vec::new(),
+            tenant,
+            scope: Some(MFA_ENROLLMENT_SCOPE.to_string()),
+        };
+
+        self.encode(&claims)
+    }
+
+    // Checks signature, issuer, audience and expiry, rejects tokens that
+    // outlive JWT_EXPIRATION, tokens whose jti has been blacklisted and
+    // tokens of logins that have been ended, e.g. by logout-all, a password
+    // reset or refresh token reuse.
+    pub async fn validate_access_token(&self, token: &str) -> Result<AccessTokenClaims> {
+        let claims = decode::<AccessTokenClaims>(token, &self.decoding_key, &self.validation)
+            .map_err(|e| {
+                debug!("Rejected access token: {}", e);
+                UserError::InvalidToken
+            })?
+            .claims;
+
+        if claims.exp - claims.iat > self.max_token_lifetime.num_seconds() {
+            debug!(
+                "Rejected access token {}: lifetime exceeds limit",
+                claims.jti
+            );
+            return Err(UserError::InvalidToken);
+        }
+
+        if self.blacklist_repo.is_blacklisted(claims.jti).await? {
+            return Err(UserError::TokenRevoked);
+        }
+        // restricted tokens stand in for a login that has no session yet
+        if claims.scope.is_none() && !self.session_repo.is_family_active(claims.sid).await? {
+            return Err(UserError::TokenRevoked);
+        }
+
+        Ok(claims)
+    }
+
+    // Blacklists an access token until it would have expired anyway
+    pub async fn revoke_access_token(
+        &self,
+        claims: &AccessTokenClaims,
+        revoked_by: Option<Uuid>,
+        reason: &str,
+    ) -> Result<()> {
+        let expires_at = DateTime::<Utc>::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now);
+        self.blacklist_repo
+            .add(claims.jti, claims.sub, expires_at, revoked_by, reason)
+            .await
+    }
+
+    fn encode(&self, claims: &AccessTokenClaims) -> Result<String> {
+        encode(&Header::new(Algorithm::HS256), claims, &self.encoding_key)
+            .map_err(|e| UserError::TokenError(e.to_string()))
+    }
+}
+
+// Generates a URL-safe random token with `num_bytes` bytes of entropy
+pub fn generate_secure_token(num_bytes: usize) -> String {
+    let mut bytes = vec![0u8; num_bytes];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// Hex encoded SHA-256 digest of an opaque token, used for storage and lookup
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::{TestApp, test_config, unconnected_pool};
+
+    // rejections happen before the blacklist is consulted
+    fn token_service(config: &AppConfig) -> TokenService {
+        TokenService::new(unconnected_pool(), config)
+    }
+
+    fn claims(service: &TokenService) -> AccessTokenClaims {
+        let now = Utc::now();
+        AccessTokenClaims {
+            sub: Uuid::new_v4(),
+            iss: service.issuer.clone(),
+            aud: service.audience.clone(),
+            iat: now.timestamp(),
+            exp: (now + service.access_token_ttl).timestamp(),
+            jti: Uuid::new_v4(),
+            sid: Uuid::new_v4(),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+            tenant: None,
+            scope: None,
+        }
+    }
+
+    async fn rejects(service: &TokenService, claims: &AccessTokenClaims) -> bool {
+        let token = service.encode(claims).unwrap();
+        matches!(
+            service.validate_access_token(&token).await,
+            Err(UserError::InvalidToken)
+        )
+    }
+
+    #[actix_web::test]
+    async fn tokens_for_another_issuer_or_audience_are_rejected() {
+        let service = token_service(&test_config());
+
+        let mut other_issuer = claims(&service);
+        other_issuer.iss = "https://elsewhere.example".to_string();
+        assert!(rejects(&service, &other_issuer).await);
+
+        let mut other_audience = claims(&service);
+        other_audience.aud = "another-service".to_string();
+        assert!(rejects(&service, &other_audience).await);
+    }
+
+    #[actix_web::test]
+    async fn expired_and_overlong_tokens_are_rejected() {
+        let service = token_service(&test_config());
+
+        // past the default 60 seconds of leeway
+        let mut expired = claims(&service);
+        expired.iat -= 3600;
+        expired.exp = Utc::now().timestamp() - 120;
+        assert!(rejects(&service, &expired).await);
+
+        let mut overlong = claims(&service);
+        overlong.exp = overlong.iat + service.max_token_lifetime.num_seconds() + 60;
+        assert!(rejects(&service, &overlong).await);
+    }
+
+    #[actix_web::test]
+    async fn tokens_signed_with_another_secret_are_rejected() {
+        let service = token_service(&test_config());
+        let mut config = test_config();
+        config.jwt_secret = "another secret".to_string();
+        let forger = token_service(&config);
+
+        let token = forger.encode(&claims(&service)).unwrap();
+        assert!(matches!(
+            service.validate_access_token(&token).await,
+            Err(UserError::InvalidToken)
+        ));
+        assert!(matches!(
+            service.validate_access_token("not.a.jwt").await,
+            Err(UserError::InvalidToken)
+        ));
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn issued_tokens_validate_until_revoked() {
+        let app = TestApp::start().await;
+        let (user_id, access_token) = app.signed_in_user().await;
+        let tokens = &app.state.token_service;
+
+        let claims = tokens.validate_access_token(&access_token).await.unwrap();
+        assert_eq!(claims.sub, user_id);
+        assert_eq!(claims.iss, test_config().jwt_issuer);
+        assert_eq!(claims.aud, test_config().jwt_audience);
+        assert!(claims.exp > claims.iat);
+
+        let (status, body) = app
+            .post("/api/v1/auth/logout", Some(&access_token), json!({}))
+            .await;
+        assert!(status.is_success(), "{}", body);
+        assert!(matches!(
+            tokens.validate_access_token(&access_token).await,
+            Err(UserError::TokenRevoked)
+        ));
+        let (status, _) = app.get("/api/v1/auth/tenants", Some(&access_token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
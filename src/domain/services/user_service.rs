@@ -1,20 +1,44 @@
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
 use std::sync::Arc;
+use tracing::error;
 use uuid::Uuid;
 
+use crate::config::app_config::AppConfig;
 use crate::config::database::PgPool;
 use crate::domain::errors::UserError;
-use crate::domain::models::User;
-use crate::domain::repositories::{RepositoryTrait, UserRepository};
+use crate::domain::models::{OutboxEmail, OutboxEmailPayload, Tenant, User, effective_permissions};
+use crate::domain::repositories::{
+    EmailOutboxRepository, RepositoryTrait, TenantRepository, UserRepository,
+};
+
+use super::email_service::normalize_email;
+use super::token_service::{generate_secure_token, hash_token};
+
 type Result<T> = std::result::Result<T, UserError>;
 
+const VERIFICATION_TOKEN_BYTES: usize = 32;
+
 pub struct UserService {
     user_repo: UserRepository,
+    tenant_repo: TenantRepository,
+    email_outbox_repo: EmailOutboxRepository,
+    max_failed_login_attempts: i32,
+    account_lockout_duration: i32,     // in minutes
+    verification_code_expiration: i32, // in hours
+    verification_resend_interval: f64, // in seconds
 }
 
 impl UserService {
-    pub fn new(db_pool: Arc<PgPool>) -> Self {
+    pub fn new(db_pool: Arc<PgPool>, config: &AppConfig) -> Self {
         Self {
-            user_repo: UserRepository::new(db_pool),
+            user_repo: UserRepository::new(db_pool.clone()),
+            tenant_repo: TenantRepository::new(db_pool.clone()),
+            email_outbox_repo: EmailOutboxRepository::new(db_pool),
+            max_failed_login_attempts: config.max_failed_login_attempts as i32,
+            account_lockout_duration: config.account_lockout_duration as i32,
+            verification_code_expiration: config.verification_code_expiration as i32,
+            verification_resend_interval: config.verification_resend_interval as f64,
         }
     }
 
@@ -23,28 +47,251 @@ impl UserService {
         Ok(user)
     }
 
-    // takes a new user object and persists it to the database
-    pub async fn create_user(&self, user: User) -> Result<User> {
-        let user = self.user_repo.create(&user).await?;
+    // takes a new user object and persists it to the database together with
+    // a verification token; the verification email is queued in the outbox
+    // in the same transaction. only the hash of the token is stored on the user.
+    pub async fn create_user(&self, mut user: User) -> Result<User> {
+        let token = generate_secure_token(VERIFICATION_TOKEN_BYTES);
+        user.email_verification_token = Some(hash_token(&token));
+        user.email_verification_sent_at = Some(Utc::now());
+
+        let verification_email =
+            OutboxEmail::new(user.id, OutboxEmailPayload::Verification { token });
+        let user = self
+            .user_repo
+            .create(&user, Some(&verification_email))
+            .await?;
         Ok(user)
     }
 
     // creates a new user with default values and email
     // user not persisted
     pub fn create_user_with_defaults(&self, email: &String) -> User {
-        let mut user = User::default();
-        user.email = email.to_string();
-        user
+        User {
+            email: email.to_string(),
+            ..Default::default()
+        }
     }
 
     pub async fn user_exists(&self, email: &str) -> Result<bool> {
-        let exists = self.user_repo.email_exists(email).await?;
+        let exists = self
+            .user_repo
+            .email_exists(&normalize_email(email)?)
+            .await?;
         Ok(exists)
     }
 
-    pub async fn generate_email_verification_token(&self, user_id: &Uuid) -> Result<String> {
-        // Todo: generate a token and save it to the database
-        // let token = Uuid::new_v4().to_string();
-        Ok("some very long verification token".to_string())
+    // an address that cannot be normalized cannot belong to an account
+    pub async fn find_by_email(&self, email: &str) -> Result<Option<User>> {
+        let Ok(email) = normalize_email(email) else {
+            return Ok(None);
+        };
+        let user = self.user_repo.find_by_email(&email).await?;
+        Ok(user)
+    }
+
+    // stamps last_login_at/last_login_ip/last_user_agent after a successful sign in
+    pub async fn record_successful_login(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<IpAddr>,
+        user_agent: Option<&str>,
+    ) -> Result<()> {
+        self.user_repo
+            .record_successful_login(user_id, ip_address, user_agent)
+            .await
+    }
+
+    // records a failed sign in attempt and returns the lockout expiry when the
+    // attempt locked the account, in which case the owner is notified.
+    // MAX_FAILED_LOGIN_ATTEMPTS=0 disables lockout.
+    pub async fn register_failed_login(&self, user_id: Uuid) -> Result<Option<DateTime<Utc>>> {
+        let max_attempts = if self.max_failed_login_attempts == 0 {
+            i32::MAX
+        } else {
+            self.max_failed_login_attempts
+        };
+
+        let locked_until = self
+            .user_repo
+            .register_failed_login(user_id, max_attempts, self.account_lockout_duration)
+            .await?;
+
+        if let Some(locked_until) = locked_until {
+            self.send_lock_notice(user_id, locked_until).await;
+        }
+
+        Ok(locked_until)
+    }
+
+    // locks the account for ACCOUNT_LOCKOUT_DURATION and notifies the owner
+    pub async fn lock_account(&self, user_id: Uuid) -> Result<DateTime<Utc>> {
+        let locked_until = self
+            .user_repo
+            .lock_account(user_id, self.account_lockout_duration)
+            .await?;
+        self.send_lock_notice(user_id, locked_until).await;
+        Ok(locked_until)
+    }
+
+    // the lockout itself stands even if the notice cannot be queued
+    async fn send_lock_notice(&self, user_id: Uuid, locked_until: DateTime<Utc>) {
+        let notice = OutboxEmail::new(user_id, OutboxEmailPayload::AccountLocked { locked_until });
+        if let Err(e) = self.email_outbox_repo.enqueue(&notice).await {
+            error!(
+                "Failed to queue account locked email for {}: {}",
+                user_id, e
+            );
+        }
+    }
+
+    // Permissions of the user's roles in the tenant (global roles
+    // included), without those covered by a wildcard the user also holds
+    pub async fn get_permissions(
+        &self,
+        user_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<String>> {
+        let permissions = self.user_repo.find_permissions(user_id, tenant_id).await?;
+        Ok(effective_permissions(permissions))
+    }
+
+    pub async fn get_role_names(
+        &self,
+        user_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<String>> {
+        self.user_repo.find_role_names(user_id, tenant_id).await
+    }
+
+    // the user's roles in the tenant that may only be used with MFA, as
+    // listed in the tenant's mfa_required_roles
+    pub async fn get_mfa_required_roles(
+        &self,
+        user_id: Uuid,
+        tenant_id: Option<Uuid>,
+    ) -> Result<Vec<String>> {
+        let Some(tenant_id) = tenant_id else {
+            return Ok(Vec::new());
+        };
+        let required_roles = self.tenant_repo.find_mfa_required_roles(tenant_id).await?;
+        if required_roles.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let roles = self.get_role_names(user_id, Some(tenant_id)).await?;
+        Ok(roles
+            .into_iter()
+            .filter(|role| required_roles.iter().any(|r| r.eq_ignore_ascii_case(role)))
+            .collect())
+    }
+
+    // tenants the user belongs to: the one owning their email domain, once
+    // the address is verified, and every tenant they hold a role in
+    pub async fn get_tenants(&self, user: &User) -> Result<Vec<Tenant>> {
+        let domain = user
+            .email
+            .rsplit_once('@')
+            .filter(|_| user.email_verified)
+            .map(|(_, domain)| domain);
+        self.tenant_repo.find_for_user(user.id, domain).await
+    }
+
+    pub async fn is_tenant_member(&self, user: &User, tenant_id: Uuid) -> Result<bool> {
+        let tenants = self.get_tenants(user).await?;
+        Ok(tenants.iter().any(|tenant| tenant.tenant_id == tenant_id))
+    }
+
+    // tenant a new session starts in: the email domain's tenant if the
+    // address is verified, otherwise the first tenant the user holds a role in
+    pub async fn get_default_tenant_id(&self, user: &User) -> Result<Option<Uuid>> {
+        if user.email_verified
+            && let Some(tenant_id) = self.get_tenant_id(user).await?
+        {
+            return Ok(Some(tenant_id));
+        }
+        let tenants = self.get_tenants(user).await?;
+        Ok(tenants.first().map(|tenant| tenant.tenant_id))
+    }
+
+    // resolves the education tenant a user belongs to from their email domain
+    pub async fn get_tenant_id(&self, user: &User) -> Result<Option<Uuid>> {
+        let Some((_, domain)) = user.email.rsplit_once('@') else {
+            return Ok(None);
+        };
+        self.tenant_repo.find_id_by_domain(domain).await
+    }
+
+    // replaces the stored hash, e.g. when upgrading to stronger hashing parameters
+    pub async fn update_password_hash(&self, user_id: Uuid, password_hash: &str) -> Result<()> {
+        self.user_repo
+            .update_password_hash(user_id, password_hash)
+            .await
+    }
+
+    // issues a fresh verification token for an unverified account and queues
+    // the email carrying it. returns false when the email is unknown, already
+    // verified, or a token was sent within VERIFICATION_RESEND_INTERVAL, so
+    // callers can respond uniformly.
+    pub async fn regenerate_email_verification_token(&self, email: &str) -> Result<bool> {
+        let Ok(email) = normalize_email(email) else {
+            return Ok(false);
+        };
+        let token = generate_secure_token(VERIFICATION_TOKEN_BYTES);
+        let user_id = self
+            .user_repo
+            .replace_email_verification_token(
+                &email,
+                &hash_token(&token),
+                self.verification_resend_interval,
+                OutboxEmailPayload::Verification { token },
+            )
+            .await?;
+        Ok(user_id.is_some())
+    }
+
+    // verifies the email address the token was sent to
+    pub async fn verify_email(&self, token: &str) -> Result<Uuid> {
+        self.user_repo
+            .verify_email(&hash_token(token), self.verification_code_expiration)
+            .await?
+            .ok_or(UserError::InvalidVerificationToken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::TestApp;
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn email_domain_grants_membership_once_verified() {
+        let app = TestApp::start().await;
+        let (tenant_id, domain) = app.create_tenant().await;
+        let user_id = app.register(&format!("teacher@{}", domain)).await;
+        let users = &app.state.user_service;
+
+        let user = users.get_user(user_id).await.unwrap().unwrap();
+        assert!(users.get_tenants(&user).await.unwrap().is_empty());
+        assert!(!users.is_tenant_member(&user, tenant_id).await.unwrap());
+        assert_eq!(users.get_default_tenant_id(&user).await.unwrap(), None);
+
+        app.pool
+            .get()
+            .await
+            .unwrap()
+            .execute(
+                "UPDATE auth.users SET email_verified = TRUE WHERE id = $1",
+                &[&user_id],
+            )
+            .await
+            .unwrap();
+
+        let user = users.get_user(user_id).await.unwrap().unwrap();
+        assert!(users.is_tenant_member(&user, tenant_id).await.unwrap());
+        assert_eq!(
+            users.get_default_tenant_id(&user).await.unwrap(),
+            Some(tenant_id)
+        );
     }
 }
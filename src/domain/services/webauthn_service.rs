@@ -0,0 +1,697 @@
+/*
+This module holds the WebAuthn relying party.
+
+Users register credentials (passkeys or security keys) with a registration
+ceremony and prove possession of one with an authentication ceremony:
+
+  1. the server issues options holding a random, single-use challenge
+  2. the browser has an authenticator sign it and returns the result
+  3. the server checks the client data (ceremony type, challenge, origin
+     in WEBAUTHN_ORIGINS), the authenticator data (RP id hash of
+     WEBAUTHN_RP_ID, user presence) and, for assertions, the signature and
+     signature counter
+
+ES256 and RS256 credentials are accepted. Attestation is not requested,
+so attestation statements are not verified; a credential is trusted
+because the signed-in user registered it, not because of its make.
+
+A credential answers MFA challenges like any other method. Passkeys also
+sign users in on their own, without a password or email: the options name
+no credentials, the authenticator offers the passkeys it holds for the RP
+and identifies the user by the user handle. Such logins require user
+verification (PIN or biometrics), so they count as multi-factor.
+*/
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{Duration, Utc};
+use ciborium::Value as CborValue;
+use p256::ecdsa::signature::Verifier;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::adapters::dtos::{
+    ClientContextDto, WebauthnAssertionDto, WebauthnAuthenticatorSelectionDto,
+    WebauthnCreationOptionsDto, WebauthnCredentialDescriptorDto, WebauthnCredentialDto,
+    WebauthnCredentialParametersDto, WebauthnRegistrationDto, WebauthnRelyingPartyDto,
+    WebauthnRequestOptionsDto, WebauthnUserEntityDto,
+};
+use crate::config::app_config::AppConfig;
+use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
+use crate::domain::models::{
+    MfaMethodType, SecurityEvent, SecurityEventType, WebauthnCeremony, WebauthnChallenge,
+    WebauthnCredential,
+};
+use crate::domain::repositories::{MfaRepository, SecurityEventRepository, WebauthnRepository};
+
+use super::mfa_service::generate_recovery_codes;
+use super::token_service::hash_token;
+use super::user_service::UserService;
+
+type Result<T> = std::result::Result<T, UserError>;
+
+const CHALLENGE_BYTES: usize = 32;
+const PUBLIC_KEY_TYPE: &str = "public-key";
+const MAX_CREDENTIAL_ID_LENGTH: usize = 1023;
+const MAX_CREDENTIAL_NAME_LENGTH: usize = 100;
+
+// COSE algorithm identifiers
+const ES256: i32 = -7;
+const RS256: i32 = -257;
+
+// authenticator data flags
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_BACKUP_ELIGIBLE: u8 = 0x08;
+const FLAG_BACKED_UP: u8 = 0x10;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+pub struct WebauthnService {
+    webauthn_repo: WebauthnRepository,
+    mfa_repo: MfaRepository,
+    security_event_repo: SecurityEventRepository,
+    user_service: Arc<UserService>,
+    rp_id: String,
+    rp_name: String,
+    origins: Vec<String>,
+    challenge_ttl: Duration,
+}
+
+impl WebauthnService {
+    pub fn new(db_pool: Arc<PgPool>, user_service: Arc<UserService>, config: &AppConfig) -> Self {
+        assert!(
+            !config.webauthn_origins.is_empty(),
+            "WEBAUTHN_ORIGINS must name at least one origin"
+        );
+
+        Self {
+            webauthn_repo: WebauthnRepository::new(db_pool.clone()),
+            mfa_repo: MfaRepository::new(db_pool.clone()),
+            security_event_repo: SecurityEventRepository::new(db_pool),
+            user_service,
+            rp_id: config.webauthn_rp_id.clone(),
+            rp_name: config.webauthn_rp_name.clone(),
+            origins: config.webauthn_origins.clone(),
+            challenge_ttl: Duration::minutes(config.webauthn_challenge_expiration.max(1) as i64),
+        }
+    }
+
+    // Options for navigator.credentials.create(). Credentials the user
+    // already registered are excluded so an authenticator is not added twice.
+    pub async fn start_registration(&self, user_id: Uuid) -> Result<WebauthnCreationOptionsDto> {
+        let user = self
+            .user_service
+            .get_user(user_id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+        let credentials = self.webauthn_repo.find_credentials(user.id).await?;
+
+        let challenge = self
+            .create_challenge(Some(user.id), WebauthnCeremony::Registration)
+            .await?;
+
+        Ok(WebauthnCreationOptionsDto {
+            challenge,
+            rp: WebauthnRelyingPartyDto {
+                id: self.rp_id.clone(),
+                name: self.rp_name.clone(),
+            },
+            user: WebauthnUserEntityDto {
+                id: URL_SAFE_NO_PAD.encode(user.id.as_bytes()),
+                name: user.email.clone(),
+                display_name: user.username.clone().unwrap_or(user.email),
+            },
+            pub_key_cred_params: [ES256, RS256]
+                .into_iter()
+                .map(|alg| WebauthnCredentialParametersDto {
+                    credential_type: PUBLIC_KEY_TYPE.to_string(),
+                    alg,
+                })
+                .collect(),
+            timeout: self.challenge_ttl.num_milliseconds(),
+            exclude_credentials: credentials.iter().map(credential_descriptor).collect(),
+            authenticator_selection: WebauthnAuthenticatorSelectionDto {
+                // discoverable credentials are what make passkey logins work
+                resident_key: "preferred".to_string(),
+                require_resident_key: false,
+                user_verification: "preferred".to_string(),
+            },
+            attestation: "none".to_string(),
+        })
+    }
+
+    // Verifies the browser's answer to start_registration and stores the
+    // credential. Returns fresh recovery codes as well when this is the
+    // user's first MFA method.
+    pub async fn finish_registration(
+        &self,
+        user_id: Uuid,
+        registration: &WebauthnRegistrationDto,
+        name: Option<&str>,
+        client: &ClientContextDto,
+    ) -> Result<(WebauthnCredentialDto, Option<Vec<String>>)> {
+        if registration.credential_type != PUBLIC_KEY_TYPE {
+            return Err(invalid_response("unsupported credential type"));
+        }
+        let client_data_json = decode(&registration.response.client_data_json)?;
+        let client_data = self.check_client_data(&client_data_json, "webauthn.create")?;
+
+        self.webauthn_repo
+            .consume_challenge(
+                &hash_token(&client_data.challenge),
+                WebauthnCeremony::Registration,
+            )
+            .await?
+            .filter(|challenge| challenge.user_id == Some(user_id))
+            .ok_or_else(|| invalid_response("unknown or expired challenge"))?;
+
+        let attestation =
+            parse_attestation_object(&decode(&registration.response.attestation_object)?)?;
+        let auth_data = parse_authenticator_data(&attestation)?;
+        self.check_authenticator_data(&auth_data, false)?;
+
+        let attested = auth_data
+            .attested_credential
+            .ok_or_else(|| invalid_response("no attested credential data"))?;
+        if URL_SAFE_NO_PAD.encode(&attested.credential_id) != registration.id {
+            return Err(invalid_response("credential id mismatch"));
+        }
+        // rejects keys and algorithms that could not verify assertions later
+        let algorithm = parse_public_key(&attested.public_key)?.algorithm();
+
+        let first_method = !self
+            .mfa_repo
+            .find_verified(user_id)
+            .await?
+            .iter()
+            .any(|method| method.method_type != MfaMethodType::Recovery);
+        let (recovery_codes, recovery_code_hashes) = if first_method {
+            let (codes, hashes) = generate_recovery_codes();
+            (Some(codes), Some(hashes))
+        } else {
+            (None, None)
+        };
+
+        let credential = self
+            .webauthn_repo
+            .create_credential(
+                &WebauthnCredential {
+                    id: Uuid::new_v4(),
+                    user_id,
+                    credential_id: attested.credential_id,
+                    public_key: attested.public_key,
+                    algorithm,
+                    sign_count: auth_data.sign_count as i64,
+                    transports: registration.response.transports.clone(),
+                    aaguid: attested.aaguid,
+                    name: normalize_credential_name(name),
+                    backup_eligible: auth_data.flags & FLAG_BACKUP_ELIGIBLE != 0,
+                    backed_up: auth_data.flags & FLAG_BACKED_UP != 0,
+                    created_at: Utc::now(),
+                    last_used_at: None,
+                },
+                recovery_code_hashes.as_deref(),
+            )
+            .await?;
+
+        info!("WebAuthn credential registered for user {}", user_id);
+        self.record_event(
+            SecurityEventType::PasskeyRegistered,
+            user_id,
+            client,
+            None,
+            json!({ "credential": credential.id, "aaguid": credential.aaguid }),
+        )
+        .await?;
+
+        Ok((credential_dto(credential), recovery_codes))
+    }
+
+    pub async fn list_credentials(&self, user_id: Uuid) -> Result<Vec<WebauthnCredentialDto>> {
+        let credentials = self.webauthn_repo.find_credentials(user_id).await?;
+        Ok(credentials.into_iter().map(credential_dto).collect())
+    }
+
+    // Removes one of the user's credentials. Takes an assertion of any of
+    // their credentials, so a stolen access token is not enough. Removing
+    // the last one removes the webauthn MFA method.
+    pub async fn remove_credential(
+        &self,
+        user_id: Uuid,
+        id: Uuid,
+        assertion: &WebauthnAssertionDto,
+        client: &ClientContextDto,
+    ) -> Result<()> {
+        if self
+            .verify_assertion(assertion, Some(user_id))
+            .await?
+            .is_none()
+        {
+            return Err(invalid_response("assertion was not accepted"));
+        }
+
+        let remaining = self
+            .webauthn_repo
+            .delete_credential(user_id, id)
+            .await?
+            .ok_or(UserError::NotFound)?;
+        if remaining == 0
+            && let Some(method) = self
+                .mfa_repo
+                .find_method(user_id, MfaMethodType::Webauthn)
+                .await?
+        {
+            self.mfa_repo.delete(method.method_id).await?;
+        }
+
+        info!("WebAuthn credential {} removed for user {}", id, user_id);
+        self.record_event(
+            SecurityEventType::PasskeyRemoved,
+            user_id,
+            client,
+            None,
+            json!({ "credential": id }),
+        )
+        .await
+    }
+
+    // Options for navigator.credentials.get(). With a user only their
+    // credentials are allowed; without one this starts a passkey login.
+    pub async fn start_authentication(
+        &self,
+        user_id: Option<Uuid>,
+    ) -> Result<WebauthnRequestOptionsDto> {
+        let allow_credentials = match user_id {
+            Some(user_id) => {
+                let credentials = self.webauthn_repo.find_credentials(user_id).await?;
+                if credentials.is_empty() {
+                    return Err(UserError::NotFound);
+                }
+                credentials.iter().map(credential_descriptor).collect()
+            }
+            None => Vec::new(),
+        };
+
+        let challenge = self
+            .create_challenge(user_id, WebauthnCeremony::Authentication)
+            .await?;
+
+        Ok(WebauthnRequestOptionsDto {
+            challenge,
+            timeout: self.challenge_ttl.num_milliseconds(),
+            rp_id: self.rp_id.clone(),
+            allow_credentials,
+            user_verification: if user_id.is_some() {
+                "preferred"
+            } else {
+                "required"
+            }
+            .to_string(),
+        })
+    }
+
+    // Checks a passkey login and returns the user it signs in. Failures are
+    // reported as invalid credentials.
+    pub async fn verify_passkey(
+        &self,
+        assertion: &WebauthnAssertionDto,
+        client: &ClientContextDto,
+    ) -> Result<Uuid> {
+        let Some(credential) = self.verify_assertion(assertion, None).await? else {
+            return Err(UserError::InvalidCredentials);
+        };
+
+        self.record_event(
+            SecurityEventType::PasskeyLogin,
+            credential.user_id,
+            client,
+            None,
+            json!({ "credential": credential.id }),
+        )
+        .await?;
+        Ok(credential.user_id)
+    }
+
+    // Checks an assertion against the challenge it answers and returns the
+    // credential that signed it, or None when it does not verify. With
+    // `user_id` the credential must be theirs; without one this is a
+    // passkey login and user verification is required.
+    pub async fn verify_assertion(
+        &self,
+        assertion: &WebauthnAssertionDto,
+        user_id: Option<Uuid>,
+    ) -> Result<Option<WebauthnCredential>> {
+        if assertion.credential_type != PUBLIC_KEY_TYPE {
+            return Err(invalid_response("unsupported credential type"));
+        }
+        let client_data_json = decode(&assertion.response.client_data_json)?;
+        let client_data = self.check_client_data(&client_data_json, "webauthn.get")?;
+        let raw_auth_data = decode(&assertion.response.authenticator_data)?;
+        let signature = decode(&assertion.response.signature)?;
+
+        // the challenge is used up whatever the outcome
+        let Some(challenge) = self
+            .webauthn_repo
+            .consume_challenge(
+                &hash_token(&client_data.challenge),
+                WebauthnCeremony::Authentication,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let Some(credential) = self
+            .webauthn_repo
+            .find_by_credential_id(&decode(&assertion.id)?)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let owner_matches =
+            |expected: Option<Uuid>| expected.is_none_or(|expected| expected == credential.user_id);
+        if !owner_matches(user_id) || !owner_matches(challenge.user_id) {
+            return Ok(None);
+        }
+        if let Some(user_handle) = &assertion.response.user_handle
+            && decode(user_handle)? != credential.user_id.as_bytes()
+        {
+            return Ok(None);
+        }
+
+        let auth_data = parse_authenticator_data(&raw_auth_data)?;
+        self.check_authenticator_data(&auth_data, user_id.is_none())?;
+
+        let mut signed = raw_auth_data;
+        signed.extend_from_slice(&Sha256::digest(&client_data_json));
+        if !parse_public_key(&credential.public_key)?.verify(&signed, &signature) {
+            return Ok(None);
+        }
+
+        if !self
+            .webauthn_repo
+            .record_use(
+                credential.id,
+                auth_data.sign_count as i64,
+                auth_data.flags & FLAG_BACKED_UP != 0,
+            )
+            .await?
+        {
+            warn!(
+                "Signature counter of WebAuthn credential {} went backwards, possibly cloned",
+                credential.id
+            );
+            return Ok(None);
+        }
+
+        Ok(Some(credential))
+    }
+
+    async fn create_challenge(
+        &self,
+        user_id: Option<Uuid>,
+        ceremony: WebauthnCeremony,
+    ) -> Result<String> {
+        let mut bytes = [0u8; CHALLENGE_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        let challenge = URL_SAFE_NO_PAD.encode(bytes);
+
+        self.webauthn_repo
+            .create_challenge(&WebauthnChallenge {
+                challenge_id: Uuid::new_v4(),
+                user_id,
+                challenge_hash: hash_token(&challenge),
+                ceremony,
+                expires_at: Utc::now() + self.challenge_ttl,
+            })
+            .await?;
+
+        Ok(challenge)
+    }
+
+    fn check_client_data(
+        &self,
+        client_data_json: &[u8],
+        ceremony_type: &str,
+    ) -> Result<ClientData> {
+        let client_data: ClientData = serde_json::from_slice(client_data_json)
+            .map_err(|_| invalid_response("malformed client data"))?;
+
+        if client_data.ceremony_type != ceremony_type {
+            return Err(invalid_response("wrong ceremony type"));
+        }
+        if !self.origins.contains(&client_data.origin) {
+            return Err(invalid_response("origin not allowed"));
+        }
+        Ok(client_data)
+    }
+
+    fn check_authenticator_data(
+        &self,
+        auth_data: &AuthenticatorData,
+        require_user_verification: bool,
+    ) -> Result<()> {
+        if auth_data.rp_id_hash[..] != Sha256::digest(self.rp_id.as_bytes())[..] {
+            return Err(invalid_response(
+                "credential belongs to another relying party",
+            ));
+        }
+        if auth_data.flags & FLAG_USER_PRESENT == 0 {
+            return Err(invalid_response("user presence not confirmed"));
+        }
+        if require_user_verification && auth_data.flags & FLAG_USER_VERIFIED == 0 {
+            return Err(invalid_response("user verification required"));
+        }
+        Ok(())
+    }
+
+    async fn record_event(
+        &self,
+        event_type: SecurityEventType,
+        user_id: Uuid,
+        client: &ClientContextDto,
+        failure_reason: Option<&str>,
+        metadata: Value,
+    ) -> Result<()> {
+        self.security_event_repo
+            .create(&SecurityEvent {
+                event_type,
+                user_id: Some(user_id),
+                ip_address: client.ip_address,
+                user_agent: client.user_agent.clone(),
+                device_identifier: client.device_identifier.clone(),
+                success: failure_reason.is_none(),
+                failure_reason: failure_reason.map(str::to_string),
+                metadata: Some(metadata),
+            })
+            .await
+    }
+}
+
+// CollectedClientData, as far as it is checked
+#[derive(Debug, Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+}
+
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    attested_credential: Option<AttestedCredential>,
+}
+
+struct AttestedCredential {
+    aaguid: Uuid,
+    credential_id: Vec<u8>,
+    // COSE_Key
+    public_key: Vec<u8>,
+}
+
+enum PublicKey {
+    Es256(p256::ecdsa::VerifyingKey),
+    Rs256(rsa::pkcs1v15::VerifyingKey<Sha256>),
+}
+
+impl PublicKey {
+    fn algorithm(&self) -> i32 {
+        match self {
+            PublicKey::Es256(_) => ES256,
+            PublicKey::Rs256(_) => RS256,
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            PublicKey::Es256(key) => p256::ecdsa::Signature::from_der(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            PublicKey::Rs256(key) => rsa::pkcs1v15::Signature::try_from(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+        }
+    }
+}
+
+fn invalid_response(reason: &str) -> UserError {
+    UserError::InvalidWebauthnResponse(reason.to_string())
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| invalid_response("malformed base64url value"))
+}
+
+fn credential_descriptor(credential: &WebauthnCredential) -> WebauthnCredentialDescriptorDto {
+    WebauthnCredentialDescriptorDto {
+        credential_type: PUBLIC_KEY_TYPE.to_string(),
+        id: URL_SAFE_NO_PAD.encode(&credential.credential_id),
+        transports: credential.transports.clone(),
+    }
+}
+
+fn credential_dto(credential: WebauthnCredential) -> WebauthnCredentialDto {
+    WebauthnCredentialDto {
+        id: credential.id,
+        name: credential.name,
+        aaguid: credential.aaguid,
+        transports: credential.transports,
+        backup_eligible: credential.backup_eligible,
+        backed_up: credential.backed_up,
+        created_at: credential.created_at,
+        last_used_at: credential.last_used_at,
+    }
+}
+
+fn normalize_credential_name(name: Option<&str>) -> Option<String> {
+    name.map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| name.chars().take(MAX_CREDENTIAL_NAME_LENGTH).collect())
+}
+
+// Returns authData of an attestation object. The attestation statement is
+// not checked, see the module comment.
+fn parse_attestation_object(attestation_object: &[u8]) -> Result<Vec<u8>> {
+    let value: CborValue = ciborium::from_reader(attestation_object)
+        .map_err(|_| invalid_response("malformed attestation object"))?;
+
+    value
+        .as_map()
+        .and_then(|entries| {
+            entries
+                .iter()
+                .find(|(key, _)| key.as_text() == Some("authData"))
+        })
+        .and_then(|(_, auth_data)| auth_data.as_bytes())
+        .cloned()
+        .ok_or_else(|| invalid_response("attestation object has no authData"))
+}
+
+// rpIdHash (32) | flags (1) | signCount (4) | [attestedCredentialData]
+fn parse_authenticator_data(data: &[u8]) -> Result<AuthenticatorData> {
+    let malformed = || invalid_response("malformed authenticator data");
+    if data.len() < 37 {
+        return Err(malformed());
+    }
+
+    let mut rp_id_hash = [0u8; 32];
+    rp_id_hash.copy_from_slice(&data[..32]);
+    let flags = data[32];
+    let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+    // aaguid (16) | credentialIdLength (2) | credentialId | COSE_Key
+    let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+        let rest = &data[37..];
+        if rest.len() < 18 {
+            return Err(malformed());
+        }
+        let aaguid = Uuid::from_slice(&rest[..16]).map_err(|_| malformed())?;
+        let id_length = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+        if id_length > MAX_CREDENTIAL_ID_LENGTH || rest.len() < 18 + id_length {
+            return Err(malformed());
+        }
+        let credential_id = rest[18..18 + id_length].to_vec();
+
+        // the key is followed by extensions, if any
+        let key_bytes = &rest[18 + id_length..];
+        let mut reader = key_bytes;
+        let _: CborValue = ciborium::from_reader(&mut reader).map_err(|_| malformed())?;
+        let key_length = key_bytes.len() - reader.len();
+
+        Some(AttestedCredential {
+            aaguid,
+            credential_id,
+            public_key: key_bytes[..key_length].to_vec(),
+        })
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData {
+        rp_id_hash,
+        flags,
+        sign_count,
+        attested_credential,
+    })
+}
+
+// Reads an EC2 P-256 (ES256) or RSA (RS256) COSE_Key (RFC 9053)
+fn parse_public_key(cose_key: &[u8]) -> Result<PublicKey> {
+    let unsupported = || invalid_response("unsupported public key");
+    let value: CborValue = ciborium::from_reader(cose_key).map_err(|_| unsupported())?;
+    let entries = value.as_map().ok_or_else(unsupported)?;
+
+    let field = |label: i64| {
+        entries.iter().find_map(|(key, value)| {
+            key.as_integer()
+                .filter(|key| i128::from(*key) == label as i128)
+                .map(|_| value)
+        })
+    };
+    let integer = |label: i64| field(label).and_then(CborValue::as_integer).map(i128::from);
+    let bytes = |label: i64| field(label).and_then(CborValue::as_bytes);
+
+    // kty: 2 = EC2, 3 = RSA
+    match (integer(1), integer(3)) {
+        (Some(2), Some(alg)) if alg == ES256 as i128 => {
+            // crv 1 = P-256
+            if integer(-1) != Some(1) {
+                return Err(unsupported());
+            }
+            let (Some(x), Some(y)) = (bytes(-2), bytes(-3)) else {
+                return Err(unsupported());
+            };
+            if x.len() != 32 || y.len() != 32 {
+                return Err(unsupported());
+            }
+            let point = p256::EncodedPoint::from_affine_coordinates(
+                p256::FieldBytes::from_slice(x),
+                p256::FieldBytes::from_slice(y),
+                false,
+            );
+            let key =
+                p256::ecdsa::VerifyingKey::from_encoded_point(&point).map_err(|_| unsupported())?;
+            Ok(PublicKey::Es256(key))
+        }
+        (Some(3), Some(alg)) if alg == RS256 as i128 => {
+            let (Some(n), Some(e)) = (bytes(-1), bytes(-2)) else {
+                return Err(unsupported());
+            };
+            let key = rsa::RsaPublicKey::new(
+                rsa::BigUint::from_bytes_be(n),
+                rsa::BigUint::from_bytes_be(e),
+            )
+            .map_err(|_| unsupported())?;
+            Ok(PublicKey::Rs256(rsa::pkcs1v15::VerifyingKey::new(key)))
+        }
+        _ => Err(unsupported()),
+    }
+}
@@ -3,6 +3,8 @@ mod app_modules;
 mod config;
 mod domain;
 mod server;
+#[cfg(test)]
+mod test_support;
 
 use std::net::TcpListener;
 
@@ -2,19 +2,20 @@
 
 use actix_web::{
     App, HttpResponse, HttpServer,
-    middleware::{Logger, NormalizePath},
+    middleware::{Logger, NormalizePath, from_fn},
     web,
 };
 
 use actix_cors::Cors;
 use std::net::TcpListener;
+use std::sync::Arc;
 use tracing_actix_web::TracingLogger;
 
-use serde_json::json;
-use tracing_subscriber;
-
 use crate::app_modules::app_state::AppState;
+use crate::app_modules::middleware::request_id::request_id;
+use crate::config::app_config::get_config;
 use crate::config::database::PgPool;
+use crate::domain::errors::UserError;
 
 use crate::app_modules::api::api_routes;
 
@@ -30,7 +31,11 @@ impl WebServer {
 
     pub async fn run(self) -> Result<(), std::io::Error> {
         // Create application state
-        let app_state = web::Data::new(AppState::new(self.db_pool.clone()));
+        let config = get_config().await;
+        let app_state = web::Data::new(AppState::new(self.db_pool.clone(), config));
+
+        // Deliver queued emails in the background
+        tokio::spawn(Arc::clone(&app_state.email_outbox_service).run());
 
         // Initialize tracing/logging
         tracing_subscriber::fmt::init();
@@ -39,6 +44,7 @@ impl WebServer {
         HttpServer::new(move || {
             App::new()
                 // Middleware
+                .wrap(from_fn(request_id))
                 .wrap(Logger::default())
                 .wrap(TracingLogger::default())
                 .wrap(Cors::default())
@@ -48,12 +54,9 @@ impl WebServer {
                 // Configure routes
                 .configure(api_routes)
                 // Fallback handler
-                .default_service(web::route().to(|| async {
-                    HttpResponse::NotFound().json(json!({
-                        "error": "Not Found",
-                        "message": "The requested resource could not be found"
-                    }))
-                }))
+                .default_service(
+                    web::route().to(|| async { Err::<HttpResponse, _>(UserError::NotFound) }),
+                )
         })
         .listen(self.listener)?
         .run()
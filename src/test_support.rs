@@ -0,0 +1,442 @@
+/*
+Shared setup for tests that need a database.
+
+TEST_DATABASE_URL points at a Postgres server the tests may create
+databases on, e.g. postgres://postgres@localhost:5432/postgres. Each test
+process creates a fresh database from it, applies migrations/versions and
+runs every test against it; tests keep apart by using their own users.
+Tests needing the database are marked #[ignore] so that a plain cargo test
+reports them as ignored rather than passing them untried; run them with
+cargo test -- --include-ignored. They fail when TEST_DATABASE_URL is not
+set.
+
+The app is built with capturing adapters, so tests can read the mail and
+texts it sent. They are shared by the whole process, since any test's
+outbox run may deliver another test's mail; tests look for messages to
+their own addresses and numbers.
+
+SoftAuthenticator stands in for a browser and a platform authenticator in
+WebAuthn ceremonies, with an ES256 key held in memory.
+*/
+use std::sync::{Arc, LazyLock};
+
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::{App, web};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use bb8::Pool;
+use bb8_postgres::PostgresConnectionManager;
+use ciborium::Value as CborValue;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+use tokio_postgres::NoTls;
+use uuid::Uuid;
+
+use crate::adapters::email_transports::MemoryEmailTransport;
+use crate::adapters::sms_senders::MemorySmsSender;
+use crate::app_modules::api::api_routes;
+use crate::app_modules::app_state::AppState;
+use crate::config::app_config::AppConfig;
+use crate::config::database::PgPool;
+use crate::domain::models::{EmailMessage, SmsMessage};
+
+pub const TEST_PASSWORD: &str = "Correct-Horse-Battery-9";
+pub const TEST_ORIGIN: &str = "https://app.gandalf.test";
+pub const TEST_RP_ID: &str = "gandalf.test";
+
+// connection settings of this process's database, created on first use
+static TEST_DATABASE: OnceCell<tokio_postgres::Config> = OnceCell::const_new();
+
+static EMAILS: LazyLock<Arc<MemoryEmailTransport>> =
+    LazyLock::new(|| Arc::new(MemoryEmailTransport::new()));
+static TEXTS: LazyLock<Arc<MemorySmsSender>> = LazyLock::new(|| Arc::new(MemorySmsSender::new()));
+
+pub fn test_config() -> AppConfig {
+    AppConfig::from_pairs(&[
+        ("JWT_SECRET", "test secret, not for production use"),
+        // 32 zero bytes
+        (
+            "MFA_ENCRYPTION_KEY",
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+        ),
+        ("ARGON2_MEMORY_COST", "1024"),
+        ("ARGON2_TIME_COST", "1"),
+        ("EMAIL_TRANSPORT", "memory"),
+        ("SMS_SENDER", "memory"),
+        (
+            "EMAIL_TEMPLATES_DIR",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/templates/email"),
+        ),
+        ("APP_BASE_URL", "https://app.gandalf.test"),
+        ("WEBAUTHN_RP_ID", TEST_RP_ID),
+        ("WEBAUTHN_ORIGINS", TEST_ORIGIN),
+    ])
+}
+
+// A pool that never connects, for services whose tested paths do not
+// reach the database
+pub fn unconnected_pool() -> Arc<PgPool> {
+    let manager =
+        PostgresConnectionManager::new("postgres://localhost/unused".parse().unwrap(), NoTls);
+    Arc::new(Pool::builder().build_unchecked(manager))
+}
+
+// A fresh email address on a domain no tenant owns
+pub fn unique_email() -> String {
+    format!("user-{}@example.com", Uuid::new_v4().simple())
+}
+
+// A fresh phone number in international format
+pub fn unique_phone_number() -> String {
+    format!("+1555{:07}", Uuid::new_v4().as_u128() % 10_000_000)
+}
+
+// The first run of digits of the given length in `text`, e.g. a code
+pub fn find_code(text: &str, digits: usize) -> Option<String> {
+    text.split(|c: char| !c.is_ascii_digit())
+        .find(|run| run.len() == digits)
+        .map(str::to_string)
+}
+
+pub struct TestApp {
+    pub state: web::Data<AppState>,
+    pub pool: PgPool,
+}
+
+impl TestApp {
+    pub async fn start() -> TestApp {
+        let db_config = TEST_DATABASE.get_or_init(create_database).await;
+
+        let manager = PostgresConnectionManager::new(db_config.clone(), NoTls);
+        let pool = Pool::builder()
+            .max_size(4)
+            .build(manager)
+            .await
+            .expect("test database is reachable");
+
+        let state = web::Data::new(AppState::with_adapters(
+            pool.clone(),
+            &test_config(),
+            EMAILS.clone(),
+            TEXTS.clone(),
+        ));
+
+        TestApp { state, pool }
+    }
+
+    // Sends the request through the API and returns the status and the
+    // JSON body (Null when empty)
+    pub async fn call(&self, request: TestRequest) -> (StatusCode, Value) {
+        let service = test::init_service(
+            App::new()
+                .app_data(self.state.clone())
+                .configure(api_routes),
+        )
+        .await;
+        let response = test::call_service(&service, request.to_request()).await;
+        let status = response.status();
+        let body = test::read_body(response).await;
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&body).expect("responses are JSON")
+        };
+        (status, body)
+    }
+
+    pub async fn post(&self, path: &str, bearer: Option<&str>, body: Value) -> (StatusCode, Value) {
+        let mut request = TestRequest::post().uri(path).set_json(body);
+        if let Some(token) = bearer {
+            request = request.insert_header(("Authorization", format!("Bearer {}", token)));
+        }
+        self.call(request).await
+    }
+
+    pub async fn get(&self, path: &str, bearer: Option<&str>) -> (StatusCode, Value) {
+        let mut request = TestRequest::get().uri(path);
+        if let Some(token) = bearer {
+            request = request.insert_header(("Authorization", format!("Bearer {}", token)));
+        }
+        self.call(request).await
+    }
+
+    // Registers an account with TEST_PASSWORD and returns its id
+    pub async fn register(&self, email: &str) -> Uuid {
+        let (status, body) = self
+            .post(
+                "/api/v1/users/register",
+                None,
+                json!({ "email": email, "password": TEST_PASSWORD }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["user"]["id"]
+            .as_str()
+            .and_then(|id| id.parse().ok())
+            .expect("registration returns the user id")
+    }
+
+    // Signs in with TEST_PASSWORD and returns the login response
+    pub async fn login(&self, email: &str) -> (StatusCode, Value) {
+        self.post(
+            "/api/v1/auth/login",
+            None,
+            json!({ "email": email, "password": TEST_PASSWORD }),
+        )
+        .await
+    }
+
+    // Registers an account and signs in, returning its id and access token
+    pub async fn signed_in_user(&self) -> (Uuid, String) {
+        let email = unique_email();
+        let user_id = self.register(&email).await;
+        let (status, body) = self.login(&email).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let access_token = body["access_token"]
+            .as_str()
+            .expect("login without MFA returns tokens")
+            .to_string();
+        (user_id, access_token)
+    }
+
+    // A tenant owning a fresh domain; returns its id and the domain
+    pub async fn create_tenant(&self) -> (Uuid, String) {
+        let domain = format!("{}.school.test", Uuid::new_v4().simple());
+        let tenant_id = self
+            .pool
+            .get()
+            .await
+            .unwrap()
+            .query_one(
+                "INSERT INTO auth.education_tenants (tenant_name, domain)
+                 VALUES ($1, $1) RETURNING tenant_id",
+                &[&domain],
+            )
+            .await
+            .unwrap()
+            .get("tenant_id");
+        (tenant_id, domain)
+    }
+
+    // A new role granting `permission`, assigned to the user in the tenant,
+    // or globally without one
+    pub async fn grant(&self, user_id: Uuid, permission: &str, tenant_id: Option<Uuid>) -> Uuid {
+        let conn = self.pool.get().await.unwrap();
+        let role_id: Uuid = conn
+            .query_one(
+                "INSERT INTO auth.roles (role_name) VALUES ($1) RETURNING id",
+                &[&format!("role_{}", Uuid::new_v4().simple())],
+            )
+            .await
+            .unwrap()
+            .get("id");
+        conn.execute(
+            "INSERT INTO auth.role_permissions (role_id, permission) VALUES ($1, $2)",
+            &[&role_id, &permission],
+        )
+        .await
+        .unwrap();
+        conn.execute(
+            "INSERT INTO auth.user_roles (user_id, role_id, tenant_id) VALUES ($1, $2, $3)",
+            &[&user_id, &role_id, &tenant_id],
+        )
+        .await
+        .unwrap();
+        role_id
+    }
+
+    pub fn emails_to(&self, address: &str) -> Vec<EmailMessage> {
+        EMAILS
+            .sent_messages()
+            .into_iter()
+            .filter(|message| message.to.contains(address))
+            .collect()
+    }
+
+    pub fn texts_to(&self, phone_number: &str) -> Vec<SmsMessage> {
+        TEXTS
+            .sent_messages()
+            .into_iter()
+            .filter(|message| message.to == phone_number)
+            .collect()
+    }
+}
+
+async fn create_database() -> tokio_postgres::Config {
+    let url = std::env::var("TEST_DATABASE_URL")
+        .expect("TEST_DATABASE_URL points at a Postgres server for tests needing a database");
+    let mut db_config: tokio_postgres::Config = url.parse().expect("TEST_DATABASE_URL is valid");
+
+    let (admin, connection) = db_config
+        .connect(NoTls)
+        .await
+        .expect("TEST_DATABASE_URL is reachable");
+    tokio::spawn(connection);
+
+    let name = format!("gandalf_test_{}", std::process::id());
+    // each its own statement, since DROP and CREATE DATABASE refuse to run
+    // in the implicit transaction of a multi-statement batch
+    for statement in [
+        format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)"),
+        format!("CREATE DATABASE {name}"),
+        "DO $$ BEGIN
+            CREATE ROLE talkio_auth_service;
+        EXCEPTION WHEN duplicate_object THEN NULL;
+        END $$"
+            .to_string(),
+    ] {
+        admin
+            .batch_execute(&statement)
+            .await
+            .expect("test database can be created");
+    }
+
+    db_config.dbname(&name);
+    let (client, connection) = db_config
+        .connect(NoTls)
+        .await
+        .expect("test database is reachable");
+    tokio::spawn(connection);
+
+    let mut migrations: Vec<_> =
+        std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations/versions"))
+            .expect("migrations directory exists")
+            .map(|entry| entry.expect("migration is readable").path())
+            .collect();
+    migrations.sort();
+    for path in migrations {
+        let sql = std::fs::read_to_string(&path).expect("migration is readable");
+        client
+            .batch_execute(&sql)
+            .await
+            .unwrap_or_else(|e| panic!("{} failed: {}", path.display(), e));
+    }
+
+    db_config
+}
+
+// A software authenticator holding a single ES256 credential. Its fields
+// can be changed between ceremonies to produce answers a real
+// authenticator would not.
+pub struct SoftAuthenticator {
+    key: SigningKey,
+    credential_id: Vec<u8>,
+    user_handle: Option<String>,
+    pub sign_count: u32,
+    pub origin: String,
+    pub rp_id: String,
+    pub user_verified: bool,
+}
+
+impl SoftAuthenticator {
+    pub fn new() -> Self {
+        let mut credential_id = vec![0u8; 16];
+        OsRng.fill_bytes(&mut credential_id);
+        Self {
+            key: SigningKey::random(&mut OsRng),
+            credential_id,
+            user_handle: None,
+            sign_count: 0,
+            origin: TEST_ORIGIN.to_string(),
+            rp_id: TEST_RP_ID.to_string(),
+            user_verified: true,
+        }
+    }
+
+    // The result of navigator.credentials.create() for creation options
+    pub fn create(&mut self, options: &Value) -> Value {
+        self.user_handle = options["user"]["id"].as_str().map(str::to_string);
+        let client_data = self.client_data("webauthn.create", options);
+
+        // aaguid (16) | credentialIdLength (2) | credentialId | COSE_Key
+        let mut auth_data = self.authenticator_data(0x40);
+        auth_data.extend_from_slice(&[0u8; 16]);
+        auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        auth_data.extend(self.cose_key());
+
+        let attestation_object = cbor(CborValue::Map(vec![
+            ("fmt".into(), "none".into()),
+            ("attStmt".into(), CborValue::Map(Vec::new())),
+            ("authData".into(), CborValue::Bytes(auth_data)),
+        ]));
+
+        json!({
+            "id": URL_SAFE_NO_PAD.encode(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object),
+                "transports": ["internal"],
+            },
+        })
+    }
+
+    // The result of navigator.credentials.get() for request options
+    pub fn get(&mut self, options: &Value) -> Value {
+        self.sign_count += 1;
+        let client_data = self.client_data("webauthn.get", options);
+        let auth_data = self.authenticator_data(0);
+
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data));
+        let signature: Signature = self.key.sign(&signed);
+
+        json!({
+            "id": URL_SAFE_NO_PAD.encode(&self.credential_id),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "authenticatorData": URL_SAFE_NO_PAD.encode(auth_data),
+                "signature": URL_SAFE_NO_PAD.encode(signature.to_der()),
+                "userHandle": self.user_handle,
+            },
+        })
+    }
+
+    fn client_data(&self, ceremony_type: &str, options: &Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "type": ceremony_type,
+            "challenge": options["challenge"],
+            "origin": self.origin,
+            "crossOrigin": false,
+        }))
+        .unwrap()
+    }
+
+    // rpIdHash (32) | flags (1) | signCount (4), user presence always set
+    fn authenticator_data(&self, flags: u8) -> Vec<u8> {
+        let mut flags = flags | 0x01;
+        if self.user_verified {
+            flags |= 0x04;
+        }
+        let mut data = Sha256::digest(self.rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&self.sign_count.to_be_bytes());
+        data
+    }
+
+    // EC2 P-256 COSE_Key of the credential
+    fn cose_key(&self) -> Vec<u8> {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        cbor(CborValue::Map(vec![
+            (1.into(), 2.into()),
+            (3.into(), (-7).into()),
+            ((-1).into(), 1.into()),
+            ((-2).into(), CborValue::Bytes(point.x().unwrap().to_vec())),
+            ((-3).into(), CborValue::Bytes(point.y().unwrap().to_vec())),
+        ]))
+    }
+}
+
+fn cbor(value: CborValue) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&value, &mut bytes).unwrap();
+    bytes
+}